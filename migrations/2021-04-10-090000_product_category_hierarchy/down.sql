@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+drop index product_category_parent_id_idx;
+alter table product_category
+    drop constraint product_category_not_own_parent,
+    drop column parent_id;
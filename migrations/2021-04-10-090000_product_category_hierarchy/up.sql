@@ -0,0 +1,6 @@
+-- Your SQL goes here
+alter table product_category
+    add column parent_id int references product_category(id),
+    add constraint product_category_not_own_parent check (parent_id <> id);
+
+create index product_category_parent_id_idx on product_category(parent_id);
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+create table product (
+    id serial primary key,
+    sku varchar unique not null,
+    name varchar not null,
+    description varchar not null default '',
+    unit_of_measure varchar not null default 'each',
+    length_mm int check (length_mm > 0),
+    width_mm int check (width_mm > 0),
+    height_mm int check (height_mm > 0),
+    weight_g int check (weight_g > 0),
+    version int not null default 0
+);
+
+create table product_category_assignment (
+    product_id int not null references product(id) on delete cascade,
+    category_id int not null references product_category(id),
+    primary key (product_id, category_id)
+);
+
+create index product_category_assignment_category_id_idx on product_category_assignment(category_id);
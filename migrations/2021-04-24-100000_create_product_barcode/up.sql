@@ -0,0 +1,10 @@
+-- Your SQL goes here
+create table product_barcode (
+    id serial primary key,
+    product_id int not null references product(id) on delete cascade,
+    code varchar not null,
+    gtin varchar(14) unique not null,
+    symbology varchar not null
+);
+
+create index product_barcode_product_id_idx on product_barcode(product_id);
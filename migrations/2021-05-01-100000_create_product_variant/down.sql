@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+drop table product_variant_option;
+drop table product_variant_axis;
+drop index product_parent_id_idx;
+alter table product drop column parent_id;
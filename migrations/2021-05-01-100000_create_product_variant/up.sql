@@ -0,0 +1,19 @@
+-- Your SQL goes here
+alter table product add column parent_id int references product(id) on delete cascade;
+
+create index product_parent_id_idx on product(parent_id);
+
+create table product_variant_axis (
+    id serial primary key,
+    product_id int not null references product(id) on delete cascade,
+    name varchar not null,
+    position int not null,
+    unique (product_id, name)
+);
+
+create table product_variant_option (
+    variant_id int not null references product(id) on delete cascade,
+    axis_id int not null references product_variant_axis(id) on delete cascade,
+    value varchar not null,
+    primary key (variant_id, axis_id)
+);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+create table product_packaging (
+    id serial primary key,
+    product_id int not null references product(id) on delete cascade,
+    name varchar not null,
+    quantity int not null check (quantity > 0),
+    contained_packaging_id int references product_packaging(id),
+    unique (product_id, name)
+);
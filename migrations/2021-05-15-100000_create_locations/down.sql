@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+drop table bin_location;
+drop table warehouse_aisle;
+drop table warehouse_zone;
+drop table warehouse;
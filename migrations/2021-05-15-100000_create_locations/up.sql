@@ -0,0 +1,42 @@
+-- Your SQL goes here
+create table warehouse (
+    id serial primary key,
+    code varchar unique not null,
+    name varchar not null,
+    version int not null default 0
+);
+
+create table warehouse_zone (
+    id serial primary key,
+    warehouse_id int not null references warehouse(id),
+    code varchar not null,
+    name varchar not null,
+    version int not null default 0,
+    unique (warehouse_id, code)
+);
+
+create table warehouse_aisle (
+    id serial primary key,
+    zone_id int not null references warehouse_zone(id),
+    code varchar not null,
+    sequence int not null default 0,
+    unique (zone_id, code)
+);
+
+create table bin_location (
+    id serial primary key,
+    warehouse_id int not null references warehouse(id),
+    zone_id int not null references warehouse_zone(id),
+    aisle_id int not null references warehouse_aisle(id),
+    code varchar not null,
+    location_code varchar unique not null,
+    location_type varchar not null,
+    max_units int check (max_units > 0),
+    max_volume_cm3 bigint check (max_volume_cm3 > 0),
+    max_weight_g bigint check (max_weight_g > 0),
+    version int not null default 0,
+    unique (aisle_id, code)
+);
+
+create index bin_location_warehouse_id_idx on bin_location(warehouse_id);
+create index bin_location_zone_id_idx on bin_location(zone_id);
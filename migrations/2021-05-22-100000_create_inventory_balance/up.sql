@@ -0,0 +1,15 @@
+-- Your SQL goes here
+create table inventory_balance (
+    id serial primary key,
+    product_id int not null references product(id),
+    bin_location_id int not null references bin_location(id),
+    warehouse_id int not null references warehouse(id),
+    on_hand int not null default 0 check (on_hand >= 0),
+    allocated int not null default 0 check (allocated >= 0),
+    version int not null default 0,
+    check (allocated <= on_hand),
+    unique (product_id, bin_location_id)
+);
+
+create index inventory_balance_bin_location_id_idx on inventory_balance(bin_location_id);
+create index inventory_balance_warehouse_id_idx on inventory_balance(warehouse_id);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+drop table stock_movement;
+drop function stock_movement_is_append_only();
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+create table stock_movement (
+    id serial primary key,
+    product_id int not null references product(id),
+    from_location_id int references bin_location(id),
+    to_location_id int references bin_location(id),
+    quantity int not null check (quantity > 0),
+    reason varchar not null,
+    user_name varchar not null,
+    reference varchar,
+    created_at timestamp not null default now(),
+    check (from_location_id is not null or to_location_id is not null)
+);
+
+create index stock_movement_product_id_idx on stock_movement(product_id);
+create index stock_movement_from_location_id_idx on stock_movement(from_location_id);
+create index stock_movement_to_location_id_idx on stock_movement(to_location_id);
+
+create function stock_movement_is_append_only() returns trigger as $$
+begin
+    raise exception 'stock movements are append only';
+end;
+$$ language plpgsql;
+
+create trigger stock_movement_append_only before update or delete on stock_movement
+    for each row execute procedure stock_movement_is_append_only();
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+create table stock_transfer (
+    id serial primary key,
+    product_id int not null references product(id),
+    from_warehouse_id int not null references warehouse(id),
+    from_location_id int not null references bin_location(id),
+    to_warehouse_id int not null references warehouse(id),
+    to_location_id int references bin_location(id),
+    quantity int not null check (quantity > 0),
+    status varchar not null default 'in_transit',
+    shipped_by varchar not null,
+    shipped_at timestamp not null default now(),
+    received_by varchar,
+    received_at timestamp,
+    check (from_warehouse_id <> to_warehouse_id)
+);
+
+create index stock_transfer_product_id_idx on stock_transfer(product_id);
+create index stock_transfer_in_transit_idx on stock_transfer(to_warehouse_id) where status = 'in_transit';
@@ -0,0 +1,10 @@
+-- This file should undo anything in `up.sql`
+alter table stock_transfer drop column lot_id;
+alter table stock_movement drop column lot_id;
+
+drop index inventory_balance_product_bin_lot_key;
+alter table inventory_balance drop column lot_id;
+alter table inventory_balance add constraint inventory_balance_product_id_bin_location_id_key
+    unique (product_id, bin_location_id);
+
+drop table product_lot;
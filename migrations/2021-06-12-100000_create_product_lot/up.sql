@@ -0,0 +1,20 @@
+-- Your SQL goes here
+create table product_lot (
+    id serial primary key,
+    product_id int not null references product(id),
+    lot_number varchar not null,
+    manufactured_on date,
+    expires_on date,
+    unique (product_id, lot_number),
+    check (expires_on is null or manufactured_on is null or expires_on >= manufactured_on)
+);
+
+create index product_lot_expires_on_idx on product_lot(expires_on);
+
+alter table inventory_balance add column lot_id int references product_lot(id);
+alter table inventory_balance drop constraint inventory_balance_product_id_bin_location_id_key;
+create unique index inventory_balance_product_bin_lot_key
+    on inventory_balance(product_id, bin_location_id, coalesce(lot_id, 0));
+
+alter table stock_movement add column lot_id int references product_lot(id);
+alter table stock_transfer add column lot_id int references product_lot(id);
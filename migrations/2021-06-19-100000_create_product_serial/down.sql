@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table stock_movement_serial;
+drop table product_serial;
+alter table product drop column serialised;
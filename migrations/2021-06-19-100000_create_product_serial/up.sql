@@ -0,0 +1,20 @@
+-- Your SQL goes here
+alter table product add column serialised boolean not null default false;
+
+create table product_serial (
+    id serial primary key,
+    product_id int not null references product(id),
+    serial_number varchar not null,
+    bin_location_id int references bin_location(id),
+    unique (product_id, serial_number)
+);
+
+create index product_serial_bin_location_id_idx on product_serial(bin_location_id);
+
+create table stock_movement_serial (
+    movement_id int not null references stock_movement(id),
+    serial_id int not null references product_serial(id),
+    primary key (movement_id, serial_id)
+);
+
+create index stock_movement_serial_serial_id_idx on stock_movement_serial(serial_id);
@@ -0,0 +1,24 @@
+-- Your SQL goes here
+create table stock_reservation (
+    id serial primary key,
+    product_id int not null references product(id),
+    warehouse_id int not null references warehouse(id),
+    quantity int not null check (quantity > 0),
+    reference varchar,
+    status varchar not null default 'active',
+    created_by varchar not null,
+    created_at timestamp not null default now(),
+    expires_at timestamp not null
+);
+
+create index stock_reservation_active_idx on stock_reservation(expires_at) where status = 'active';
+
+create table stock_reservation_line (
+    id serial primary key,
+    reservation_id int not null references stock_reservation(id) on delete cascade,
+    bin_location_id int not null references bin_location(id),
+    lot_id int references product_lot(id),
+    quantity int not null check (quantity > 0)
+);
+
+create index stock_reservation_line_reservation_id_idx on stock_reservation_line(reservation_id);
@@ -0,0 +1,26 @@
+-- Your SQL goes here
+create table cycle_count (
+    id serial primary key,
+    warehouse_id int not null references warehouse(id),
+    blind boolean not null default false,
+    recount_threshold int not null check (recount_threshold >= 0),
+    created_by varchar not null,
+    created_at timestamp not null default now()
+);
+
+create table cycle_count_task (
+    id serial primary key,
+    cycle_count_id int not null references cycle_count(id) on delete cascade,
+    bin_location_id int not null references bin_location(id),
+    product_id int not null references product(id),
+    lot_id int references product_lot(id),
+    status varchar not null default 'pending',
+    system_quantity int,
+    counted_quantity int check (counted_quantity >= 0),
+    counted_by varchar,
+    recount_quantity int check (recount_quantity >= 0),
+    recounted_by varchar,
+    version int not null default 0
+);
+
+create index cycle_count_task_cycle_count_id_idx on cycle_count_task(cycle_count_id);
@@ -0,0 +1,15 @@
+-- Your SQL goes here
+create table reorder_policy (
+    id serial primary key,
+    product_id int not null references product(id) on delete cascade,
+    warehouse_id int not null references warehouse(id) on delete cascade,
+    method varchar not null,
+    reorder_point int not null check (reorder_point >= 0),
+    reorder_quantity int check (reorder_quantity > 0),
+    max_quantity int,
+    order_unit varchar,
+    version int not null default 0,
+    unique (product_id, warehouse_id),
+    check ((method = 'reorder_point' and reorder_quantity is not null)
+        or (method = 'min_max' and max_quantity > reorder_point))
+);
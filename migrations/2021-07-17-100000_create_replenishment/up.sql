@@ -0,0 +1,27 @@
+-- Your SQL goes here
+create table pick_face (
+    id serial primary key,
+    bin_location_id int not null unique references bin_location(id) on delete cascade,
+    product_id int not null references product(id),
+    min_quantity int not null check (min_quantity >= 0),
+    max_quantity int not null,
+    version int not null default 0,
+    check (max_quantity > min_quantity)
+);
+
+create table replenishment_task (
+    id serial primary key,
+    pick_face_id int not null references pick_face(id),
+    product_id int not null references product(id),
+    lot_id int references product_lot(id),
+    from_location_id int not null references bin_location(id),
+    to_location_id int not null references bin_location(id),
+    quantity int not null check (quantity > 0),
+    status varchar not null default 'pending',
+    created_at timestamp not null default now(),
+    assigned_to varchar,
+    completed_by varchar,
+    completed_at timestamp
+);
+
+create index replenishment_task_pick_face_id_idx on replenishment_task(pick_face_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+drop table purchase_order_line;
+drop table purchase_order;
+drop table supplier;
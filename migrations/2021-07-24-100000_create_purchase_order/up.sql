@@ -0,0 +1,33 @@
+-- Your SQL goes here
+create table supplier (
+    id serial primary key,
+    name varchar not null,
+    contact_name varchar,
+    contact_email varchar,
+    lead_time_days int not null check (lead_time_days >= 0),
+    currency varchar(3) not null,
+    version int not null default 0
+);
+
+create table purchase_order (
+    id serial primary key,
+    supplier_id int not null references supplier(id),
+    warehouse_id int not null references warehouse(id),
+    currency varchar(3) not null,
+    status varchar not null default 'draft',
+    expected_on date,
+    created_by varchar not null,
+    created_at timestamp not null default now(),
+    version int not null default 0
+);
+
+create table purchase_order_line (
+    id serial primary key,
+    purchase_order_id int not null references purchase_order(id) on delete cascade,
+    product_id int not null references product(id),
+    quantity int not null check (quantity > 0),
+    unit_cost_minor bigint not null check (unit_cost_minor >= 0),
+    received_quantity int not null default 0 check (received_quantity >= 0)
+);
+
+create index purchase_order_line_purchase_order_id_idx on purchase_order_line(purchase_order_id);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+drop table goods_receipt_line;
+drop table goods_receipt;
+drop table shipping_notice_line;
+drop table shipping_notice;
+alter table purchase_order_line drop column damaged_quantity;
+alter table supplier drop column under_receipt_percent;
+alter table supplier drop column over_receipt_percent;
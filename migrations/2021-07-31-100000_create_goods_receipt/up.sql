@@ -0,0 +1,47 @@
+-- Your SQL goes here
+alter table supplier add column over_receipt_percent int not null default 0 check (over_receipt_percent >= 0);
+alter table supplier add column under_receipt_percent int not null default 0
+    check (under_receipt_percent between 0 and 100);
+
+alter table purchase_order_line add column damaged_quantity int not null default 0 check (damaged_quantity >= 0);
+
+create table shipping_notice (
+    id serial primary key,
+    purchase_order_id int not null references purchase_order(id),
+    reference varchar not null,
+    expected_on date,
+    status varchar not null default 'open',
+    created_at timestamp not null default now(),
+    unique (purchase_order_id, reference)
+);
+
+create table shipping_notice_line (
+    id serial primary key,
+    notice_id int not null references shipping_notice(id) on delete cascade,
+    purchase_order_line_id int not null references purchase_order_line(id),
+    quantity int not null check (quantity > 0),
+    lot_number varchar,
+    expires_on date
+);
+
+create table goods_receipt (
+    id serial primary key,
+    purchase_order_id int not null references purchase_order(id),
+    notice_id int references shipping_notice(id),
+    bin_location_id int not null references bin_location(id),
+    received_by varchar not null,
+    received_at timestamp not null default now()
+);
+
+create table goods_receipt_line (
+    id serial primary key,
+    goods_receipt_id int not null references goods_receipt(id) on delete cascade,
+    purchase_order_line_id int not null references purchase_order_line(id),
+    quantity int not null check (quantity >= 0),
+    damaged_quantity int not null check (damaged_quantity >= 0),
+    lot_id int references product_lot(id),
+    movement_id int references stock_movement(id)
+);
+
+create index shipping_notice_line_notice_id_idx on shipping_notice_line(notice_id);
+create index goods_receipt_line_goods_receipt_id_idx on goods_receipt_line(goods_receipt_id);
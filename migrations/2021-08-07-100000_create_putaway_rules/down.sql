@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+alter table goods_receipt_line drop column put_away_quantity;
+drop table zone_restriction;
+drop table home_location;
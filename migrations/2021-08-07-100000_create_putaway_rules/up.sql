@@ -0,0 +1,17 @@
+-- Your SQL goes here
+create table home_location (
+    id serial primary key,
+    product_id int not null references product(id) on delete cascade,
+    bin_location_id int not null references bin_location(id) on delete cascade,
+    unique (product_id, bin_location_id)
+);
+
+create table zone_restriction (
+    zone_id int not null references warehouse_zone(id) on delete cascade,
+    category_id int not null references product_category(id) on delete cascade,
+    primary key (zone_id, category_id)
+);
+
+alter table goods_receipt_line add column put_away_quantity int not null default 0;
+alter table goods_receipt_line add constraint goods_receipt_line_put_away_quantity_check
+    check (put_away_quantity between 0 and quantity);
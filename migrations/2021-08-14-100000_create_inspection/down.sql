@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+drop table inspection;
+alter table goods_receipt_line drop constraint goods_receipt_line_put_away_quantity_check;
+alter table goods_receipt_line drop column rejected_quantity;
+alter table goods_receipt_line add constraint goods_receipt_line_put_away_quantity_check
+    check (put_away_quantity between 0 and quantity);
+alter table supplier drop column requires_inspection;
+alter table product drop column requires_inspection;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+alter table product add column requires_inspection boolean not null default false;
+alter table supplier add column requires_inspection boolean not null default false;
+
+alter table goods_receipt_line add column rejected_quantity int not null default 0;
+alter table goods_receipt_line drop constraint goods_receipt_line_put_away_quantity_check;
+alter table goods_receipt_line add constraint goods_receipt_line_put_away_quantity_check
+    check (put_away_quantity >= 0 and rejected_quantity >= 0 and put_away_quantity + rejected_quantity <= quantity);
+
+create table inspection (
+    id serial primary key,
+    goods_receipt_line_id int not null references goods_receipt_line(id),
+    product_id int not null references product(id),
+    lot_id int references product_lot(id),
+    bin_location_id int not null references bin_location(id),
+    quantity int not null check (quantity > 0),
+    status varchar not null default 'pending',
+    rejected_quantity int not null default 0 check (rejected_quantity between 0 and quantity),
+    inspected_by varchar,
+    inspected_at timestamp,
+    notes varchar,
+    created_at timestamp not null default now()
+);
+
+create index inspection_status_idx on inspection(status);
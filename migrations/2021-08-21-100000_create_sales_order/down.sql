@@ -0,0 +1,6 @@
+-- This file should undo anything in `up.sql`
+drop table sales_order_allocation;
+drop table sales_order_line;
+drop table sales_order;
+drop table customer_address;
+drop table customer;
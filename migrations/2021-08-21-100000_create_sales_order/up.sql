@@ -0,0 +1,52 @@
+-- Your SQL goes here
+create table customer (
+    id serial primary key,
+    name varchar not null,
+    email varchar,
+    version int not null default 0
+);
+
+create table customer_address (
+    id serial primary key,
+    customer_id int not null references customer(id) on delete cascade,
+    recipient varchar not null,
+    street varchar not null,
+    postal_code varchar not null,
+    city varchar not null,
+    country varchar(2) not null,
+    is_default boolean not null default false
+);
+
+create unique index customer_address_default_idx on customer_address(customer_id) where is_default;
+
+create table sales_order (
+    id serial primary key,
+    customer_id int not null references customer(id),
+    warehouse_id int not null references warehouse(id),
+    shipping_address_id int not null references customer_address(id),
+    reference varchar,
+    status varchar not null default 'open',
+    allocation_strategy varchar not null default 'fefo',
+    created_by varchar not null,
+    created_at timestamp not null default now(),
+    version int not null default 0
+);
+
+create table sales_order_line (
+    id serial primary key,
+    sales_order_id int not null references sales_order(id) on delete cascade,
+    product_id int not null references product(id),
+    quantity int not null check (quantity > 0),
+    allocated_quantity int not null default 0 check (allocated_quantity between 0 and quantity)
+);
+
+create table sales_order_allocation (
+    id serial primary key,
+    sales_order_line_id int not null references sales_order_line(id) on delete cascade,
+    bin_location_id int not null references bin_location(id),
+    lot_id int references product_lot(id),
+    quantity int not null check (quantity > 0)
+);
+
+create index sales_order_line_sales_order_id_idx on sales_order_line(sales_order_id);
+create index sales_order_allocation_sales_order_line_id_idx on sales_order_allocation(sales_order_line_id);
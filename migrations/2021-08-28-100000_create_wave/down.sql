@@ -0,0 +1,9 @@
+-- This file should undo anything in `up.sql`
+drop table pick_list_line;
+drop table pick_list;
+alter table sales_order
+    drop column wave_id,
+    drop column priority,
+    drop column cutoff_at,
+    drop column carrier;
+drop table wave;
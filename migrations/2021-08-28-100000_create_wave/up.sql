@@ -0,0 +1,35 @@
+-- Your SQL goes here
+alter table sales_order
+    add column carrier varchar,
+    add column cutoff_at timestamp,
+    add column priority int not null default 0;
+
+create table wave (
+    id serial primary key,
+    warehouse_id int not null references warehouse(id),
+    grouped_by varchar not null,
+    group_key varchar not null,
+    picking_mode varchar not null,
+    released_by varchar not null,
+    released_at timestamp not null default now()
+);
+
+alter table sales_order add column wave_id int references wave(id);
+
+create table pick_list (
+    id serial primary key,
+    wave_id int not null references wave(id) on delete cascade,
+    sales_order_id int references sales_order(id),
+    zone_id int references warehouse_zone(id)
+);
+
+create table pick_list_line (
+    id serial primary key,
+    pick_list_id int not null references pick_list(id) on delete cascade,
+    sales_order_allocation_id int not null references sales_order_allocation(id),
+    sequence int not null,
+    unique (pick_list_id, sequence)
+);
+
+create index sales_order_wave_id_idx on sales_order(wave_id);
+create index pick_list_wave_id_idx on pick_list(wave_id);
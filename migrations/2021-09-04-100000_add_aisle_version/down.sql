@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+alter table warehouse_aisle drop column version;
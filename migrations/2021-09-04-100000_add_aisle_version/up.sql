@@ -0,0 +1,2 @@
+-- Your SQL goes here
+alter table warehouse_aisle add column version int not null default 0;
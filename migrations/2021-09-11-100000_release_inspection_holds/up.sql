@@ -0,0 +1,9 @@
+-- Your SQL goes here
+update inventory_balance b
+set allocated = b.allocated - held.quantity, version = b.version + 1
+from (select product_id, lot_id, bin_location_id, sum(quantity)::int as quantity
+      from inspection where status = 'pending'
+      group by product_id, lot_id, bin_location_id) held
+where b.product_id = held.product_id
+  and b.bin_location_id = held.bin_location_id
+  and b.lot_id is not distinct from held.lot_id;
@@ -6,7 +6,145 @@ use rocket::Rocket;
 pub struct PostgresConnection(diesel::PgConnection);
 
 pub fn configure_routes(server: Rocket) -> Rocket {
-    server.mount("/", routes![crate::product::category::routes::post])
+    server.mount("/", routes![crate::product::category::routes::post,
+                              crate::product::category::routes::list,
+                              crate::product::category::routes::get,
+                              crate::product::category::routes::put,
+                              crate::product::category::routes::delete,
+                              crate::product::category::routes::subtree,
+                              crate::product::category::routes::ancestors,
+                              crate::product::category::routes::move_to,
+                              crate::product::routes::post,
+                              crate::product::routes::list,
+                              crate::product::routes::get,
+                              crate::product::routes::put,
+                              crate::product::routes::delete,
+                              crate::product::barcode::routes::post,
+                              crate::product::barcode::routes::list,
+                              crate::product::barcode::routes::delete,
+                              crate::product::barcode::routes::lookup,
+                              crate::product::variant::routes::post,
+                              crate::product::variant::routes::list,
+                              crate::product::packaging::routes::post,
+                              crate::product::packaging::routes::get,
+                              crate::product::packaging::routes::delete,
+                              crate::product::packaging::routes::convert,
+                              crate::product::lot::routes::post,
+                              crate::product::lot::routes::for_product,
+                              crate::product::lot::routes::get,
+                              crate::product::lot::routes::expiring,
+                              crate::location::warehouse::routes::post,
+                              crate::location::warehouse::routes::list,
+                              crate::location::warehouse::routes::get,
+                              crate::location::warehouse::routes::put,
+                              crate::location::warehouse::routes::delete,
+                              crate::location::zone::routes::post,
+                              crate::location::zone::routes::list,
+                              crate::location::zone::routes::get,
+                              crate::location::zone::routes::put,
+                              crate::location::zone::routes::delete,
+                              crate::location::aisle::routes::post,
+                              crate::location::aisle::routes::list,
+                              crate::location::aisle::routes::get,
+                              crate::location::aisle::routes::put,
+                              crate::location::aisle::routes::delete,
+                              crate::location::bin::routes::post,
+                              crate::location::bin::routes::list,
+                              crate::location::bin::routes::find_by_code,
+                              crate::location::bin::routes::get,
+                              crate::location::bin::routes::put,
+                              crate::location::bin::routes::delete,
+                              crate::inventory::balance::routes::by_product,
+                              crate::inventory::balance::routes::by_bin,
+                              crate::inventory::balance::routes::by_warehouse,
+                              crate::inventory::balance::routes::by_warehouse_bins,
+                              crate::inventory::movement::routes::post,
+                              crate::inventory::movement::routes::get,
+                              crate::inventory::movement::routes::list,
+                              crate::inventory::movement::routes::drift,
+                              crate::inventory::movement::routes::rebuild,
+                              crate::inventory::transfer::routes::between_bins,
+                              crate::inventory::transfer::routes::ship,
+                              crate::inventory::transfer::routes::in_transit,
+                              crate::inventory::transfer::routes::get,
+                              crate::inventory::transfer::routes::receive,
+                              crate::inventory::serial::routes::in_stock,
+                              crate::inventory::serial::routes::history,
+                              crate::inventory::reservation::routes::post,
+                              crate::inventory::reservation::routes::get,
+                              crate::inventory::reservation::routes::pick,
+                              crate::inventory::reservation::routes::cancel,
+                              crate::inventory::reservation::routes::release_expired,
+                              crate::inventory::count::routes::post,
+                              crate::inventory::count::routes::get,
+                              crate::inventory::count::routes::count,
+                              crate::inventory::count::routes::approve,
+                              crate::inventory::reorder::routes::post,
+                              crate::inventory::reorder::routes::list,
+                              crate::inventory::reorder::routes::get,
+                              crate::inventory::reorder::routes::put,
+                              crate::inventory::reorder::routes::delete,
+                              crate::inventory::reorder::routes::suggestions,
+                              crate::inventory::replenishment::routes::post,
+                              crate::inventory::replenishment::routes::list,
+                              crate::inventory::replenishment::routes::get,
+                              crate::inventory::replenishment::routes::put,
+                              crate::inventory::replenishment::routes::delete,
+                              crate::inventory::replenishment::routes::generate,
+                              crate::inventory::replenishment::routes::queue,
+                              crate::inventory::replenishment::routes::get_task,
+                              crate::inventory::replenishment::routes::start,
+                              crate::inventory::replenishment::routes::complete,
+                              crate::inventory::replenishment::routes::cancel,
+                              crate::purchasing::supplier::routes::post,
+                              crate::purchasing::supplier::routes::list,
+                              crate::purchasing::supplier::routes::get,
+                              crate::purchasing::supplier::routes::put,
+                              crate::purchasing::supplier::routes::delete,
+                              crate::purchasing::order::routes::post,
+                              crate::purchasing::order::routes::list,
+                              crate::purchasing::order::routes::get,
+                              crate::purchasing::order::routes::put,
+                              crate::purchasing::order::routes::add_line,
+                              crate::purchasing::order::routes::remove_line,
+                              crate::purchasing::order::routes::send,
+                              crate::purchasing::order::routes::cancel,
+                              crate::purchasing::order::routes::close,
+                              crate::purchasing::receiving::routes::import_notice,
+                              crate::purchasing::receiving::routes::list_notices,
+                              crate::purchasing::receiving::routes::get_notice,
+                              crate::purchasing::receiving::routes::receive,
+                              crate::purchasing::receiving::routes::get_receipt,
+                              crate::inventory::putaway::routes::post_home,
+                              crate::inventory::putaway::routes::list_homes,
+                              crate::inventory::putaway::routes::delete_home,
+                              crate::inventory::putaway::routes::get_restriction,
+                              crate::inventory::putaway::routes::put_restriction,
+                              crate::inventory::putaway::routes::suggest,
+                              crate::inventory::putaway::routes::confirm,
+                              crate::purchasing::inspection::routes::list,
+                              crate::purchasing::inspection::routes::get,
+                              crate::purchasing::inspection::routes::release,
+                              crate::purchasing::inspection::routes::reject,
+                              crate::sales::customer::routes::post,
+                              crate::sales::customer::routes::list,
+                              crate::sales::customer::routes::get,
+                              crate::sales::customer::routes::put,
+                              crate::sales::customer::routes::delete,
+                              crate::sales::customer::routes::post_address,
+                              crate::sales::customer::routes::delete_address,
+                              crate::sales::order::routes::post,
+                              crate::sales::order::routes::list,
+                              crate::sales::order::routes::get,
+                              crate::sales::order::routes::add_line,
+                              crate::sales::order::routes::remove_line,
+                              crate::sales::order::routes::allocate,
+                              crate::sales::order::routes::cancel,
+                              crate::sales::wave::routes::post,
+                              crate::sales::wave::routes::list,
+                              crate::sales::wave::routes::get,
+                              crate::sales::picking::routes::get,
+                              crate::sales::picking::routes::print])
 }
 
 pub fn attach_fairings(server: Rocket) -> Rocket {
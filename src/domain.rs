@@ -0,0 +1,42 @@
+use std::fmt::{self, Display};
+use crate::inventory::balance::models::StockError;
+use crate::product::packaging::models::ConversionError;
+
+/// Error of a step in the life of a document such as a cycle count, a purchase or sales order
+/// or a goods receipt.
+#[derive(Debug, PartialEq)]
+pub enum DomainError {
+    /// The document's status does not allow the requested step.
+    Rejected(String),
+    /// The request itself is not acceptable, whatever the document's status.
+    Invalid(String),
+    Stock(StockError)
+}
+
+impl Display for DomainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainError::Rejected(message) => f.write_str(message),
+            DomainError::Invalid(message) => f.write_str(message),
+            DomainError::Stock(e) => Display::fmt(e, f)
+        }
+    }
+}
+
+impl From<diesel::result::Error> for DomainError {
+    fn from(error: diesel::result::Error) -> Self {
+        DomainError::Stock(StockError::Database(error))
+    }
+}
+
+impl From<StockError> for DomainError {
+    fn from(error: StockError) -> Self {
+        DomainError::Stock(error)
+    }
+}
+
+impl From<ConversionError> for DomainError {
+    fn from(error: ConversionError) -> Self {
+        DomainError::Invalid(error.to_string())
+    }
+}
@@ -0,0 +1,208 @@
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use rocket_contrib::json::Json;
+use serde::{Serialize, Deserialize};
+use diesel::result::{Error as DieselError, DatabaseErrorKind};
+use crate::etag::etag;
+use crate::product::packaging::models::ConversionError;
+use crate::inventory::balance::models::StockError;
+use crate::domain::DomainError;
+
+/// Error returned by every route handler, rendered as a JSON body with a matching status code.
+#[derive(Debug, PartialEq)]
+pub enum ApiError {
+    BadRequest(String),
+    NotFound,
+    Conflict(String),
+    PreconditionRequired,
+    /// Optimistic lock lost, carries the current version and representation of the resource.
+    PreconditionFailed(i32, serde_json::Value),
+    UnprocessableEntity(String),
+    ServiceUnavailable(String),
+    Internal(String)
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: String,
+    pub message: String
+}
+
+impl ApiError {
+    pub fn precondition_failed(version: i32, current: &impl Serialize) -> ApiError {
+        match serde_json::to_value(current) {
+            Ok(value) => ApiError::PreconditionFailed(version, value),
+            Err(e) => ApiError::Internal(e.to_string())
+        }
+    }
+
+    /// Answers an update which lost its optimistic lock with the `current` state of the resource as
+    /// loaded again, or with the error of loading it, so a resource deleted meanwhile is a 404.
+    pub fn precondition_failed_with<T: Serialize>(current: Result<T, DieselError>, version: impl FnOnce(&T) -> i32) -> ApiError {
+        match current {
+            Ok(current) => ApiError::precondition_failed(version(&current), &current),
+            Err(e) => e.into()
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::NotFound => Status::NotFound,
+            ApiError::Conflict(_) => Status::Conflict,
+            ApiError::PreconditionRequired => Status::PreconditionRequired,
+            ApiError::PreconditionFailed(_, _) => Status::PreconditionFailed,
+            ApiError::UnprocessableEntity(_) => Status::UnprocessableEntity,
+            ApiError::ServiceUnavailable(_) => Status::ServiceUnavailable,
+            ApiError::Internal(_) => Status::InternalServerError
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        let status = self.status();
+        let message = match self {
+            ApiError::BadRequest(message) => message.clone(),
+            ApiError::NotFound => String::from("The requested resource does not exist"),
+            ApiError::Conflict(message) => message.clone(),
+            ApiError::PreconditionRequired => String::from("The If-Match header is required for this request"),
+            ApiError::PreconditionFailed(version, _) =>
+                format!("The resource was modified concurrently, its current version is {}", version),
+            ApiError::UnprocessableEntity(message) => message.clone(),
+            ApiError::ServiceUnavailable(message) => message.clone(),
+            ApiError::Internal(message) => message.clone()
+        };
+        ErrorBody {
+            status: status.code,
+            error: status.reason.to_string(),
+            message
+        }
+    }
+}
+
+impl From<DieselError> for ApiError {
+    fn from(error: DieselError) -> Self {
+        match error {
+            DieselError::NotFound => ApiError::NotFound,
+            DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, info) =>
+                ApiError::Conflict(info.message().to_string()),
+            DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, info) =>
+                ApiError::Conflict(info.message().to_string()),
+            DieselError::DatabaseError(DatabaseErrorKind::SerializationFailure, info) =>
+                ApiError::ServiceUnavailable(info.message().to_string()),
+            DieselError::DatabaseError(DatabaseErrorKind::UnableToSendCommand, info) =>
+                ApiError::ServiceUnavailable(info.message().to_string()),
+            e => ApiError::Internal(e.to_string())
+        }
+    }
+}
+
+impl From<ConversionError> for ApiError {
+    fn from(error: ConversionError) -> Self {
+        ApiError::UnprocessableEntity(error.to_string())
+    }
+}
+
+impl From<StockError> for ApiError {
+    fn from(error: StockError) -> Self {
+        match error {
+            StockError::Database(e) => e.into(),
+            StockError::Contention => ApiError::ServiceUnavailable(error.to_string()),
+            StockError::InsufficientStock { .. } | StockError::Shortage { .. } => ApiError::Conflict(error.to_string()),
+            StockError::InvalidMovement(message) => ApiError::UnprocessableEntity(message),
+            StockError::Overflow { .. } => ApiError::UnprocessableEntity(error.to_string())
+        }
+    }
+}
+
+impl From<DomainError> for ApiError {
+    fn from(error: DomainError) -> Self {
+        match error {
+            DomainError::Rejected(message) => ApiError::Conflict(message),
+            DomainError::Invalid(message) => ApiError::UnprocessableEntity(message),
+            DomainError::Stock(e) => e.into()
+        }
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        if let ApiError::PreconditionFailed(version, current) = self {
+            return Response::build_from(Json(current).respond_to(request)?)
+                .status(Status::PreconditionFailed)
+                .raw_header("ETag", etag(version))
+                .ok();
+        }
+        Response::build_from(Json(self.body()).respond_to(request)?)
+            .status(self.status())
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::error::ApiError;
+    use crate::domain::DomainError;
+    use diesel::result::{Error as DieselError, DatabaseErrorKind};
+    use rocket::http::Status;
+
+    fn database_error(kind: DatabaseErrorKind) -> DieselError {
+        DieselError::DatabaseError(kind, Box::new(String::from("database said no")))
+    }
+
+    #[test]
+    fn not_found_maps_to_404() {
+        assert_eq!(ApiError::from(DieselError::NotFound).status(), Status::NotFound);
+    }
+
+    #[test]
+    fn unique_violation_maps_to_409_with_database_message() {
+        let error = ApiError::from(database_error(DatabaseErrorKind::UniqueViolation));
+        assert_eq!(error, ApiError::Conflict(String::from("database said no")));
+        assert_eq!(error.status(), Status::Conflict);
+    }
+
+    #[test]
+    fn serialization_failure_and_lost_connection_map_to_503() {
+        let serialization = ApiError::from(database_error(DatabaseErrorKind::SerializationFailure));
+        let connection = ApiError::from(database_error(DatabaseErrorKind::UnableToSendCommand));
+        assert_eq!(serialization.status(), Status::ServiceUnavailable);
+        assert_eq!(connection.status(), Status::ServiceUnavailable);
+    }
+
+    #[test]
+    fn other_errors_map_to_500() {
+        assert_eq!(ApiError::from(DieselError::RollbackTransaction).status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn precondition_failed_carries_current_representation() {
+        let error = ApiError::precondition_failed(2, &vec!["current"]);
+        assert_eq!(error, ApiError::PreconditionFailed(2, serde_json::json!(["current"])));
+        assert_eq!(error.status(), Status::PreconditionFailed);
+    }
+
+    #[test]
+    fn precondition_failed_with_a_deleted_resource_is_not_found() {
+        let current = ApiError::precondition_failed_with(Ok(vec![3]), |current| current[0]);
+        assert_eq!(current, ApiError::PreconditionFailed(3, serde_json::json!([3])));
+        let deleted = ApiError::precondition_failed_with(Err::<Vec<i32>, _>(DieselError::NotFound), |current| current[0]);
+        assert_eq!(deleted, ApiError::NotFound);
+    }
+
+    #[test]
+    fn rejected_steps_are_conflicts_and_invalid_requests_unprocessable() {
+        assert_eq!(ApiError::from(DomainError::Rejected(String::from("shipped"))).status(), Status::Conflict);
+        assert_eq!(ApiError::from(DomainError::Invalid(String::from("negative"))).status(), Status::UnprocessableEntity);
+        assert_eq!(ApiError::from(DomainError::from(DieselError::NotFound)), ApiError::NotFound);
+    }
+
+    #[test]
+    fn error_body_carries_status_and_reason() {
+        let body = ApiError::Conflict(String::from("taken")).body();
+        assert_eq!(body.status, 409);
+        assert_eq!(body.error, "Conflict");
+        assert_eq!(body.message, "taken");
+    }
+}
@@ -0,0 +1,78 @@
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::response::{self, Responder, Response};
+use rocket::Outcome;
+use crate::error::ApiError;
+
+/// Formats an optimistic locking version as a strong entity tag.
+pub fn etag(version: i32) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Version the client expects a resource to have, taken from the `If-Match` header.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct IfMatch(i32);
+
+impl IfMatch {
+    pub fn version(&self) -> i32 {
+        self.0
+    }
+
+    fn parse(header: &str) -> Option<IfMatch> {
+        let tag = header.trim();
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag.strip_prefix('"')
+            .and_then(|t| t.strip_suffix('"'))
+            .and_then(|t| t.parse::<i32>().ok())
+            .map(IfMatch)
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = ApiError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match request.headers().get_one("If-Match") {
+            None => Outcome::Failure((Status::PreconditionRequired, ApiError::PreconditionRequired)),
+            Some(header) => match IfMatch::parse(header) {
+                Some(if_match) => Outcome::Success(if_match),
+                None => Outcome::Failure((Status::BadRequest,
+                                          ApiError::BadRequest(format!("Malformed If-Match header: {}", header))))
+            }
+        }
+    }
+}
+
+/// Wraps a responder and adds an `ETag` header carrying the resource version.
+pub struct Tagged<R>(pub i32, pub R);
+
+impl<'r, R: Responder<'r>> Responder<'r> for Tagged<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        Response::build_from(self.1.respond_to(request)?)
+            .raw_header("ETag", etag(self.0))
+            .ok()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::etag::{IfMatch, etag};
+
+    #[test]
+    fn strong_and_weak_tags_are_parsed() {
+        assert_eq!(IfMatch::parse("\"3\""), Some(IfMatch(3)));
+        assert_eq!(IfMatch::parse(" W/\"12\" "), Some(IfMatch(12)));
+    }
+
+    #[test]
+    fn unquoted_wildcard_and_garbage_tags_are_rejected() {
+        assert_eq!(IfMatch::parse("3"), None);
+        assert_eq!(IfMatch::parse("*"), None);
+        assert_eq!(IfMatch::parse("\"three\""), None);
+    }
+
+    #[test]
+    fn etag_round_trips_through_if_match() {
+        assert_eq!(IfMatch::parse(&etag(42)).map(|m| m.version()), Some(42));
+    }
+}
@@ -0,0 +1,588 @@
+use diesel::prelude::*;
+use std::fmt::{self, Debug, Display};
+use diesel::update;
+use crate::schema::{bin_location, inventory_balance, product_lot};
+use crate::location::bin::models::{BinLocation, LocationType, PICKABLE};
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+use crate::inventory::reservation::models::Reservation;
+use std::convert::TryFrom;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Array, Integer, BigInt, Nullable, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+use chrono::Local;
+use std::collections::{HashMap, HashSet};
+
+/// How often a stock change is retried after losing the optimistic lock on a balance.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Packaging level a full pallet of a product is defined as.
+pub const PALLET: &str = "pallet";
+
+sql_enum! {
+    /// Order in which stock is taken from the balances of a warehouse. Expired lots are never
+    /// allocated, whatever the strategy.
+    pub enum AllocationStrategy {
+        Fefo => "fefo",
+        Fifo => "fifo",
+        LeastBins => "least_bins",
+        FewestPartialPallets => "fewest_partial_pallets"
+    }
+}
+
+impl AllocationStrategy {
+    /// Order by clause over the balance `b`, its lot `l` and the pallet size `$4`.
+    fn order_by(self) -> &'static str {
+        match self {
+            AllocationStrategy::Fefo => "l.expires_on asc nulls last, b.id",
+            AllocationStrategy::Fifo =>
+                "(select min(m.created_at) from stock_movement m \
+                  where m.product_id = b.product_id and m.to_location_id = b.bin_location_id \
+                    and m.lot_id is not distinct from b.lot_id) asc nulls last, b.id",
+            AllocationStrategy::LeastBins => "b.on_hand - b.allocated desc, l.expires_on asc nulls last, b.id",
+            AllocationStrategy::FewestPartialPallets =>
+                "coalesce((b.on_hand - b.allocated) % $4 = 0, false) asc, b.on_hand - b.allocated asc, \
+                 l.expires_on asc nulls last, b.id"
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, QueryableByName, Identifiable, Deserialize, Serialize)]
+#[table_name="inventory_balance"]
+pub struct InventoryBalance {
+    id: i32,
+    product_id: i32,
+    bin_location_id: i32,
+    warehouse_id: i32,
+    on_hand: i32,
+    allocated: i32,
+    version: i32,
+    lot_id: Option<i32>
+}
+
+impl AsChangeset for InventoryBalance {
+    type Target = inventory_balance::table;
+    type Changeset = <(DieselEq<inventory_balance::on_hand, Bound<Integer, i32>>,
+                       DieselEq<inventory_balance::allocated, Bound<Integer, i32>>,
+                       DieselEq<inventory_balance::version, DieselAdd<inventory_balance::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            inventory_balance::on_hand.eq(self.on_hand),
+            inventory_balance::allocated.eq(self.allocated),
+            inventory_balance::version.eq(inventory_balance::version + 1)
+        ).as_changeset()
+    }
+}
+
+/// Change to the quantities of a balance, positive values add to them.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct StockChange {
+    pub on_hand: i32,
+    pub allocated: i32
+}
+
+impl StockChange {
+    pub fn on_hand(quantity: i32) -> StockChange {
+        StockChange { on_hand: quantity, ..Default::default() }
+    }
+
+    pub fn allocated(quantity: i32) -> StockChange {
+        StockChange { allocated: quantity, ..Default::default() }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StockError {
+    InsufficientStock { product_id: i32, bin_location_id: i32, available: i32 },
+    Contention,
+    /// A stock movement whose locations or quantity do not fit its reason.
+    InvalidMovement(String),
+    /// Not enough unexpired stock of a product in a warehouse to allocate the requested quantity.
+    Shortage { product_id: i32, warehouse_id: i32, available: i32 },
+    /// A change which would take the quantities of a balance beyond what can be stored.
+    Overflow { product_id: i32, bin_location_id: i32 },
+    Database(diesel::result::Error)
+}
+
+impl Display for StockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StockError::InsufficientStock { product_id, bin_location_id, available } =>
+                write!(f, "Only {} units of product {} are available in bin {}", available, product_id, bin_location_id),
+            StockError::Contention => write!(f, "The stock level is being changed concurrently, please retry"),
+            StockError::InvalidMovement(message) => write!(f, "{}", message),
+            StockError::Shortage { product_id, warehouse_id, available } =>
+                write!(f, "Only {} units of product {} can be allocated in warehouse {}", available, product_id, warehouse_id),
+            StockError::Overflow { product_id, bin_location_id } =>
+                write!(f, "The stock of product {} in bin {} would exceed the largest storable quantity", product_id, bin_location_id),
+            StockError::Database(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl From<diesel::result::Error> for StockError {
+    fn from(error: diesel::result::Error) -> Self {
+        StockError::Database(error)
+    }
+}
+
+impl InventoryBalance {
+
+    pub fn find(balance_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<InventoryBalance, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        inventory_balance.find(balance_id).first(conn)
+    }
+
+    pub fn for_product(product: i32, warehouse: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<InventoryBalance>, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        let mut query = inventory_balance.filter(product_id.eq(product)).into_boxed();
+        if let Some(warehouse) = warehouse {
+            query = query.filter(warehouse_id.eq(warehouse));
+        }
+        query.order(bin_location_id.asc()).load(conn)
+    }
+
+    pub fn for_bin(bin: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<InventoryBalance>, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        inventory_balance.filter(bin_location_id.eq(bin)).order(product_id.asc()).load(conn)
+    }
+
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<InventoryBalance>, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        inventory_balance.filter(warehouse_id.eq(warehouse)).order((product_id.asc(), bin_location_id.asc())).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn bin_location_id(&self) -> i32 {
+        self.bin_location_id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn on_hand(&self) -> i32 {
+        self.on_hand
+    }
+
+    pub fn allocated(&self) -> i32 {
+        self.allocated
+    }
+
+    pub fn available(&self) -> i32 {
+        self.on_hand - self.allocated
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn lot_id(&self) -> Option<i32> {
+        self.lot_id
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<InventoryBalance>, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(inventory_balance.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    /// Applies `change` to the balance of `product` in `bin`, creating the balance if needed.
+    /// Lost optimistic locks are retried against the freshly read balance.
+    pub fn apply(product: i32, bin: &BinLocation, change: StockChange, conn: &impl Connection<Backend=Pg>) -> Result<InventoryBalance, StockError> {
+        InventoryBalance::apply_to_lot(product, None, bin, change, conn)
+    }
+
+    /// Like `apply`, but for the stock of one lot of `product`.
+    pub fn apply_to_lot(product: i32, lot: Option<i32>, bin: &BinLocation, change: StockChange, conn: &impl Connection<Backend=Pg>) -> Result<InventoryBalance, StockError> {
+        conn.transaction(|| {
+            for _ in 0..MAX_ATTEMPTS {
+                let current = InventoryBalance::locate(product, lot, bin, conn)?;
+                if let Some(updated) = current.changed_by(change)?.update(conn)? {
+                    return Ok(updated);
+                }
+            }
+            Err(StockError::Contention)
+        })
+    }
+
+    /// Allocates `quantity` of `product` in `warehouse` First-Expired-First-Out. Only stock in
+    /// pickable bins and never stock of expired lots is allocated, stock without expiry date is
+    /// allocated last. Stock of expired reservations is released before it is allocated.
+    pub fn allocate(product: i32, warehouse: i32, quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Allocation>, StockError> {
+        conn.transaction(|| {
+            let allocations = InventoryBalance::allocate_up_to(product, warehouse, &[], quantity, conn)?;
+            let allocated: i32 = allocations.iter().map(|allocation| allocation.quantity).sum();
+            if allocated < quantity {
+                return Err(StockError::Shortage { product_id: product, warehouse_id: warehouse, available: allocated });
+            }
+            Ok(allocations)
+        })
+    }
+
+    /// Like `allocate`, but only from pickable bins of `location_types` if any are given, and
+    /// settles for less than `quantity` if that is all there is.
+    pub fn allocate_up_to(product: i32, warehouse: i32, location_types: &[LocationType], quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Allocation>, StockError> {
+        conn.transaction(|| {
+            Reservation::release_expired(conn)?;
+            InventoryBalance::allocate_from(InventoryBalance::allocatable(product, warehouse, location_types, AllocationStrategy::Fefo, None, conn)?, quantity, conn)
+        })
+    }
+
+    /// Allocates up to `quantity` of `product` in `warehouse` in the order of `strategy`, only from
+    /// pickable bins of `location_types` if any are given. When avoiding partial pallets, balances
+    /// which are no whole number of the product's `pallet` packaging level are taken from first.
+    /// Stock of expired reservations is released before, like for every allocation.
+    pub fn allocate_by(product: &Product, warehouse: i32, location_types: &[LocationType], strategy: AllocationStrategy, quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Allocation>, StockError> {
+        let pallet = match strategy {
+            AllocationStrategy::FewestPartialPallets => PackagingHierarchy::load(product, conn)?
+                .factor(PALLET)
+                .ok()
+                .filter(|factor| *factor > 1)
+                .and_then(|factor| i32::try_from(factor).ok()),
+            _ => None
+        };
+        conn.transaction(|| {
+            Reservation::release_expired(conn)?;
+            InventoryBalance::allocate_from(InventoryBalance::allocatable(product.id(), warehouse, location_types, strategy, pallet, conn)?, quantity, conn)
+        })
+    }
+
+    fn allocate_from(balances: Vec<InventoryBalance>, quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Allocation>, StockError> {
+        conn.transaction(|| {
+            let mut remaining = quantity;
+            let mut allocations = Vec::new();
+            for balance in balances {
+                if remaining == 0 {
+                    break;
+                }
+                let taken = remaining.min(balance.available());
+                let bin = BinLocation::find(balance.bin_location_id, conn)?;
+                InventoryBalance::apply_to_lot(balance.product_id, balance.lot_id, &bin, StockChange::allocated(taken), conn)?;
+                allocations.push(Allocation { bin_location_id: bin.id(), lot_id: balance.lot_id, quantity: taken });
+                remaining -= taken;
+            }
+            Ok(allocations)
+        })
+    }
+
+    /// Balances with stock available for allocation, in the order `strategy` takes from them.
+    fn allocatable(product: i32, warehouse: i32, location_types: &[LocationType], strategy: AllocationStrategy, pallet: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<InventoryBalance>, diesel::result::Error> {
+        let location_types: Vec<LocationType> = PICKABLE.iter()
+            .copied()
+            .filter(|kind| location_types.is_empty() || location_types.contains(kind))
+            .collect();
+        diesel::sql_query(format!(
+            "select b.* from inventory_balance b \
+             join bin_location bin on bin.id = b.bin_location_id \
+             left join product_lot l on l.id = b.lot_id \
+             where b.product_id = $1 and b.warehouse_id = $2 and b.on_hand > b.allocated \
+               and (l.expires_on is null or l.expires_on >= current_date) \
+               and bin.location_type = any($3) \
+             order by {}", strategy.order_by()))
+            .bind::<Integer, _>(product)
+            .bind::<Integer, _>(warehouse)
+            .bind::<Array<Text>, _>(&location_types)
+            .bind::<Nullable<Integer>, _>(pallet)
+            .load(conn)
+    }
+
+    /// The balance of `product` in `lot` and `bin`, if stock of it was ever booked there.
+    pub fn lookup(product: i32, lot: Option<i32>, bin: i32, conn: &impl Connection<Backend=Pg>) -> Result<Option<InventoryBalance>, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        let query = inventory_balance
+            .filter(product_id.eq(product).and(bin_location_id.eq(bin)))
+            .into_boxed();
+        match lot {
+            Some(lot) => query.filter(lot_id.eq(lot)).first(conn).optional(),
+            None => query.filter(lot_id.is_null()).first(conn).optional()
+        }
+    }
+
+    fn locate(product: i32, lot: Option<i32>, bin: &BinLocation, conn: &impl Connection<Backend=Pg>) -> Result<InventoryBalance, diesel::result::Error> {
+        use crate::schema::inventory_balance::dsl::*;
+        diesel::insert_into(inventory_balance)
+            .values(NewInventoryBalance {
+                product_id: product,
+                bin_location_id: bin.id(),
+                warehouse_id: bin.warehouse_id(),
+                lot_id: lot
+            })
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+        InventoryBalance::lookup(product, lot, bin.id(), conn)?.ok_or(diesel::result::Error::NotFound)
+    }
+
+    fn changed_by(&self, change: StockChange) -> Result<InventoryBalance, StockError> {
+        let overflow = || StockError::Overflow { product_id: self.product_id, bin_location_id: self.bin_location_id };
+        let on_hand = self.on_hand.checked_add(change.on_hand).ok_or_else(overflow)?;
+        let allocated = self.allocated.checked_add(change.allocated).ok_or_else(overflow)?;
+        if on_hand < 0 || allocated < 0 || allocated > on_hand {
+            return Err(StockError::InsufficientStock {
+                product_id: self.product_id,
+                bin_location_id: self.bin_location_id,
+                available: self.available()
+            });
+        }
+        Ok(InventoryBalance { on_hand, allocated, ..self.clone() })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="inventory_balance"]
+struct NewInventoryBalance {
+    product_id: i32,
+    bin_location_id: i32,
+    warehouse_id: i32,
+    lot_id: Option<i32>
+}
+
+/// Quantity allocated from one bin and lot.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Allocation {
+    pub bin_location_id: i32,
+    pub lot_id: Option<i32>,
+    pub quantity: i32
+}
+
+/// A balance as it is exposed over HTTP, including the quantity available for allocation. The
+/// unallocated stock of an expired lot is reported as expired instead, as it can not be allocated.
+/// Stock in bins that are not pickable, e.g. received stock waiting for inspection on a dock or
+/// rejected stock in a returns bin, is never available.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct StockLevel {
+    #[serde(flatten)]
+    pub balance: InventoryBalance,
+    pub available: i32,
+    pub expired: i32
+}
+
+impl StockLevel {
+    pub fn of(balances: Vec<InventoryBalance>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<StockLevel>, diesel::result::Error> {
+        let lots: Vec<i32> = balances.iter().filter_map(InventoryBalance::lot_id).collect();
+        let bins: Vec<i32> = balances.iter().map(InventoryBalance::bin_location_id).collect();
+        let location_types: HashMap<i32, LocationType> = bin_location::table
+            .filter(bin_location::id.eq_any(bins))
+            .select((bin_location::id, bin_location::location_type))
+            .load::<(i32, LocationType)>(conn)?
+            .into_iter()
+            .collect();
+        let expired_lots: HashSet<i32> = product_lot::table
+            .filter(product_lot::id.eq_any(lots))
+            .filter(product_lot::expires_on.lt(Local::today().naive_local()))
+            .select(product_lot::id)
+            .load::<i32>(conn)?
+            .into_iter()
+            .collect();
+        Ok(balances.into_iter()
+            .map(|balance| match balance.lot_id {
+                Some(lot) if expired_lots.contains(&lot) => StockLevel { available: 0, expired: balance.available(), balance },
+                _ if !location_types.get(&balance.bin_location_id).map_or(false, |kind| kind.is_pickable()) =>
+                    StockLevel { available: 0, expired: 0, balance },
+                _ => StockLevel { available: balance.available(), expired: 0, balance }
+            })
+            .collect())
+    }
+}
+
+/// Stock of one product summed over all bins of a warehouse. Only stock in pickable bins is
+/// available, stock of expired lots is reported as expired instead.
+#[derive(Debug, PartialEq, QueryableByName, Serialize, Deserialize)]
+pub struct ProductStock {
+    #[sql_type="Integer"]
+    pub product_id: i32,
+    #[sql_type="BigInt"]
+    pub on_hand: i64,
+    #[sql_type="BigInt"]
+    pub allocated: i64,
+    #[sql_type="BigInt"]
+    pub available: i64,
+    #[sql_type="BigInt"]
+    pub expired: i64
+}
+
+impl ProductStock {
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ProductStock>, diesel::result::Error> {
+        diesel::sql_query(
+            "select b.product_id, sum(b.on_hand) as on_hand, sum(b.allocated) as allocated, \
+                    coalesce(sum(b.on_hand - b.allocated) filter (where (l.expires_on is null or l.expires_on >= current_date) and bin.location_type = any($2)), 0)::bigint as available, \
+                    coalesce(sum(b.on_hand - b.allocated) filter (where l.expires_on < current_date), 0)::bigint as expired \
+             from inventory_balance b \
+             join bin_location bin on bin.id = b.bin_location_id \
+             left join product_lot l on l.id = b.lot_id \
+             where b.warehouse_id = $1 \
+             group by b.product_id order by b.product_id")
+            .bind::<Integer, _>(warehouse)
+            .bind::<Array<Text>, _>(&PICKABLE[..])
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError, StockLevel, ProductStock, Allocation, AllocationStrategy, PALLET};
+    use crate::product::lot::models::LotDefinition;
+    use crate::product::packaging::models::{PackagingDefinition, PackagingHierarchy};
+    use chrono::{Duration, Local};
+
+    #[test]
+    fn applying_changes_creates_and_updates_balance() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(10), &conn).unwrap();
+            let balance = InventoryBalance::apply(soap.id(), &bins[0], StockChange::allocated(4), &conn).unwrap();
+
+            assert_eq!(balance.on_hand(), 10);
+            assert_eq!(balance.allocated(), 4);
+            assert_eq!(balance.available(), 6);
+            assert_eq!(balance.version(), 2);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn stock_can_not_drop_below_what_is_allocated() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange { on_hand: 5, allocated: 3 }, &conn).unwrap();
+
+            let taken = InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(-3), &conn);
+            assert_eq!(taken, Err(StockError::InsufficientStock { product_id: soap.id(), bin_location_id: bins[0].id(), available: 2 }));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn changes_beyond_i32_are_overflows() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(i32::MAX), &conn).unwrap();
+
+            let overflow = InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(1), &conn);
+            assert_eq!(overflow, Err(StockError::Overflow { product_id: soap.id(), bin_location_id: bins[0].id() }));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn stale_balance_does_not_get_saved() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let first = InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+            let stale = InventoryBalance::find(first.id(), &conn).unwrap();
+
+            assert!(first.changed_by(StockChange::on_hand(1)).unwrap().update(&conn).unwrap().is_some());
+            assert_eq!(stale.changed_by(StockChange::on_hand(1)).unwrap().update(&conn).unwrap(), None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn warehouse_stock_is_summed_per_product() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::Reserve]);
+            let (_, other_bins) = warehouse_with_bins(&conn, "WH2", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange { on_hand: 5, allocated: 2 }, &conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[1], StockChange::on_hand(20), &conn).unwrap();
+            InventoryBalance::apply(soap.id(), &other_bins[0], StockChange::on_hand(100), &conn).unwrap();
+
+            assert_eq!(ProductStock::for_warehouse(warehouse.id(), &conn).unwrap(),
+                       vec![ProductStock { product_id: soap.id(), on_hand: 25, allocated: 2, available: 23, expired: 0 }]);
+            assert_eq!(InventoryBalance::for_product(soap.id(), Some(warehouse.id()), &conn).unwrap().len(), 2);
+            assert_eq!(InventoryBalance::for_product(soap.id(), None, &conn).unwrap().len(), 3);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn allocation_takes_first_expiring_lots_and_skips_expired_ones() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::Reserve]);
+            let milk = product(&conn, "MILK");
+            let today = Local::today().naive_local();
+            let lot = |number: &str, days: i64| LotDefinition { lot_number: number.to_string(), manufactured_on: None, expires_on: Some(today + Duration::days(days)) }
+                .for_product(&milk).unwrap()
+                .create(&conn).unwrap();
+            let expired = lot("L1", -2);
+            let late = lot("L2", 60);
+            let early = lot("L3", 5);
+            InventoryBalance::apply_to_lot(milk.id(), Some(expired.id()), &bins[0], StockChange::on_hand(3), &conn).unwrap();
+            InventoryBalance::apply_to_lot(milk.id(), Some(late.id()), &bins[0], StockChange::on_hand(3), &conn).unwrap();
+            InventoryBalance::apply_to_lot(milk.id(), Some(early.id()), &bins[1], StockChange::on_hand(3), &conn).unwrap();
+            InventoryBalance::apply(milk.id(), &bins[1], StockChange::on_hand(3), &conn).unwrap();
+
+            let allocated = InventoryBalance::allocate(milk.id(), warehouse.id(), 8, &conn).unwrap();
+            assert_eq!(allocated, vec![
+                Allocation { bin_location_id: bins[1].id(), lot_id: Some(early.id()), quantity: 3 },
+                Allocation { bin_location_id: bins[0].id(), lot_id: Some(late.id()), quantity: 3 },
+                Allocation { bin_location_id: bins[1].id(), lot_id: None, quantity: 2 }
+            ]);
+
+            let shortage = InventoryBalance::allocate(milk.id(), warehouse.id(), 2, &conn);
+            assert_eq!(shortage, Err(StockError::Shortage { product_id: milk.id(), warehouse_id: warehouse.id(), available: 1 }));
+
+            assert_eq!(ProductStock::for_warehouse(warehouse.id(), &conn).unwrap(),
+                       vec![ProductStock { product_id: milk.id(), on_hand: 12, allocated: 8, available: 1, expired: 3 }]);
+            let levels = StockLevel::of(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap(), &conn).unwrap();
+            let mut reported: Vec<_> = levels.iter().map(|level| (level.balance.lot_id(), level.available, level.expired)).collect();
+            reported.sort();
+            assert_eq!(reported, vec![(Some(expired.id()), 0, 3), (Some(late.id()), 0, 0)]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn strategies_change_the_order_stock_is_taken_in() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve, LocationType::Reserve, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let hierarchy = PackagingHierarchy::load(&soap, &conn).unwrap();
+            PackagingDefinition { name: PALLET.to_string(), quantity: 5, contains: "each".to_string() }
+                .for_product(&soap, &hierarchy).unwrap()
+                .create(&conn).unwrap();
+            for (bin, quantity) in bins.iter().zip([4, 10, 7].iter()) {
+                InventoryBalance::apply(soap.id(), bin, StockChange::on_hand(*quantity), &conn).unwrap();
+            }
+            let taken = |strategy| {
+                let allocations = InventoryBalance::allocate_by(&soap, warehouse.id(), &[], strategy, 12, &conn).unwrap();
+                for allocation in &allocations {
+                    let bin = bins.iter().find(|bin| bin.id() == allocation.bin_location_id).unwrap();
+                    InventoryBalance::apply(soap.id(), bin, StockChange::allocated(-allocation.quantity), &conn).unwrap();
+                }
+                allocations.into_iter().map(|allocation| (allocation.bin_location_id, allocation.quantity)).collect::<Vec<_>>()
+            };
+
+            assert_eq!(taken(AllocationStrategy::LeastBins), vec![(bins[1].id(), 10), (bins[2].id(), 2)]);
+            assert_eq!(taken(AllocationStrategy::FewestPartialPallets), vec![(bins[0].id(), 4), (bins[2].id(), 7), (bins[1].id(), 1)]);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::inventory::balance::models::{InventoryBalance, StockLevel, ProductStock};
+use crate::inventory::reservation::models::Reservation;
+use crate::inventory::transfer::models::StockTransfer;
+use crate::product::models::Product;
+use crate::location::bin::models::BinLocation;
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use serde::{Serialize, Deserialize};
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+/// Stock levels of one product per bin together with their totals. Stock shipped between
+/// warehouses is reported as in transit rather than on hand, stock of expired lots as expired
+/// rather than available. Expired reservations are released before any stock is reported.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductStockReport {
+    product_id: i32,
+    on_hand: i64,
+    allocated: i64,
+    available: i64,
+    expired: i64,
+    in_transit: i64,
+    levels: Vec<StockLevel>
+}
+
+#[get("/inventory/product/<id>?<warehouse>")]
+pub fn by_product(id: i32, warehouse: Option<i32>, conn: PostgresConnection) -> Result<Json<ProductStockReport>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Reservation::release_expired(&*conn)?;
+    let levels = StockLevel::of(InventoryBalance::for_product(product.id(), warehouse, &*conn)?, &*conn)?;
+    Ok(Json(ProductStockReport {
+        product_id: product.id(),
+        on_hand: levels.iter().map(|l| i64::from(l.balance.on_hand())).sum(),
+        allocated: levels.iter().map(|l| i64::from(l.balance.allocated())).sum(),
+        available: levels.iter().map(|l| i64::from(l.available)).sum(),
+        expired: levels.iter().map(|l| i64::from(l.expired)).sum(),
+        in_transit: StockTransfer::quantity_in_transit(product.id(), warehouse, &*conn)?,
+        levels
+    }))
+}
+
+#[get("/inventory/bin/<id>")]
+pub fn by_bin(id: i32, conn: PostgresConnection) -> Result<Json<Vec<StockLevel>>, ApiError> {
+    let bin = BinLocation::find(id, &*conn)?;
+    Reservation::release_expired(&*conn)?;
+    let balances = InventoryBalance::for_bin(bin.id(), &*conn)?;
+    Ok(Json(StockLevel::of(balances, &*conn)?))
+}
+
+#[get("/inventory/warehouse/<id>")]
+pub fn by_warehouse(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ProductStock>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Reservation::release_expired(&*conn)?;
+    Ok(Json(ProductStock::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[get("/inventory/warehouse/<id>/bins")]
+pub fn by_warehouse_bins(id: i32, conn: PostgresConnection) -> Result<Json<Vec<StockLevel>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Reservation::release_expired(&*conn)?;
+    let balances = InventoryBalance::for_warehouse(warehouse.id(), &*conn)?;
+    Ok(Json(StockLevel::of(balances, &*conn)?))
+}
@@ -0,0 +1,443 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use crate::schema::{cycle_count, cycle_count_task};
+use crate::inventory::balance::models::InventoryBalance;
+use crate::domain::DomainError;
+use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+use crate::operator::Operator;
+use diesel::sql_types::{Integer, BigInt, Nullable, Text};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// Variance in units a count may show before the bin has to be counted a second time.
+pub const DEFAULT_RECOUNT_THRESHOLD: i32 = 5;
+
+sql_enum! {
+    /// Share of a warehouse's picked quantity a product accounts for: `a` products make up the
+    /// first 80% of picks, `b` products the next 15% and `c` products the rest.
+    pub enum AbcClass {
+        A => "a",
+        B => "b",
+        C => "c"
+    }
+}
+
+sql_enum! {
+    pub enum CountTaskStatus {
+        Pending => "pending",
+        Recount => "recount",
+        Counted => "counted",
+        Posted => "posted"
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="cycle_count"]
+pub struct CycleCount {
+    id: i32,
+    warehouse_id: i32,
+    blind: bool,
+    recount_threshold: i32,
+    created_by: String,
+    created_at: NaiveDateTime
+}
+
+impl CycleCount {
+
+    pub fn find(count_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<CycleCount, diesel::result::Error> {
+        use crate::schema::cycle_count::dsl::*;
+        cycle_count.find(count_id).first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn is_blind(&self) -> bool {
+        self.blind
+    }
+
+    pub fn tasks(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<CountTask>, diesel::result::Error> {
+        use crate::schema::cycle_count_task::dsl::*;
+        cycle_count_task.filter(cycle_count_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+}
+
+/// One product, or lot of a product, in one bin to be counted.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="cycle_count_task"]
+pub struct CountTask {
+    id: i32,
+    cycle_count_id: i32,
+    bin_location_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    status: CountTaskStatus,
+    system_quantity: Option<i32>,
+    counted_quantity: Option<i32>,
+    counted_by: Option<String>,
+    recount_quantity: Option<i32>,
+    recounted_by: Option<String>,
+    version: i32
+}
+
+impl CountTask {
+
+    pub fn find(task_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<CountTask, diesel::result::Error> {
+        use crate::schema::cycle_count_task::dsl::*;
+        cycle_count_task.find(task_id).first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn cycle_count_id(&self) -> i32 {
+        self.cycle_count_id
+    }
+
+    pub fn status(&self) -> CountTaskStatus {
+        self.status
+    }
+
+    /// The last counted quantity, which is the recount if there was one.
+    pub fn final_quantity(&self) -> Option<i32> {
+        self.recount_quantity.or(self.counted_quantity)
+    }
+
+    /// Counted minus booked quantity, known once the bin has been counted.
+    pub fn variance(&self) -> Option<i32> {
+        Some(self.final_quantity()? - self.system_quantity?)
+    }
+
+    /// Records a count, or the recount if the first count was off by more than the threshold
+    /// of the cycle count. The booked quantity is taken at the time of counting so that stock
+    /// moved before does not show up as a variance. Returns `None` if the task changed meanwhile.
+    pub fn record(self, quantity: i32, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<CountTask>, DomainError> {
+        use crate::schema::cycle_count_task::dsl::*;
+        if quantity < 0 {
+            return Err(DomainError::Invalid(format!("Counted quantity can not be negative, got {}", quantity)));
+        }
+        let count = CycleCount::find(self.cycle_count_id, conn)?;
+        let booked = InventoryBalance::lookup(self.product_id, self.lot_id, self.bin_location_id, conn)?
+            .map_or(0, |balance| balance.on_hand());
+        let stale_filter = cycle_count_task.filter(id.eq(self.id).and(version.eq(self.version)));
+
+        let recorded = match self.status {
+            CountTaskStatus::Pending => {
+                let next = if (quantity - booked).abs() > count.recount_threshold { CountTaskStatus::Recount } else { CountTaskStatus::Counted };
+                diesel::update(stale_filter)
+                    .set((status.eq(next), system_quantity.eq(booked), counted_quantity.eq(quantity),
+                          counted_by.eq(operator.name()), version.eq(version + 1)))
+                    .get_result(conn)
+            },
+            CountTaskStatus::Recount => {
+                if self.counted_by.as_deref() == Some(operator.name()) {
+                    return Err(DomainError::Rejected(format!("Task {} must be recounted by someone other than {}", self.id, operator.name())));
+                }
+                diesel::update(stale_filter)
+                    .set((status.eq(CountTaskStatus::Counted), system_quantity.eq(booked), recount_quantity.eq(quantity),
+                          recounted_by.eq(operator.name()), version.eq(version + 1)))
+                    .get_result(conn)
+            },
+            _ => return Err(DomainError::Rejected(format!("Task {} has already been counted", self.id)))
+        };
+        Ok(recorded.optional()?)
+    }
+
+    /// Books the variance of a counted task as a cycle count adjustment of the bin. Adjusting
+    /// serialised products needs the serial numbers of the units found or missing.
+    pub fn approve(self, serial_numbers: Vec<String>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<CountTask>, DomainError> {
+        use crate::schema::cycle_count_task::dsl::*;
+        let variance = match (self.status, self.variance()) {
+            (CountTaskStatus::Counted, Some(variance)) => variance,
+            _ => return Err(DomainError::Rejected(format!("Only counted tasks can be approved, task {} is {}", self.id, self.status)))
+        };
+        conn.transaction(|| {
+            let posted = diesel::update(cycle_count_task.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set((status.eq(CountTaskStatus::Posted), version.eq(version + 1)))
+                .get_result::<CountTask>(conn)
+                .optional()?;
+            let posted = match posted {
+                Some(posted) => posted,
+                None => return Ok(None)
+            };
+            if variance != 0 {
+                let (from, to) = if variance < 0 { (Some(self.bin_location_id), None) } else { (None, Some(self.bin_location_id)) };
+                NewStockMovement::new(self.product_id, from, to, variance.abs(), MovementReason::CycleCount, operator)
+                    .in_lot(self.lot_id)
+                    .with_serials(serial_numbers)
+                    .with_reference(&format!("COUNT-{}", self.cycle_count_id))
+                    .post(conn)?;
+            }
+            Ok(Some(posted))
+        })
+    }
+}
+
+/// Which bins a cycle count covers.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CountSelection {
+    Zone(i32),
+    AbcClass(AbcClass),
+    /// A random sample of this many bins.
+    Sample(i64)
+}
+
+/// Body of a `POST /warehouse/<id>/cycle-count` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountPlan {
+    pub selection: CountSelection,
+    #[serde(default)]
+    pub blind: bool,
+    #[serde(default = "default_recount_threshold")]
+    pub recount_threshold: i32
+}
+
+fn default_recount_threshold() -> i32 {
+    DEFAULT_RECOUNT_THRESHOLD
+}
+
+impl CountPlan {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.recount_threshold < 0 {
+            return Err(String::from("Recount threshold can not be negative"));
+        }
+        if let CountSelection::Sample(size) = self.selection {
+            if size <= 0 {
+                return Err(format!("Sample size must be positive, got {}", size));
+            }
+        }
+        Ok(())
+    }
+
+    /// Creates the cycle count with one task per product, or lot of a product, in each bin
+    /// selected in `warehouse`.
+    pub fn schedule(self, warehouse: i32, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<CycleCount, diesel::result::Error> {
+        conn.transaction(|| {
+            let count: CycleCount = diesel::insert_into(cycle_count::table)
+                .values((
+                    cycle_count::warehouse_id.eq(warehouse),
+                    cycle_count::blind.eq(self.blind),
+                    cycle_count::recount_threshold.eq(self.recount_threshold),
+                    cycle_count::created_by.eq(operator.name())
+                ))
+                .get_result(conn)?;
+            let tasks: Vec<NewCountTask> = selected_slots(warehouse, &self.selection, conn)?
+                .into_iter()
+                .map(|slot| NewCountTask {
+                    cycle_count_id: count.id,
+                    bin_location_id: slot.bin_location_id,
+                    product_id: slot.product_id,
+                    lot_id: slot.lot_id
+                })
+                .collect();
+            diesel::insert_into(cycle_count_task::table)
+                .values(&tasks)
+                .execute(conn)?;
+            Ok(count)
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="cycle_count_task"]
+struct NewCountTask {
+    cycle_count_id: i32,
+    bin_location_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>
+}
+
+/// A product, or lot of a product, in a bin which can be counted.
+#[derive(Debug, QueryableByName)]
+struct CountSlot {
+    #[sql_type="Integer"]
+    bin_location_id: i32,
+    #[sql_type="Integer"]
+    product_id: i32,
+    #[sql_type="Nullable<Integer>"]
+    lot_id: Option<i32>
+}
+
+/// Everything booked in a bin, and the products a bin is the home location of even while
+/// nothing of them is booked there, so that stock missing from empty bins is counted too.
+const SLOTS: &str =
+    "slots as ( \
+        select b.bin_location_id, b.product_id, b.lot_id from inventory_balance b \
+        union \
+        select h.bin_location_id, h.product_id, null::int from home_location h \
+        where not exists (select 1 from inventory_balance b \
+                          where b.bin_location_id = h.bin_location_id and b.product_id = h.product_id) \
+    )";
+
+/// The bins of `warehouse` picked by `selection`, with what is to be counted in each of them.
+fn selected_slots(warehouse: i32, selection: &CountSelection, conn: &impl Connection<Backend=Pg>) -> Result<Vec<CountSlot>, diesel::result::Error> {
+    match selection {
+        CountSelection::Zone(zone) => diesel::sql_query(format!(
+            "with {} \
+             select s.* from bin_location l join slots s on s.bin_location_id = l.id \
+             where l.warehouse_id = $1 and l.zone_id = $2 order by l.location_code, s.product_id, s.lot_id", SLOTS))
+            .bind::<Integer, _>(warehouse)
+            .bind::<Integer, _>(*zone)
+            .load(conn),
+        CountSelection::AbcClass(class) => diesel::sql_query(format!(
+            "with {}, picks as ( \
+                select m.product_id, sum(m.quantity) as picked from stock_movement m \
+                join bin_location l on l.id = m.from_location_id \
+                where m.reason = 'pick' and l.warehouse_id = $1 and m.created_at > now() - interval '90 days' \
+                group by m.product_id \
+            ), ranked as ( \
+                select product_id, picked, sum(picked) over (order by picked desc, product_id) as running, \
+                       sum(picked) over () as total from picks \
+            ), classes as ( \
+                select product_id, case when running - picked < 0.8 * total then 'a' \
+                                        when running - picked < 0.95 * total then 'b' \
+                                        else 'c' end as abc_class from ranked \
+            ) \
+            select s.* from bin_location l join slots s on s.bin_location_id = l.id \
+            left join classes c on c.product_id = s.product_id \
+            where l.warehouse_id = $1 and coalesce(c.abc_class, 'c') = $2 order by l.location_code, s.product_id, s.lot_id", SLOTS))
+            .bind::<Integer, _>(warehouse)
+            .bind::<Text, _>(class.as_str())
+            .load(conn),
+        CountSelection::Sample(size) => diesel::sql_query(format!(
+            "with {}, sample as ( \
+                select id, location_code from bin_location where warehouse_id = $1 order by random() limit $2 \
+            ) \
+            select s.* from sample l join slots s on s.bin_location_id = l.id order by l.location_code, s.product_id, s.lot_id", SLOTS))
+            .bind::<Integer, _>(warehouse)
+            .bind::<BigInt, _>(*size)
+            .load(conn)
+    }
+}
+
+/// A task as shown to the counter. Blind counts hide the booked quantity until counting is done,
+/// and the first count while the bin waits for its recount.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CountTaskView {
+    #[serde(flatten)]
+    pub task: CountTask,
+    pub expected_quantity: Option<i32>,
+    pub variance: Option<i32>
+}
+
+/// A cycle count and its tasks.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CountSheet {
+    #[serde(flatten)]
+    pub count: CycleCount,
+    pub tasks: Vec<CountTaskView>
+}
+
+impl CountSheet {
+    pub fn load(count: CycleCount, conn: &impl Connection<Backend=Pg>) -> Result<CountSheet, diesel::result::Error> {
+        let tasks = count.tasks(conn)?
+            .into_iter()
+            .map(|task| CountTaskView::of(task, count.blind, conn))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CountSheet { count, tasks })
+    }
+}
+
+impl CountTaskView {
+    pub fn of(task: CountTask, blind: bool, conn: &impl Connection<Backend=Pg>) -> Result<CountTaskView, diesel::result::Error> {
+        let done = task.status == CountTaskStatus::Counted || task.status == CountTaskStatus::Posted;
+        if blind && !done {
+            let hidden = CountTask { system_quantity: None, counted_quantity: None, counted_by: None, ..task };
+            return Ok(CountTaskView { task: hidden, expected_quantity: None, variance: None });
+        }
+        let expected_quantity = if done {
+            None
+        } else {
+            Some(InventoryBalance::lookup(task.product_id, task.lot_id, task.bin_location_id, conn)?.map_or(0, |b| b.on_hand()))
+        };
+        let variance = task.variance();
+        Ok(CountTaskView { task, expected_quantity, variance })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+    use crate::inventory::count::models::{CountPlan, CountSelection, CountSheet, CountTaskStatus, AbcClass};
+    use crate::inventory::putaway::models::HomeLocationDefinition;
+    use crate::domain::DomainError;
+    use crate::operator::Operator;
+
+    #[test]
+    fn variances_above_threshold_need_a_recount_by_someone_else() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let (alice, bob) = (Operator::new("alice"), Operator::new("bob"));
+            NewStockMovement::new(soap.id(), None, Some(bins[0].id()), 20, MovementReason::Receipt, &alice).post(&conn).unwrap();
+
+            let plan = CountPlan { selection: CountSelection::Zone(bins[0].zone_id()), blind: true, recount_threshold: 2 };
+            let count = plan.schedule(warehouse.id(), &alice, &conn).unwrap();
+            let sheet = CountSheet::load(count.clone(), &conn).unwrap();
+            assert_eq!(sheet.tasks.len(), 1);
+            assert_eq!(sheet.tasks[0].expected_quantity, None);
+
+            let task = sheet.tasks.into_iter().next().unwrap().task;
+            let task = task.record(15, &alice, &conn).unwrap().unwrap();
+            assert_eq!(task.status(), CountTaskStatus::Recount);
+            assert!(matches!(task.clone().record(16, &alice, &conn), Err(DomainError::Rejected(_))));
+            assert!(matches!(task.clone().record(-1, &bob, &conn), Err(DomainError::Invalid(_))));
+            let recount = CountSheet::load(count.clone(), &conn).unwrap().tasks.remove(0);
+            assert_eq!((recount.task.counted_quantity, recount.task.counted_by, recount.task.system_quantity), (None, None, None));
+            let task = task.record(16, &bob, &conn).unwrap().unwrap();
+            assert_eq!((task.status(), task.variance()), (CountTaskStatus::Counted, Some(-4)));
+
+            let posted = task.approve(Vec::new(), &bob, &conn).unwrap().unwrap();
+            assert_eq!(posted.status(), CountTaskStatus::Posted);
+            assert_eq!(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0].on_hand(), 16);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn empty_home_locations_in_the_selected_bins_are_counted_too() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::PickFace, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let gel = product(&conn, "GEL");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+            HomeLocationDefinition { bin_location_id: bins[1].id() }.for_product(&gel, &bins[1]).unwrap().create(&conn).unwrap();
+
+            let plan = CountPlan { selection: CountSelection::Zone(bins[0].zone_id()), blind: false, recount_threshold: 0 };
+            let sheet = CountSheet::load(plan.schedule(warehouse.id(), &Operator::new("clerk"), &conn).unwrap(), &conn).unwrap();
+            let tasks: Vec<_> = sheet.tasks.iter()
+                .map(|view| (view.task.bin_location_id, view.task.product_id, view.expected_quantity))
+                .collect();
+            assert_eq!(tasks, vec![(bins[0].id(), soap.id(), Some(5)), (bins[1].id(), gel.id(), Some(0))]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn abc_classes_follow_picked_quantities() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let fast = product(&conn, "FAST");
+            let slow = product(&conn, "SLOW");
+            let clerk = Operator::new("clerk");
+            for (item, picked) in &[(&fast, 90), (&slow, 10)] {
+                InventoryBalance::apply(item.id(), &bins[0], StockChange::on_hand(100), &conn).unwrap();
+                NewStockMovement::new(item.id(), Some(bins[0].id()), None, *picked, MovementReason::Pick, &clerk).post(&conn).unwrap();
+            }
+
+            let plan = CountPlan { selection: CountSelection::AbcClass(AbcClass::A), blind: false, recount_threshold: 0 };
+            let sheet = CountSheet::load(plan.schedule(warehouse.id(), &clerk, &conn).unwrap(), &conn).unwrap();
+            assert_eq!(sheet.tasks.len(), 1);
+            assert_eq!(sheet.tasks[0].expected_quantity, Some(10));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,65 @@
+use crate::inventory::count::models::{CycleCount, CountTask, CountPlan, CountSheet, CountTaskView};
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use serde::{Serialize, Deserialize};
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+#[post("/warehouse/<id>/cycle-count", format="application/json", data="<plan>")]
+pub fn post(id: i32, plan: Json<CountPlan>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<CountSheet>>, ApiError> {
+    let operator = operator?;
+    let warehouse = Warehouse::find(id, &*conn)?;
+    let plan = plan.into_inner();
+    plan.validate().map_err(ApiError::UnprocessableEntity)?;
+    let count = plan.schedule(warehouse.id(), &operator, &*conn)?;
+    Ok(Created(format!("/cycle-count/{}", count.id()), Some(Json(CountSheet::load(count, &*conn)?))))
+}
+
+#[get("/cycle-count/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<CountSheet>, ApiError> {
+    let count = CycleCount::find(id, &*conn)?;
+    Ok(Json(CountSheet::load(count, &*conn)?))
+}
+
+/// Body of a `POST /cycle-count-task/<id>/count` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CountedQuantity {
+    quantity: i32
+}
+
+#[post("/cycle-count-task/<id>/count", format="application/json", data="<counted>")]
+pub fn count(id: i32, counted: Json<CountedQuantity>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<CountTaskView>, ApiError> {
+    let operator = operator?;
+    let task = CountTask::find(id, &*conn)?;
+    match task.record(counted.quantity, &operator, &*conn)? {
+        Some(recorded) => task_view(recorded, &conn),
+        None => Err(ApiError::Conflict(format!("Task {} has been counted meanwhile", id)))
+    }
+}
+
+/// Body of a `POST /cycle-count-task/<id>/approve` request.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Approval {
+    #[serde(default)]
+    serial_numbers: Vec<String>
+}
+
+#[post("/cycle-count-task/<id>/approve", data="<approval>")]
+pub fn approve(id: i32, approval: Option<Json<Approval>>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<CountTaskView>, ApiError> {
+    let operator = operator?;
+    let task = CountTask::find(id, &*conn)?;
+    let approval = approval.map(Json::into_inner).unwrap_or_default();
+    match task.approve(approval.serial_numbers, &operator, &*conn)? {
+        Some(posted) => task_view(posted, &conn),
+        None => Err(ApiError::Conflict(format!("Task {} has been changed meanwhile", id)))
+    }
+}
+
+fn task_view(task: CountTask, conn: &PostgresConnection) -> Result<Json<CountTaskView>, ApiError> {
+    let count = CycleCount::find(task.cycle_count_id(), &**conn)?;
+    Ok(Json(CountTaskView::of(task, count.is_blind(), &**conn)?))
+}
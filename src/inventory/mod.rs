@@ -0,0 +1,9 @@
+pub mod balance;
+pub mod movement;
+pub mod transfer;
+pub mod serial;
+pub mod reservation;
+pub mod count;
+pub mod reorder;
+pub mod replenishment;
+pub mod putaway;
\ No newline at end of file
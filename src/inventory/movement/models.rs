@@ -0,0 +1,401 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use std::convert::TryFrom;
+use chrono::NaiveDateTime;
+use crate::schema::stock_movement;
+use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError};
+use crate::location::bin::models::BinLocation;
+use crate::product::lot::models::Lot;
+use crate::product::models::Product;
+use crate::inventory::serial::models::SerialUnit;
+use crate::operator::Operator;
+use crate::pagination::PageRequest;
+use diesel::sql_types::{Integer, BigInt, Nullable};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    /// Why stock moved, which also determines whether it enters, leaves or moves within
+    /// the warehouse.
+    pub enum MovementReason {
+        Receipt => "receipt",
+        Pick => "pick",
+        Adjustment => "adjustment",
+        Transfer => "transfer",
+        TransferOut => "transfer_out",
+        TransferIn => "transfer_in",
+        CycleCount => "cycle_count"
+    }
+}
+
+impl MovementReason {
+    fn check_locations(&self, from: Option<i32>, to: Option<i32>) -> Result<(), String> {
+        match (self, from, to) {
+            (MovementReason::Receipt, None, Some(_)) | (MovementReason::TransferIn, None, Some(_)) => Ok(()),
+            (MovementReason::Pick, Some(_), None) | (MovementReason::TransferOut, Some(_), None) => Ok(()),
+            (MovementReason::Adjustment, Some(_), None) | (MovementReason::Adjustment, None, Some(_)) => Ok(()),
+            (MovementReason::CycleCount, Some(_), None) | (MovementReason::CycleCount, None, Some(_)) => Ok(()),
+            (MovementReason::Transfer, Some(source), Some(target)) if source != target => Ok(()),
+            (reason, _, _) => Err(format!("A {} can not move stock from {:?} to {:?}", reason, from, to))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="stock_movement"]
+pub struct StockMovement {
+    id: i32,
+    product_id: i32,
+    from_location_id: Option<i32>,
+    to_location_id: Option<i32>,
+    quantity: i32,
+    reason: MovementReason,
+    user_name: String,
+    reference: Option<String>,
+    created_at: NaiveDateTime,
+    lot_id: Option<i32>
+}
+
+impl StockMovement {
+
+    pub fn find(movement_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<StockMovement, diesel::result::Error> {
+        use crate::schema::stock_movement::dsl::*;
+        stock_movement.find(movement_id).first(conn)
+    }
+
+    /// Movement made for the document `document` for the given reason.
+    pub fn for_document(document: &str, movement_reason: MovementReason, conn: &impl Connection<Backend=Pg>) -> Result<StockMovement, diesel::result::Error> {
+        use crate::schema::stock_movement::dsl::*;
+        stock_movement
+            .filter(reference.eq(document).and(reason.eq(movement_reason)))
+            .order(id.desc())
+            .first(conn)
+    }
+
+    /// Newest movements first, optionally only those of a product or touching a bin.
+    pub fn page(product: Option<i32>, location: Option<i32>, request: PageRequest, conn: &impl Connection<Backend=Pg>) -> Result<(Vec<StockMovement>, i64), diesel::result::Error> {
+        use crate::schema::stock_movement::dsl::*;
+        let filtered = || {
+            let mut query = stock_movement.into_boxed();
+            if let Some(product) = product {
+                query = query.filter(product_id.eq(product));
+            }
+            if let Some(location) = location {
+                query = query.filter(from_location_id.eq(location).or(to_location_id.eq(location)));
+            }
+            query
+        };
+        let total = filtered().count().get_result::<i64>(conn)?;
+        let movements = filtered()
+            .order(id.desc())
+            .limit(request.limit)
+            .offset(request.offset)
+            .load(conn)?;
+        Ok((movements, total))
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn from_location_id(&self) -> Option<i32> {
+        self.from_location_id
+    }
+
+    pub fn to_location_id(&self) -> Option<i32> {
+        self.to_location_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewStockMovement {
+    product_id: i32,
+    from_location_id: Option<i32>,
+    to_location_id: Option<i32>,
+    quantity: i32,
+    reason: MovementReason,
+    user_name: String,
+    reference: Option<String>,
+    lot_id: Option<i32>,
+    serial_numbers: Vec<String>
+}
+
+impl NewStockMovement {
+    pub fn new(product: i32, from: Option<i32>, to: Option<i32>, quantity: i32, reason: MovementReason, operator: &Operator) -> NewStockMovement {
+        NewStockMovement {
+            product_id: product,
+            from_location_id: from,
+            to_location_id: to,
+            quantity,
+            reason,
+            user_name: operator.name().to_string(),
+            reference: None,
+            lot_id: None,
+            serial_numbers: Vec::new()
+        }
+    }
+
+    /// Lot the moved stock belongs to, which must be a lot of the moved product.
+    pub fn in_lot(self, lot: Option<i32>) -> NewStockMovement {
+        NewStockMovement { lot_id: lot, ..self }
+    }
+
+    /// Serial numbers of the moved units, required for serialised products.
+    pub fn with_serials(self, serial_numbers: Vec<String>) -> NewStockMovement {
+        NewStockMovement { serial_numbers, ..self }
+    }
+
+    /// Document the movement was made for, e.g. a purchase order or a count.
+    pub fn with_reference(self, reference: &str) -> NewStockMovement {
+        NewStockMovement { reference: Some(reference.to_string()), ..self }
+    }
+
+    /// Changes the balances of both bins and records the movement, all or nothing.
+    pub fn post(self, conn: &impl Connection<Backend=Pg>) -> Result<StockMovement, StockError> {
+        use crate::schema::stock_movement::dsl::*;
+        conn.transaction(|| {
+            self.reason.check_locations(self.from_location_id, self.to_location_id)
+                .map_err(StockError::InvalidMovement)?;
+            if self.quantity <= 0 {
+                return Err(StockError::InvalidMovement(format!("Moved quantity must be positive, got {}", self.quantity)));
+            }
+            if let Some(lot) = self.lot_id {
+                if Lot::find(lot, conn)?.product_id() != self.product_id {
+                    return Err(StockError::InvalidMovement(format!("Lot {} is not a lot of product {}", lot, self.product_id)));
+                }
+            }
+
+            let source = self.from_location_id.map(|bin| BinLocation::find(bin, conn)).transpose()?;
+            let target = self.to_location_id.map(|bin| BinLocation::find(bin, conn)).transpose()?;
+            if let (Some(source), Some(target)) = (&source, &target) {
+                if source.warehouse_id() != target.warehouse_id() {
+                    return Err(StockError::InvalidMovement(String::from("Stock can only be moved between bins of the same warehouse")));
+                }
+            }
+
+            if let Some(ref source) = source {
+                InventoryBalance::apply_to_lot(self.product_id, self.lot_id, source, StockChange::on_hand(-self.quantity), conn)?;
+            }
+            if let Some(ref target) = target {
+                InventoryBalance::apply_to_lot(self.product_id, self.lot_id, target, StockChange::on_hand(self.quantity), conn)?;
+            }
+
+            let movement: StockMovement = diesel::insert_into(stock_movement)
+                .values((
+                    product_id.eq(self.product_id),
+                    from_location_id.eq(self.from_location_id),
+                    to_location_id.eq(self.to_location_id),
+                    quantity.eq(self.quantity),
+                    reason.eq(self.reason),
+                    user_name.eq(&self.user_name),
+                    reference.eq(&self.reference),
+                    lot_id.eq(self.lot_id)
+                ))
+                .get_result(conn)?;
+
+            if Product::find(self.product_id, conn)?.is_serialised() {
+                SerialUnit::move_with(&movement, &self.serial_numbers, conn)?;
+            } else if !self.serial_numbers.is_empty() {
+                return Err(StockError::InvalidMovement(format!("Product {} is not tracked by serial number", self.product_id)));
+            }
+            Ok(movement)
+        })
+    }
+}
+
+/// Body of a `POST /inventory/movement` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MovementRequest {
+    pub product_id: i32,
+    #[serde(default)]
+    pub from_location_id: Option<i32>,
+    #[serde(default)]
+    pub to_location_id: Option<i32>,
+    pub quantity: crate::product::packaging::models::Quantity,
+    pub reason: MovementReason,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub lot_id: Option<i32>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+/// A balance whose on hand quantity differs from what its movements add up to.
+#[derive(Debug, PartialEq, Clone, QueryableByName, Serialize, Deserialize)]
+pub struct Drift {
+    #[sql_type="Integer"]
+    pub product_id: i32,
+    #[sql_type="Integer"]
+    pub bin_location_id: i32,
+    #[sql_type="Nullable<Integer>"]
+    pub lot_id: Option<i32>,
+    #[sql_type="BigInt"]
+    pub on_hand: i64,
+    #[sql_type="BigInt"]
+    pub ledger_on_hand: i64
+}
+
+/// Outcome of rebuilding a drifting balance, which fails if the ledger quantity is
+/// smaller than what is already allocated from the bin.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rebuilt {
+    #[serde(flatten)]
+    pub drift: Drift,
+    pub corrected: bool
+}
+
+impl Drift {
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Drift>, diesel::result::Error> {
+        diesel::sql_query(
+            "with ledger as ( \
+                select product_id, to_location_id as bin_location_id, lot_id, quantity from stock_movement \
+                where to_location_id is not null \
+                union all \
+                select product_id, from_location_id as bin_location_id, lot_id, -quantity from stock_movement \
+                where from_location_id is not null \
+            ), totals as ( \
+                select product_id, bin_location_id, lot_id, sum(quantity) as ledger_on_hand \
+                from ledger group by product_id, bin_location_id, lot_id \
+            ) \
+            select coalesce(b.product_id, t.product_id) as product_id, \
+                   coalesce(b.bin_location_id, t.bin_location_id) as bin_location_id, \
+                   coalesce(b.lot_id, t.lot_id) as lot_id, \
+                   coalesce(b.on_hand, 0)::bigint as on_hand, \
+                   coalesce(t.ledger_on_hand, 0)::bigint as ledger_on_hand \
+            from totals t \
+            full outer join inventory_balance b \
+                on b.product_id = t.product_id and b.bin_location_id = t.bin_location_id \
+               and b.lot_id is not distinct from t.lot_id \
+            join bin_location l on l.id = coalesce(b.bin_location_id, t.bin_location_id) \
+            where l.warehouse_id = $1 and coalesce(b.on_hand, 0) <> coalesce(t.ledger_on_hand, 0) \
+            order by 1, 2")
+            .bind::<Integer, _>(warehouse)
+            .load(conn)
+    }
+
+    /// Resets the on hand quantity of every drifting balance to what the ledger says.
+    pub fn rebuild(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Rebuilt>, diesel::result::Error> {
+        conn.transaction(|| {
+            Drift::for_warehouse(warehouse, conn)?
+                .into_iter()
+                .map(|drift| {
+                    let bin = BinLocation::find(drift.bin_location_id, conn)?;
+                    let corrected = match i32::try_from(drift.ledger_on_hand - drift.on_hand) {
+                        Ok(difference) => match InventoryBalance::apply_to_lot(drift.product_id, drift.lot_id, &bin, StockChange::on_hand(difference), conn) {
+                            Ok(_) => true,
+                            Err(StockError::Database(e)) => return Err(e),
+                            Err(_) => false
+                        },
+                        Err(_) => false
+                    };
+                    Ok(Rebuilt { drift, corrected })
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use diesel::prelude::*;
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError};
+    use crate::inventory::movement::models::{NewStockMovement, MovementReason, StockMovement, Drift};
+    use crate::operator::Operator;
+    use crate::pagination::PageRequest;
+
+    #[test]
+    fn receipt_and_transfer_update_balances_and_ledger() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+
+            NewStockMovement::new(soap.id(), None, Some(bins[0].id()), 10, MovementReason::Receipt, &clerk)
+                .with_reference("PO-1")
+                .post(&conn).unwrap();
+            NewStockMovement::new(soap.id(), Some(bins[0].id()), Some(bins[1].id()), 4, MovementReason::Transfer, &clerk)
+                .post(&conn).unwrap();
+
+            let balances = InventoryBalance::for_product(soap.id(), None, &conn).unwrap();
+            assert_eq!(balances.iter().map(|b| b.on_hand()).collect::<Vec<_>>(), vec![6, 4]);
+            let (movements, total) = StockMovement::page(Some(soap.id()), Some(bins[1].id()), PageRequest::default(), &conn).unwrap();
+            assert_eq!(total, 1);
+            assert_eq!(movements[0].user_name, "clerk");
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn failed_movement_leaves_no_trace() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+
+            let moved = NewStockMovement::new(soap.id(), Some(bins[0].id()), Some(bins[1].id()), 1, MovementReason::Transfer, &clerk)
+                .post(&conn);
+            assert!(matches!(moved, Err(StockError::InsufficientStock { .. })));
+            assert_eq!(StockMovement::page(None, None, PageRequest::default(), &conn).unwrap().1, 0);
+            assert_eq!(InventoryBalance::for_product(soap.id(), None, &conn).unwrap().iter().map(|b| b.on_hand()).sum::<i32>(), 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn movement_locations_must_match_reason() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let receipt_from_bin = NewStockMovement::new(soap.id(), Some(bins[0].id()), None, 1, MovementReason::Receipt, &Operator::new("clerk"))
+                .post(&conn);
+            assert!(matches!(receipt_from_bin, Err(StockError::InvalidMovement(_))));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn movements_can_not_be_changed_afterwards() -> Result<(), String> {
+        use crate::schema::stock_movement::dsl::*;
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let movement = NewStockMovement::new(soap.id(), None, Some(bins[0].id()), 3, MovementReason::Receipt, &Operator::new("clerk"))
+                .post(&conn).unwrap();
+
+            assert!(diesel::update(stock_movement.find(movement.id())).set(quantity.eq(30)).execute(&conn).is_err());
+            assert!(diesel::delete(stock_movement.find(movement.id())).execute(&conn).is_err());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn drifting_balances_are_flagged_and_rebuilt_from_ledger() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            NewStockMovement::new(soap.id(), None, Some(bins[0].id()), 8, MovementReason::Receipt, &Operator::new("clerk"))
+                .post(&conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(2), &conn).unwrap();
+
+            let drift = Drift::for_warehouse(warehouse.id(), &conn).unwrap();
+            assert_eq!(drift, vec![Drift { product_id: soap.id(), bin_location_id: bins[0].id(), lot_id: None, on_hand: 10, ledger_on_hand: 8 }]);
+
+            let rebuilt = Drift::rebuild(warehouse.id(), &conn).unwrap();
+            assert!(rebuilt[0].corrected);
+            assert!(Drift::for_warehouse(warehouse.id(), &conn).unwrap().is_empty());
+            Ok(())
+        })
+    }
+}
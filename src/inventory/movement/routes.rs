@@ -0,0 +1,75 @@
+use crate::inventory::movement::models::{StockMovement, NewStockMovement, MovementRequest, Drift, Rebuilt};
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use rocket::request::LenientForm;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+use crate::pagination::{Page, PageRequest};
+
+
+#[post("/inventory/movement", format="application/json", data="<movement>")]
+pub fn post(movement: Json<MovementRequest>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<StockMovement>>, ApiError> {
+    let operator = operator?;
+    let movement = movement.into_inner();
+    let product = Product::find(movement.product_id, &*conn)?;
+    let quantity = movement.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    let mut new_movement = NewStockMovement::new(product.id(), movement.from_location_id, movement.to_location_id,
+                                                 quantity, movement.reason, &operator)
+        .in_lot(movement.lot_id)
+        .with_serials(movement.serial_numbers);
+    if let Some(ref reference) = movement.reference {
+        new_movement = new_movement.with_reference(reference);
+    }
+    let posted = new_movement.post(&*conn)?;
+    Ok(Created(format!("/inventory/movement/{}", posted.id()), Some(Json(posted))))
+}
+
+#[get("/inventory/movement/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<StockMovement>, ApiError> {
+    Ok(Json(StockMovement::find(id, &*conn)?))
+}
+
+#[derive(Debug, FromForm)]
+pub struct ListQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    product: Option<i32>,
+    location: Option<i32>
+}
+
+impl ListQuery {
+    fn link(&self, request: PageRequest) -> String {
+        let mut link = format!("/inventory/movement?limit={}&offset={}", request.limit, request.offset);
+        if let Some(product) = self.product {
+            link.push_str(&format!("&product={}", product));
+        }
+        if let Some(location) = self.location {
+            link.push_str(&format!("&location={}", location));
+        }
+        link
+    }
+}
+
+#[get("/inventory/movement?<query..>")]
+pub fn list(query: LenientForm<ListQuery>, conn: PostgresConnection) -> Result<Json<Page<StockMovement>>, ApiError> {
+    let request = PageRequest::new(query.limit, query.offset);
+    let (movements, total) = StockMovement::page(query.product, query.location, request, &*conn)?;
+    Ok(Json(Page::new(movements, total, request, |page| query.link(page))))
+}
+
+#[get("/inventory/warehouse/<id>/reconciliation")]
+pub fn drift(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Drift>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(Drift::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[post("/inventory/warehouse/<id>/reconciliation")]
+pub fn rebuild(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Rebuilt>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(Drift::rebuild(warehouse.id(), &*conn)?))
+}
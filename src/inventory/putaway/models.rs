@@ -0,0 +1,433 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::{goods_receipt_line, home_location, purchase_order_line};
+use crate::inventory::balance::models::StockError;
+use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+use crate::location::bin::models::{BinLoad, BinLocation, LocationType};
+use crate::location::zone::models::Zone;
+use crate::product::models::Product;
+use crate::purchasing::receiving::models::{GoodsReceipt, GoodsReceiptLine};
+use crate::purchasing::inspection::models::Inspection;
+use crate::operator::Operator;
+use diesel::sql_types::{BigInt, Bool, Integer, Nullable};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+use std::collections::HashMap;
+
+sql_enum! {
+    /// Why a bin was suggested, from the most to the least preferred.
+    pub enum PutawayReason {
+        HomeLocation => "home_location",
+        Consolidation => "consolidation",
+        EmptyBin => "empty_bin"
+    }
+}
+
+/// A bin a product is always stored in, e.g. its pick face or a dedicated reserve slot.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="home_location"]
+pub struct HomeLocation {
+    id: i32,
+    product_id: i32,
+    bin_location_id: i32
+}
+
+impl HomeLocation {
+
+    pub fn find(home_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<HomeLocation, diesel::result::Error> {
+        use crate::schema::home_location::dsl::*;
+        home_location.find(home_id).first(conn)
+    }
+
+    pub fn for_product(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<HomeLocation>, diesel::result::Error> {
+        use crate::schema::home_location::dsl::*;
+        home_location.filter(product_id.eq(product)).order(id.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::home_location::dsl::*;
+        conn.transaction(|| {
+            delete(home_location.filter(id.eq(self.id))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `POST /product/<id>/home-location` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HomeLocationDefinition {
+    pub bin_location_id: i32
+}
+
+impl HomeLocationDefinition {
+    pub fn for_product(self, product: &Product, bin: &BinLocation) -> Result<NewHomeLocation, String> {
+        if let LocationType::Staging | LocationType::Dock | LocationType::Returns = bin.location_type() {
+            return Err(format!("Bin {} is a {} bin and can not be a home location", bin.location_code(), bin.location_type()));
+        }
+        Ok(NewHomeLocation {
+            product_id: product.id(),
+            bin_location_id: bin.id()
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="home_location"]
+pub struct NewHomeLocation {
+    product_id: i32,
+    bin_location_id: i32
+}
+
+impl NewHomeLocation {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<HomeLocation, diesel::result::Error> {
+        use crate::schema::home_location::dsl::*;
+        conn.transaction(|| {
+            diesel::insert_into(home_location)
+                .values(self)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Categories a zone is reserved for, e.g. hazardous or chilled goods. A restricted zone only
+/// takes products of its categories, and products of such a category only go to zones
+/// restricted to it. Body of a `PUT /zone/<id>/restriction` request.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZoneRestriction {
+    pub category_ids: Vec<i32>
+}
+
+impl ZoneRestriction {
+    pub fn load(zone: &Zone, conn: &impl Connection<Backend=Pg>) -> Result<ZoneRestriction, diesel::result::Error> {
+        use crate::schema::zone_restriction::dsl::*;
+        let category_ids = zone_restriction
+            .filter(zone_id.eq(zone.id()))
+            .select(category_id)
+            .order(category_id.asc())
+            .load(conn)?;
+        Ok(ZoneRestriction { category_ids })
+    }
+
+    /// Replaces the categories the zone is restricted to, no categories lift the restriction.
+    pub fn apply(self, zone: &Zone, conn: &impl Connection<Backend=Pg>) -> Result<ZoneRestriction, diesel::result::Error> {
+        use crate::schema::zone_restriction::dsl::*;
+        conn.transaction(|| {
+            delete(zone_restriction.filter(zone_id.eq(zone.id()))).execute(conn)?;
+            let restrictions: Vec<_> = self.category_ids.iter()
+                .map(|category| (zone_id.eq(zone.id()), category_id.eq(*category)))
+                .collect();
+            diesel::insert_into(zone_restriction)
+                .values(&restrictions)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            ZoneRestriction::load(zone, conn)
+        })
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+struct Candidate {
+    #[sql_type="Integer"]
+    bin_location_id: i32,
+    #[sql_type="Bool"]
+    home: bool,
+    #[sql_type="BigInt"]
+    same_product: i64
+}
+
+/// Bins of the warehouse of `origin` which may store `product`, best first: its home locations,
+/// then bins already holding it, then empty bins. Within each group bins in the zone of `origin`
+/// and aisles close to it come first. Bins holding other products are left out, as are zones
+/// the product is restricted from. With `only` just that bin is checked.
+fn candidates(product: &Product, origin: &BinLocation, only: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Candidate>, diesel::result::Error> {
+    diesel::sql_query(
+        "select bin.id as bin_location_id, \
+                exists (select 1 from home_location h where h.bin_location_id = bin.id and h.product_id = $1) as home, \
+                coalesce((select sum(b.on_hand) from inventory_balance b \
+                          where b.bin_location_id = bin.id and b.product_id = $1), 0)::bigint as same_product \
+         from bin_location bin \
+         join warehouse_aisle aisle on aisle.id = bin.aisle_id \
+         join bin_location origin on origin.id = $3 \
+         join warehouse_aisle origin_aisle on origin_aisle.id = origin.aisle_id \
+         where bin.warehouse_id = origin.warehouse_id \
+           and ($4::int is null or bin.id = $4) \
+           and (bin.location_type = 'reserve' \
+                or exists (select 1 from home_location h where h.bin_location_id = bin.id and h.product_id = $1)) \
+           and not exists (select 1 from inventory_balance b \
+                           where b.bin_location_id = bin.id and b.product_id <> $1 and b.on_hand > 0) \
+           and (exists (select 1 from zone_restriction r \
+                        join product_category_assignment a on a.category_id = r.category_id \
+                        where r.zone_id = bin.zone_id and a.product_id = $2) \
+                or (not exists (select 1 from zone_restriction r where r.zone_id = bin.zone_id) \
+                    and not exists (select 1 from zone_restriction r \
+                                    join warehouse_zone z on z.id = r.zone_id \
+                                    join product_category_assignment a on a.category_id = r.category_id \
+                                    where z.warehouse_id = origin.warehouse_id and a.product_id = $2))) \
+         order by home desc, same_product > 0 desc, bin.zone_id <> origin.zone_id, \
+                  abs(aisle.sequence - origin_aisle.sequence), bin.location_code")
+        .bind::<Integer, _>(product.id())
+        .bind::<Integer, _>(product.parent_id().unwrap_or_else(|| product.id()))
+        .bind::<Integer, _>(origin.id())
+        .bind::<Nullable<Integer>, _>(only)
+        .load(conn)
+}
+
+/// Part of a received line to store in one bin.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PutawaySuggestion {
+    pub bin_location_id: i32,
+    pub location_code: String,
+    pub quantity: i32,
+    pub reason: PutawayReason
+}
+
+impl PutawaySuggestion {
+    /// Spreads `quantity` units of `product` waiting at `origin` over the best bins with room
+    /// for them. Returns the suggestions and the units no bin has room for.
+    pub fn suggest(product: &Product, origin: &BinLocation, quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<(Vec<PutawaySuggestion>, i32), diesel::result::Error> {
+        PutawaySuggestion::suggest_besides(product, origin, quantity, &mut HashMap::new(), conn)
+    }
+
+    /// Like `suggest`, leaving out the room `planned` for earlier lines of the same plan and bins
+    /// planned for other products. The suggestions are added to `planned`.
+    fn suggest_besides(product: &Product, origin: &BinLocation, quantity: i32, planned: &mut HashMap<i32, PlannedBin>, conn: &impl Connection<Backend=Pg>) -> Result<(Vec<PutawaySuggestion>, i32), diesel::result::Error> {
+        let mut remaining = quantity;
+        let mut suggestions = Vec::new();
+        for candidate in candidates(product, origin, None, conn)? {
+            if remaining == 0 {
+                break;
+            }
+            let earlier = planned.get(&candidate.bin_location_id).copied();
+            if earlier.map_or(false, |earlier| earlier.product_id != product.id()) {
+                continue;
+            }
+            let bin = BinLocation::find(candidate.bin_location_id, conn)?;
+            let take = bin.room_besides(product, earlier.map(|earlier| earlier.load).unwrap_or_default(), conn)?
+                .map_or(remaining, |room| room.min(i64::from(remaining)) as i32);
+            if take <= 0 {
+                continue;
+            }
+            let reason = if candidate.home {
+                PutawayReason::HomeLocation
+            } else if candidate.same_product > 0 {
+                PutawayReason::Consolidation
+            } else {
+                PutawayReason::EmptyBin
+            };
+            suggestions.push(PutawaySuggestion {
+                bin_location_id: bin.id(),
+                location_code: bin.location_code().to_string(),
+                quantity: take,
+                reason
+            });
+            let load = earlier.map(|earlier| earlier.load).unwrap_or_default().plus(BinLoad::of(product, take));
+            planned.insert(bin.id(), PlannedBin { product_id: product.id(), load });
+            remaining -= take;
+        }
+        Ok((suggestions, remaining))
+    }
+}
+
+/// What the earlier lines of a plan suggested to put into one bin.
+#[derive(Debug, Clone, Copy)]
+struct PlannedBin {
+    product_id: i32,
+    load: BinLoad
+}
+
+/// Where to store what is left of a received line in the receiving bin.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PutawayPlan {
+    pub goods_receipt_line_id: i32,
+    pub product_id: i32,
+    pub lot_id: Option<i32>,
+    pub quantity: i32,
+    pub suggestions: Vec<PutawaySuggestion>,
+    /// Units no bin currently has room for.
+    pub unplaced: i32
+}
+
+impl PutawayPlan {
+    /// Plans for every line of the receipt with units left in the receiving bin, except lines
+    /// held for inspection. Room suggested for one line is not suggested again for the next.
+    pub fn for_receipt(receipt: &GoodsReceipt, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PutawayPlan>, diesel::result::Error> {
+        let origin = BinLocation::find(receipt.bin_location_id(), conn)?;
+        let mut planned = HashMap::new();
+        let mut plans = Vec::new();
+        for line in receipt.lines(conn)? {
+            if line.awaiting_putaway() <= 0 || Inspection::is_holding(&line, conn)? {
+                continue;
+            }
+            let product = received_product(&line, conn)?;
+            let (suggestions, unplaced) = PutawaySuggestion::suggest_besides(&product, &origin, line.awaiting_putaway(), &mut planned, conn)?;
+            plans.push(PutawayPlan {
+                goods_receipt_line_id: line.id(),
+                product_id: product.id(),
+                lot_id: line.lot_id(),
+                quantity: line.awaiting_putaway(),
+                suggestions,
+                unplaced
+            });
+        }
+        Ok(plans)
+    }
+}
+
+fn received_product(line: &GoodsReceiptLine, conn: &impl Connection<Backend=Pg>) -> Result<Product, diesel::result::Error> {
+    let product = purchase_order_line::table
+        .find(line.purchase_order_line_id())
+        .select(purchase_order_line::product_id)
+        .first::<i32>(conn)?;
+    Product::find(product, conn)
+}
+
+/// Body of a `POST /goods-receipt/<id>/putaway` request. Without a quantity everything left of
+/// the line is put away.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PutawayConfirmation {
+    pub goods_receipt_line_id: i32,
+    pub to_location_id: i32,
+    #[serde(default)]
+    pub quantity: Option<i32>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+impl PutawayConfirmation {
+    /// Moves the units from the receiving bin to the chosen bin, which has to be one the putaway
+    /// rules allow and which has room for them.
+    pub fn confirm(self, receipt: &GoodsReceipt, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<GoodsReceiptLine, StockError> {
+        conn.transaction(|| {
+            let line: GoodsReceiptLine = goods_receipt_line::table
+                .find(self.goods_receipt_line_id)
+                .filter(goods_receipt_line::goods_receipt_id.eq(receipt.id()))
+                .for_update()
+                .first(conn)
+                .optional()?
+                .ok_or_else(|| StockError::InvalidMovement(format!("Line {} is not a line of goods receipt {}", self.goods_receipt_line_id, receipt.id())))?;
+            if Inspection::is_holding(&line, conn)? {
+                return Err(StockError::InvalidMovement(format!("Line {} is held for inspection", line.id())));
+            }
+            let quantity = self.quantity.unwrap_or_else(|| line.awaiting_putaway());
+            if quantity <= 0 || quantity > line.awaiting_putaway() {
+                return Err(StockError::InvalidMovement(format!("Can put away between 1 and {} units of line {}, got {}", line.awaiting_putaway(), line.id(), quantity)));
+            }
+
+            let product = received_product(&line, conn)?;
+            let origin = BinLocation::find(receipt.bin_location_id(), conn)?;
+            let target = BinLocation::find(self.to_location_id, conn)?;
+            if candidates(&product, &origin, Some(target.id()), conn)?.is_empty() {
+                return Err(StockError::InvalidMovement(format!("Bin {} is not a putaway location for product {}", target.location_code(), product.sku())));
+            }
+            if target.room_for(&product, conn)?.map_or(false, |room| room < i64::from(quantity)) {
+                return Err(StockError::InvalidMovement(format!("Bin {} has no room for {} units of product {}", target.location_code(), quantity, product.sku())));
+            }
+
+            NewStockMovement::new(product.id(), Some(origin.id()), Some(target.id()), quantity, MovementReason::Transfer, operator)
+                .in_lot(line.lot_id())
+                .with_serials(self.serial_numbers)
+                .with_reference(&format!("PUTAWAY-{}", receipt.id()))
+                .post(conn)?;
+            Ok(update(goods_receipt_line::table.find(line.id()))
+                .set(goods_receipt_line::put_away_quantity.eq(goods_receipt_line::put_away_quantity + quantity))
+                .get_result(conn)?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::{BinChanges, LocationType};
+    use crate::location::zone::models::Zone;
+    use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+    use crate::inventory::putaway::models::{HomeLocationDefinition, PutawayPlan, PutawayReason, PutawaySuggestion, ZoneRestriction};
+    use crate::product::category::models::NewProductCategory;
+    use crate::purchasing::supplier::models::SupplierDetails;
+    use crate::purchasing::order::models::{NewPurchaseOrder, PurchaseOrderStatus};
+    use crate::purchasing::receiving::models::{GoodsReceipt, ReceivedLine};
+    use crate::operator::Operator;
+
+    #[test]
+    fn home_locations_come_before_consolidation_and_empty_bins() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Staging, LocationType::Reserve, LocationType::Reserve, LocationType::PickFace, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let brush = product(&conn, "BRUSH");
+            let clerk = Operator::new("clerk");
+            NewStockMovement::new(soap.id(), None, Some(bins[2].id()), 5, MovementReason::Receipt, &clerk).post(&conn).unwrap();
+            NewStockMovement::new(brush.id(), None, Some(bins[4].id()), 5, MovementReason::Receipt, &clerk).post(&conn).unwrap();
+            HomeLocationDefinition { bin_location_id: bins[3].id() }.for_product(&soap, &bins[3]).unwrap().create(&conn).unwrap();
+
+            let (suggestions, unplaced) = PutawaySuggestion::suggest(&soap, &bins[0], 10, &conn).unwrap();
+            assert_eq!(unplaced, 0);
+            assert_eq!(suggestions[0].bin_location_id, bins[3].id());
+            assert_eq!(suggestions[0].reason, PutawayReason::HomeLocation);
+
+            let small_face: BinChanges = serde_json::from_str(r#"{"location_type": "pick_face", "max_units": 4}"#).unwrap();
+            let small_face = small_face.for_bin(bins[3].clone(), 0).update(&conn).unwrap().unwrap();
+            let (suggestions, _) = PutawaySuggestion::suggest(&soap, &bins[0], 10, &conn).unwrap();
+            let placed: Vec<_> = suggestions.iter().map(|s| (s.bin_location_id, s.quantity, s.reason)).collect();
+            assert_eq!(placed, vec![(small_face.id(), 4, PutawayReason::HomeLocation), (bins[2].id(), 6, PutawayReason::Consolidation)]);
+            assert!(suggestions.iter().all(|s| s.bin_location_id != bins[4].id()));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn restricted_zones_only_take_products_of_their_categories() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Staging, LocationType::Reserve]);
+            let acid = product(&conn, "ACID");
+            let soap = product(&conn, "SOAP");
+            let hazmat = NewProductCategory::new("hazmat").create(&conn).unwrap();
+            acid.assign_categories(&[hazmat.id()], &conn).unwrap();
+
+            let (unrestricted, _) = PutawaySuggestion::suggest(&soap, &bins[0], 1, &conn).unwrap();
+            assert_eq!(unrestricted.len(), 1);
+
+            let zone = Zone::find(bins[1].zone_id(), &conn).unwrap();
+            ZoneRestriction { category_ids: vec![hazmat.id()] }.apply(&zone, &conn).unwrap();
+            let (acid_bins, _) = PutawaySuggestion::suggest(&acid, &bins[0], 1, &conn).unwrap();
+            let (soap_bins, unplaced) = PutawaySuggestion::suggest(&soap, &bins[0], 1, &conn).unwrap();
+            assert_eq!(acid_bins.len(), 1);
+            assert!(soap_bins.is_empty());
+            assert_eq!(unplaced, 1);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn room_planned_for_one_line_is_not_planned_again_for_the_next() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Staging, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let small: BinChanges = serde_json::from_str(r#"{"location_type": "reserve", "max_units": 6}"#).unwrap();
+            let small = small.for_bin(bins[1].clone(), 0).update(&conn).unwrap().unwrap();
+            let supplier = SupplierDetails {
+                name: "Acme".to_string(), contact_name: None, contact_email: None, lead_time_days: 5,
+                currency: "EUR".to_string(), over_receipt_percent: 0, under_receipt_percent: 0, requires_inspection: false
+            }.create(&conn).unwrap();
+            let order = NewPurchaseOrder::new(&supplier, &warehouse, &Operator::new("buyer")).create(&conn).unwrap()
+                .add_line(soap.id(), 5, 100, &conn).unwrap().unwrap()
+                .add_line(soap.id(), 5, 100, &conn).unwrap().unwrap()
+                .transition(PurchaseOrderStatus::Sent, &conn).unwrap().unwrap();
+            let received: Vec<_> = order.lines(&conn).unwrap().iter()
+                .map(|line| ReceivedLine { purchase_order_line_id: line.id(), quantity: 5, damaged_quantity: 0, lot: None, serial_numbers: Vec::new() })
+                .collect();
+            let receipt = GoodsReceipt::record(order.id(), &bins[0], None, received, &Operator::new("clerk"), &conn).unwrap().receipt;
+
+            let plans = PutawayPlan::for_receipt(&receipt, &conn).unwrap();
+            let planned: Vec<_> = plans.iter()
+                .map(|plan| (plan.suggestions.iter().map(|s| (s.bin_location_id, s.quantity)).collect::<Vec<_>>(), plan.unplaced))
+                .collect();
+            assert_eq!(planned, vec![(vec![(small.id(), 5)], 0), (vec![(small.id(), 1)], 4)]);
+            Ok(())
+        })
+    }
+}
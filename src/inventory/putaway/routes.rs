@@ -0,0 +1,69 @@
+use crate::inventory::putaway::models::{HomeLocation, HomeLocationDefinition, PutawayConfirmation, PutawayPlan, ZoneRestriction};
+use crate::purchasing::receiving::models::{GoodsReceipt, GoodsReceiptLine};
+use crate::location::bin::models::BinLocation;
+use crate::location::zone::models::Zone;
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+#[post("/product/<id>/home-location", format="application/json", data="<definition>")]
+pub fn post_home(id: i32, definition: Json<HomeLocationDefinition>, conn: PostgresConnection) -> Result<Created<Json<HomeLocation>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    let definition = definition.into_inner();
+    let bin = BinLocation::find(definition.bin_location_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Bin {} does not exist", definition.bin_location_id)))?;
+    let created = definition
+        .for_product(&product, &bin)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Created(format!("/home-location/{}", created.id()), Some(Json(created))))
+}
+
+#[get("/product/<id>/home-location")]
+pub fn list_homes(id: i32, conn: PostgresConnection) -> Result<Json<Vec<HomeLocation>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(HomeLocation::for_product(product.id(), &*conn)?))
+}
+
+#[delete("/home-location/<id>")]
+pub fn delete_home(id: i32, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let home = HomeLocation::find(id, &*conn)?;
+    match home.delete(&*conn)? {
+        0 => Err(ApiError::NotFound),
+        _ => Ok(Status::NoContent)
+    }
+}
+
+#[get("/zone/<id>/restriction")]
+pub fn get_restriction(id: i32, conn: PostgresConnection) -> Result<Json<ZoneRestriction>, ApiError> {
+    let zone = Zone::find(id, &*conn)?;
+    Ok(Json(ZoneRestriction::load(&zone, &*conn)?))
+}
+
+#[put("/zone/<id>/restriction", format="application/json", data="<restriction>")]
+pub fn put_restriction(id: i32, restriction: Json<ZoneRestriction>, conn: PostgresConnection) -> Result<Json<ZoneRestriction>, ApiError> {
+    let zone = Zone::find(id, &*conn)?;
+    Ok(Json(restriction.into_inner().apply(&zone, &*conn)?))
+}
+
+/// Suggested bins for everything of the receipt still waiting in the receiving bin.
+#[get("/goods-receipt/<id>/putaway")]
+pub fn suggest(id: i32, conn: PostgresConnection) -> Result<Json<Vec<PutawayPlan>>, ApiError> {
+    let receipt = GoodsReceipt::find(id, &*conn)?;
+    Ok(Json(PutawayPlan::for_receipt(&receipt, &*conn)?))
+}
+
+#[post("/goods-receipt/<id>/putaway", format="application/json", data="<confirmation>")]
+pub fn confirm(id: i32, confirmation: Json<PutawayConfirmation>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<GoodsReceiptLine>, ApiError> {
+    let operator = operator?;
+    let receipt = GoodsReceipt::find(id, &*conn)?;
+    Ok(Json(confirmation.into_inner().confirm(&receipt, &operator, &*conn)?))
+}
@@ -0,0 +1,363 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::reorder_policy;
+use crate::product::models::Product;
+use crate::location::bin::models::PICKABLE;
+use crate::domain::DomainError;
+use crate::inventory::reservation::models::Reservation;
+use crate::product::packaging::models::PackagingHierarchy;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Array, BigInt, Integer, Nullable, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// Packaging level suggestions are rounded to unless the policy names its own order unit.
+pub const CASE_PACK: &str = "case";
+
+sql_enum! {
+    /// Once the stock position falls to the reorder point `min_max` orders up to the maximum,
+    /// `reorder_point` orders the fixed reorder quantity.
+    pub enum ReorderMethod {
+        MinMax => "min_max",
+        ReorderPoint => "reorder_point"
+    }
+}
+
+/// When and how much of a product to reorder for one warehouse, quantities are in base units.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="reorder_policy"]
+pub struct ReorderPolicy {
+    id: i32,
+    product_id: i32,
+    warehouse_id: i32,
+    method: ReorderMethod,
+    reorder_point: i32,
+    reorder_quantity: Option<i32>,
+    max_quantity: Option<i32>,
+    order_unit: Option<String>,
+    version: i32
+}
+
+impl AsChangeset for ReorderPolicy {
+    type Target = reorder_policy::table;
+    type Changeset = <(DieselEq<reorder_policy::method, Bound<Text, ReorderMethod>>,
+                       DieselEq<reorder_policy::reorder_point, Bound<Integer, i32>>,
+                       DieselEq<reorder_policy::reorder_quantity, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<reorder_policy::max_quantity, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<reorder_policy::order_unit, Bound<Nullable<Text>, Option<String>>>,
+                       DieselEq<reorder_policy::version, DieselAdd<reorder_policy::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            reorder_policy::method.eq(self.method),
+            reorder_policy::reorder_point.eq(self.reorder_point),
+            reorder_policy::reorder_quantity.eq(self.reorder_quantity),
+            reorder_policy::max_quantity.eq(self.max_quantity),
+            reorder_policy::order_unit.eq(self.order_unit),
+            reorder_policy::version.eq(reorder_policy::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl ReorderPolicy {
+
+    pub fn find(policy_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<ReorderPolicy, diesel::result::Error> {
+        use crate::schema::reorder_policy::dsl::*;
+        reorder_policy.find(policy_id).first(conn)
+    }
+
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReorderPolicy>, diesel::result::Error> {
+        use crate::schema::reorder_policy::dsl::*;
+        reorder_policy.filter(warehouse_id.eq(warehouse)).order(product_id.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Base units to order at a stock position of `position`, `None` while it is above the
+    /// reorder point.
+    pub fn shortfall(&self, position: i64) -> Option<i64> {
+        if position > i64::from(self.reorder_point) {
+            return None;
+        }
+        match self.method {
+            ReorderMethod::MinMax => self.max_quantity.map(|max| i64::from(max) - position),
+            ReorderMethod::ReorderPoint => self.reorder_quantity.map(i64::from)
+        }
+    }
+
+    /// The policy's order unit, else the product's case pack, else its base unit.
+    fn order_unit(&self, hierarchy: &PackagingHierarchy, base_unit: &str) -> String {
+        self.order_unit.iter()
+            .map(String::as_str)
+            .chain(std::iter::once(CASE_PACK))
+            .find(|unit| hierarchy.factor(unit).is_ok())
+            .unwrap_or(base_unit)
+            .to_lowercase()
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<ReorderPolicy>, diesel::result::Error> {
+        use crate::schema::reorder_policy::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(reorder_policy.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::reorder_policy::dsl::*;
+        conn.transaction(|| {
+            delete(reorder_policy.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `PUT /reorder-policy/<id>` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderSettings {
+    pub method: ReorderMethod,
+    pub reorder_point: i32,
+    #[serde(default)]
+    pub reorder_quantity: Option<i32>,
+    #[serde(default)]
+    pub max_quantity: Option<i32>,
+    #[serde(default)]
+    pub order_unit: Option<String>
+}
+
+impl ReorderSettings {
+    pub fn validate(&self, hierarchy: &PackagingHierarchy) -> Result<(), String> {
+        if self.reorder_point < 0 {
+            return Err(format!("Reorder point must not be negative, got {}", self.reorder_point));
+        }
+        match self.method {
+            ReorderMethod::ReorderPoint => match self.reorder_quantity {
+                Some(quantity) if quantity > 0 => (),
+                _ => return Err(String::from("Reorder point policies require a positive reorder quantity"))
+            },
+            ReorderMethod::MinMax => match self.max_quantity {
+                Some(max) if max > self.reorder_point => (),
+                _ => return Err(String::from("Min/max policies require a maximum above the reorder point"))
+            }
+        }
+        match &self.order_unit {
+            Some(unit) => hierarchy.factor(unit).map(|_| ()).map_err(|e| e.to_string()),
+            None => Ok(())
+        }
+    }
+
+    fn normalized_unit(&self) -> Option<String> {
+        self.order_unit.as_ref().map(|unit| unit.trim().to_lowercase())
+    }
+
+    pub fn for_policy(self, current: ReorderPolicy, expected_version: i32) -> ReorderPolicy {
+        ReorderPolicy {
+            order_unit: self.normalized_unit(),
+            method: self.method,
+            reorder_point: self.reorder_point,
+            reorder_quantity: self.reorder_quantity,
+            max_quantity: self.max_quantity,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+/// Body of a `POST /warehouse/<id>/reorder-policy` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderPolicyDefinition {
+    pub product_id: i32,
+    #[serde(flatten)]
+    pub settings: ReorderSettings
+}
+
+impl ReorderPolicyDefinition {
+    pub fn in_warehouse(self, warehouse: i32, hierarchy: &PackagingHierarchy) -> Result<NewReorderPolicy, String> {
+        self.settings.validate(hierarchy)?;
+        Ok(NewReorderPolicy {
+            product_id: self.product_id,
+            warehouse_id: warehouse,
+            order_unit: self.settings.normalized_unit(),
+            method: self.settings.method,
+            reorder_point: self.settings.reorder_point,
+            reorder_quantity: self.settings.reorder_quantity,
+            max_quantity: self.settings.max_quantity
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="reorder_policy"]
+pub struct NewReorderPolicy {
+    product_id: i32,
+    warehouse_id: i32,
+    method: ReorderMethod,
+    reorder_point: i32,
+    reorder_quantity: Option<i32>,
+    max_quantity: Option<i32>,
+    order_unit: Option<String>
+}
+
+impl NewReorderPolicy {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<ReorderPolicy, diesel::result::Error> {
+        use crate::schema::reorder_policy::dsl::*;
+        use crate::schema::reorder_policy::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(reorder_policy)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+#[derive(Debug, QueryableByName)]
+struct StockPosition {
+    #[sql_type="Integer"]
+    policy_id: i32,
+    #[sql_type="BigInt"]
+    available: i64,
+    #[sql_type="BigInt"]
+    in_transit: i64
+}
+
+/// A product whose available and inbound stock fell to its reorder point, with the quantity
+/// to purchase rounded up to whole order units.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReorderSuggestion {
+    pub policy_id: i32,
+    pub product_id: i32,
+    pub available: i64,
+    pub in_transit: i64,
+    pub reorder_point: i32,
+    pub shortfall: i64,
+    pub order_unit: String,
+    pub order_quantity: i64,
+    pub base_quantity: i64
+}
+
+impl ReorderSuggestion {
+    /// Only stock in pickable bins and not of expired lots is available, stock in transit to
+    /// the warehouse is counted as if it had arrived. Expired reservations are released first.
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReorderSuggestion>, DomainError> {
+        Reservation::release_expired(conn)?;
+        let positions: Vec<StockPosition> = diesel::sql_query(
+            "select p.id as policy_id, \
+                    coalesce(sum(b.on_hand - b.allocated) filter (where (l.expires_on is null or l.expires_on >= current_date) and bin.location_type = any($2)), 0)::bigint as available, \
+                    (select coalesce(sum(t.quantity), 0) from stock_transfer t \
+                     where t.product_id = p.product_id and t.to_warehouse_id = p.warehouse_id and t.status = 'in_transit')::bigint as in_transit \
+             from reorder_policy p \
+             left join inventory_balance b on b.product_id = p.product_id and b.warehouse_id = p.warehouse_id \
+             left join bin_location bin on bin.id = b.bin_location_id \
+             left join product_lot l on l.id = b.lot_id \
+             where p.warehouse_id = $1 \
+             group by p.id")
+            .bind::<Integer, _>(warehouse)
+            .bind::<Array<Text>, _>(&PICKABLE[..])
+            .load(conn)?;
+
+        let mut suggestions = Vec::new();
+        for policy in ReorderPolicy::for_warehouse(warehouse, conn)? {
+            let position = match positions.iter().find(|position| position.policy_id == policy.id) {
+                Some(position) => position,
+                None => continue
+            };
+            let shortfall = match policy.shortfall(position.available + position.in_transit) {
+                Some(shortfall) if shortfall > 0 => shortfall,
+                _ => continue
+            };
+            let product = Product::find(policy.product_id, conn)?;
+            let hierarchy = PackagingHierarchy::load(&product, conn)?;
+            let order_unit = policy.order_unit(&hierarchy, product.unit_of_measure());
+            let order_quantity = hierarchy.round_up(shortfall, &order_unit)?;
+            let base_quantity = hierarchy.to_base(order_quantity, &order_unit)?;
+            suggestions.push(ReorderSuggestion {
+                policy_id: policy.id,
+                product_id: policy.product_id,
+                available: position.available,
+                in_transit: position.in_transit,
+                reorder_point: policy.reorder_point,
+                shortfall,
+                order_unit,
+                order_quantity,
+                base_quantity
+            });
+        }
+        Ok(suggestions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::inventory::reorder::models::{ReorderMethod, ReorderPolicyDefinition, ReorderSettings, ReorderSuggestion};
+    use crate::product::packaging::models::{PackagingDefinition, PackagingHierarchy};
+
+    fn settings(method: ReorderMethod, reorder_point: i32, quantity: i32) -> ReorderSettings {
+        match method {
+            ReorderMethod::MinMax => ReorderSettings { method, reorder_point, reorder_quantity: None, max_quantity: Some(quantity), order_unit: None },
+            ReorderMethod::ReorderPoint => ReorderSettings { method, reorder_point, reorder_quantity: Some(quantity), max_quantity: None, order_unit: None }
+        }
+    }
+
+    #[test]
+    fn policies_need_the_quantity_their_method_orders() {
+        let hierarchy = PackagingHierarchy::new("each", Vec::new());
+        assert!(settings(ReorderMethod::MinMax, 10, 50).validate(&hierarchy).is_ok());
+        assert!(settings(ReorderMethod::MinMax, 10, 10).validate(&hierarchy).is_err());
+        assert!(settings(ReorderMethod::ReorderPoint, 10, 0).validate(&hierarchy).is_err());
+        let in_crates = ReorderSettings { order_unit: Some("crate".to_string()), ..settings(ReorderMethod::ReorderPoint, 10, 5) };
+        assert!(in_crates.validate(&hierarchy).is_err());
+    }
+
+    #[test]
+    fn suggestions_use_available_stock_and_round_to_case_packs() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let salt = product(&conn, "SALT");
+            let hierarchy = PackagingHierarchy::load(&soap, &conn).unwrap();
+            PackagingDefinition { name: "case".to_string(), quantity: 12, contains: soap.unit_of_measure().to_string() }
+                .for_product(&soap, &hierarchy).unwrap()
+                .create(&conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(30), &conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::allocated(25), &conn).unwrap();
+            InventoryBalance::apply(salt.id(), &bins[0], StockChange::on_hand(30), &conn).unwrap();
+
+            for (item, method) in &[(&soap, ReorderMethod::MinMax), (&salt, ReorderMethod::ReorderPoint)] {
+                let hierarchy = PackagingHierarchy::load(item, &conn).unwrap();
+                ReorderPolicyDefinition { product_id: item.id(), settings: settings(*method, 10, 40) }
+                    .in_warehouse(warehouse.id(), &hierarchy).unwrap()
+                    .create(&conn).unwrap();
+            }
+
+            let suggestions = ReorderSuggestion::for_warehouse(warehouse.id(), &conn).unwrap();
+            assert_eq!(suggestions.len(), 1);
+            let suggestion = &suggestions[0];
+            assert_eq!((suggestion.product_id, suggestion.available, suggestion.shortfall), (soap.id(), 5, 35));
+            assert_eq!((suggestion.order_unit.as_str(), suggestion.order_quantity, suggestion.base_quantity), ("case", 3, 36));
+            Ok(())
+        })
+    }
+}
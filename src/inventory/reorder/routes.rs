@@ -0,0 +1,70 @@
+use crate::inventory::reorder::models::{ReorderPolicy, ReorderPolicyDefinition, ReorderSettings, ReorderSuggestion};
+use crate::location::warehouse::models::Warehouse;
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/warehouse/<id>/reorder-policy", format="application/json", data="<definition>")]
+pub fn post(id: i32, definition: Json<ReorderPolicyDefinition>, conn: PostgresConnection) -> Result<Tagged<Created<Json<ReorderPolicy>>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    let definition = definition.into_inner();
+    let product = Product::find(definition.product_id, &*conn)?;
+    let hierarchy = PackagingHierarchy::load(&product, &*conn)?;
+    let created = definition
+        .in_warehouse(warehouse.id(), &hierarchy)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/reorder-policy/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/warehouse/<id>/reorder-policy")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ReorderPolicy>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(ReorderPolicy::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[get("/reorder-policy/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<ReorderPolicy>>, ApiError> {
+    let policy = ReorderPolicy::find(id, &*conn)?;
+    Ok(Tagged(policy.version(), Json(policy)))
+}
+
+#[put("/reorder-policy/<id>", format="application/json", data="<settings>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, settings: Json<ReorderSettings>, conn: PostgresConnection) -> Result<Tagged<Json<ReorderPolicy>>, ApiError> {
+    let expected_version = if_match?.version();
+    let settings = settings.into_inner();
+    let current = ReorderPolicy::find(id, &*conn)?;
+    let product = Product::find(current.product_id(), &*conn)?;
+    settings.validate(&PackagingHierarchy::load(&product, &*conn)?).map_err(ApiError::UnprocessableEntity)?;
+    match settings.for_policy(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(ReorderPolicy::find(id, &*conn), ReorderPolicy::version))
+    }
+}
+
+#[delete("/reorder-policy/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let policy = ReorderPolicy::find(id, &*conn)?;
+    if policy.version() != expected_version {
+        return Err(ApiError::precondition_failed(policy.version(), &policy));
+    }
+    match policy.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(ReorderPolicy::find(id, &*conn), ReorderPolicy::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
+
+/// Products of the warehouse at or below their reorder point with suggested purchase quantities.
+#[get("/warehouse/<id>/reorder-suggestion")]
+pub fn suggestions(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ReorderSuggestion>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(ReorderSuggestion::for_warehouse(warehouse.id(), &*conn)?))
+}
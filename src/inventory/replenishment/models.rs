@@ -0,0 +1,425 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use diesel::{update, delete};
+use crate::schema::{pick_face, replenishment_task};
+use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError};
+use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+use crate::location::bin::models::{BinLocation, LocationType};
+use crate::product::models::Product;
+use crate::operator::Operator;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{BigInt, Integer};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    pub enum TaskStatus {
+        Pending => "pending",
+        InProgress => "in_progress",
+        Done => "done",
+        Cancelled => "cancelled"
+    }
+}
+
+/// A pick-face bin and the product picked from it. Once its stock drops below `min_quantity`
+/// it is replenished from reserve up to `max_quantity`, or as much as the bin holds.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="pick_face"]
+pub struct PickFace {
+    id: i32,
+    bin_location_id: i32,
+    product_id: i32,
+    min_quantity: i32,
+    max_quantity: i32,
+    version: i32
+}
+
+impl AsChangeset for PickFace {
+    type Target = pick_face::table;
+    type Changeset = <(DieselEq<pick_face::min_quantity, Bound<Integer, i32>>,
+                       DieselEq<pick_face::max_quantity, Bound<Integer, i32>>,
+                       DieselEq<pick_face::version, DieselAdd<pick_face::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            pick_face::min_quantity.eq(self.min_quantity),
+            pick_face::max_quantity.eq(self.max_quantity),
+            pick_face::version.eq(pick_face::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl PickFace {
+
+    pub fn find(pick_face_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<PickFace, diesel::result::Error> {
+        use crate::schema::pick_face::dsl::*;
+        pick_face.find(pick_face_id).first(conn)
+    }
+
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PickFace>, diesel::result::Error> {
+        use crate::schema::bin_location;
+        pick_face::table
+            .inner_join(bin_location::table)
+            .filter(bin_location::warehouse_id.eq(warehouse))
+            .order(bin_location::location_code.asc())
+            .select(pick_face::all_columns)
+            .load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<PickFace>, diesel::result::Error> {
+        use crate::schema::pick_face::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(pick_face.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::pick_face::dsl::*;
+        conn.transaction(|| {
+            delete(pick_face.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `PUT /pick-face/<id>` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PickFaceLimits {
+    pub min_quantity: i32,
+    pub max_quantity: i32
+}
+
+impl PickFaceLimits {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_quantity < 0 {
+            return Err(format!("Minimum quantity must not be negative, got {}", self.min_quantity));
+        }
+        if self.max_quantity <= self.min_quantity {
+            return Err(format!("Maximum quantity {} must be above the minimum of {}", self.max_quantity, self.min_quantity));
+        }
+        Ok(())
+    }
+
+    pub fn for_pick_face(self, current: PickFace, expected_version: i32) -> PickFace {
+        PickFace {
+            min_quantity: self.min_quantity,
+            max_quantity: self.max_quantity,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+/// Body of a `POST /bin/<id>/pick-face` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PickFaceDefinition {
+    pub product_id: i32,
+    #[serde(flatten)]
+    pub limits: PickFaceLimits
+}
+
+impl PickFaceDefinition {
+    pub fn in_bin(self, bin: &BinLocation) -> Result<NewPickFace, String> {
+        if bin.location_type() != LocationType::PickFace {
+            return Err(format!("Bin {} is a {} location, not a pick face", bin.location_code(), bin.location_type()));
+        }
+        self.limits.validate()?;
+        Ok(NewPickFace {
+            bin_location_id: bin.id(),
+            product_id: self.product_id,
+            min_quantity: self.limits.min_quantity,
+            max_quantity: self.limits.max_quantity
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="pick_face"]
+pub struct NewPickFace {
+    bin_location_id: i32,
+    product_id: i32,
+    min_quantity: i32,
+    max_quantity: i32
+}
+
+impl NewPickFace {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<PickFace, diesel::result::Error> {
+        use crate::schema::pick_face::dsl::*;
+        use crate::schema::pick_face::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(pick_face)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Stock to be brought from a reserve bin to a pick face. Its quantity stays allocated in the
+/// reserve bin until the task is done or cancelled.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="replenishment_task"]
+pub struct ReplenishmentTask {
+    id: i32,
+    pick_face_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    from_location_id: i32,
+    to_location_id: i32,
+    quantity: i32,
+    status: TaskStatus,
+    created_at: NaiveDateTime,
+    assigned_to: Option<String>,
+    completed_by: Option<String>,
+    completed_at: Option<NaiveDateTime>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="replenishment_task"]
+struct NewReplenishmentTask {
+    pick_face_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    from_location_id: i32,
+    to_location_id: i32,
+    quantity: i32
+}
+
+#[derive(Debug, QueryableByName)]
+struct FaceStock {
+    #[sql_type="Integer"]
+    pick_face_id: i32,
+    #[sql_type="BigInt"]
+    on_hand: i64
+}
+
+impl ReplenishmentTask {
+
+    pub fn find(task_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<ReplenishmentTask, diesel::result::Error> {
+        use crate::schema::replenishment_task::dsl::*;
+        replenishment_task.find(task_id).first(conn)
+    }
+
+    /// Open tasks for pick faces of `warehouse`, oldest first.
+    pub fn queue(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReplenishmentTask>, diesel::result::Error> {
+        use crate::schema::bin_location;
+        replenishment_task::table
+            .inner_join(bin_location::table.on(bin_location::id.eq(replenishment_task::to_location_id)))
+            .filter(bin_location::warehouse_id.eq(warehouse))
+            .filter(replenishment_task::status.eq_any(vec![TaskStatus::Pending, TaskStatus::InProgress]))
+            .order(replenishment_task::id.asc())
+            .select(replenishment_task::all_columns)
+            .load(conn)
+    }
+
+    /// Creates tasks for every pick face of `warehouse` below its minimum without an open task.
+    /// Stock is taken from reserve bins First-Expired-First-Out, one task per bin and lot, and
+    /// never more than fits into the pick face.
+    pub fn generate(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReplenishmentTask>, StockError> {
+        conn.transaction(|| {
+            let faces: Vec<FaceStock> = diesel::sql_query(
+                "select f.id as pick_face_id, coalesce(sum(b.on_hand), 0)::bigint as on_hand \
+                 from pick_face f join bin_location bin on bin.id = f.bin_location_id \
+                 left join inventory_balance b on b.bin_location_id = f.bin_location_id and b.product_id = f.product_id \
+                 where bin.warehouse_id = $1 and not exists ( \
+                     select 1 from replenishment_task t \
+                     where t.pick_face_id = f.id and t.status in ('pending', 'in_progress')) \
+                 group by f.id having coalesce(sum(b.on_hand), 0) < f.min_quantity \
+                 order by f.id")
+                .bind::<Integer, _>(warehouse)
+                .load(conn)?;
+
+            let mut tasks = Vec::new();
+            for stock in faces {
+                let face = PickFace::find(stock.pick_face_id, conn)?;
+                let bin = BinLocation::find(face.bin_location_id, conn)?;
+                let product = Product::find(face.product_id, conn)?;
+                let wanted = i64::from(face.max_quantity) - stock.on_hand;
+                let quantity = bin.room_for(&product, conn)?.map_or(wanted, |room| room.min(wanted));
+                if quantity <= 0 {
+                    continue;
+                }
+                let allocations = InventoryBalance::allocate_up_to(face.product_id, warehouse, &[LocationType::Reserve], quantity as i32, conn)?;
+                for allocation in allocations {
+                    let task = diesel::insert_into(replenishment_task::table)
+                        .values(NewReplenishmentTask {
+                            pick_face_id: face.id,
+                            product_id: face.product_id,
+                            lot_id: allocation.lot_id,
+                            from_location_id: allocation.bin_location_id,
+                            to_location_id: face.bin_location_id,
+                            quantity: allocation.quantity
+                        })
+                        .get_result(conn)?;
+                    tasks.push(task);
+                }
+            }
+            Ok(tasks)
+        })
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn status(&self) -> TaskStatus {
+        self.status
+    }
+
+    /// Assigns a pending task to `operator`. Returns `None` if someone else took it first.
+    pub fn start(self, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<ReplenishmentTask>, diesel::result::Error> {
+        use crate::schema::replenishment_task::dsl::*;
+        update(replenishment_task.filter(id.eq(self.id).and(status.eq(TaskStatus::Pending))))
+            .set((status.eq(TaskStatus::InProgress), assigned_to.eq(Some(operator.name()))))
+            .get_result(conn)
+            .optional()
+    }
+
+    /// Moves the stock to the pick face. Returns `None` if the task is no longer open.
+    pub fn complete(self, serial_numbers: Vec<String>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<ReplenishmentTask>, StockError> {
+        use crate::schema::replenishment_task::dsl::*;
+        conn.transaction(|| {
+            let completed = update(replenishment_task.filter(id.eq(self.id).and(status.eq_any(vec![TaskStatus::Pending, TaskStatus::InProgress]))))
+                .set((
+                    status.eq(TaskStatus::Done),
+                    completed_by.eq(Some(operator.name())),
+                    completed_at.eq(diesel::dsl::now.nullable())
+                ))
+                .get_result::<ReplenishmentTask>(conn)
+                .optional()?;
+            let completed = match completed {
+                Some(completed) => completed,
+                None => return Ok(None)
+            };
+            completed.release(conn)?;
+            NewStockMovement::new(completed.product_id, Some(completed.from_location_id), Some(completed.to_location_id),
+                                  completed.quantity, MovementReason::Transfer, operator)
+                .in_lot(completed.lot_id)
+                .with_serials(serial_numbers)
+                .with_reference(&replenishment_reference(completed.id))
+                .post(conn)?;
+            Ok(Some(completed))
+        })
+    }
+
+    /// Gives the stock allocated for the task back. Returns `None` if the task is no longer open.
+    pub fn cancel(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<ReplenishmentTask>, StockError> {
+        use crate::schema::replenishment_task::dsl::*;
+        conn.transaction(|| {
+            let cancelled = update(replenishment_task.filter(id.eq(self.id).and(status.eq_any(vec![TaskStatus::Pending, TaskStatus::InProgress]))))
+                .set(status.eq(TaskStatus::Cancelled))
+                .get_result::<ReplenishmentTask>(conn)
+                .optional()?;
+            if let Some(cancelled) = &cancelled {
+                cancelled.release(conn)?;
+            }
+            Ok(cancelled)
+        })
+    }
+
+    fn release(&self, conn: &impl Connection<Backend=Pg>) -> Result<(), StockError> {
+        let bin = BinLocation::find(self.from_location_id, conn)?;
+        InventoryBalance::apply_to_lot(self.product_id, self.lot_id, &bin, StockChange::allocated(-self.quantity), conn)?;
+        Ok(())
+    }
+}
+
+/// Body of a `POST /replenishment-task/<id>/complete` request.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TaskCompletion {
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+fn replenishment_reference(task: i32) -> String {
+    format!("REPLENISHMENT-{}", task)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local};
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::inventory::replenishment::models::{PickFaceDefinition, PickFaceLimits, ReplenishmentTask, TaskStatus};
+    use crate::product::lot::models::LotDefinition;
+    use crate::operator::Operator;
+
+    fn limits(min_quantity: i32, max_quantity: i32) -> PickFaceLimits {
+        PickFaceLimits { min_quantity, max_quantity }
+    }
+
+    #[test]
+    fn pick_faces_need_a_maximum_above_their_minimum() {
+        assert!(limits(5, 20).validate().is_ok());
+        assert!(limits(5, 5).validate().is_err());
+        assert!(limits(-1, 5).validate().is_err());
+    }
+
+    #[test]
+    fn pick_faces_below_minimum_are_replenished_from_reserve_first_expired_first() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::Reserve, LocationType::Reserve]);
+            let milk = product(&conn, "MILK");
+            let today = Local::today().naive_local();
+            let later = LotDefinition { lot_number: "L1".to_string(), manufactured_on: None, expires_on: Some(today + Duration::days(60)) }
+                .for_product(&milk).unwrap().create(&conn).unwrap();
+            let sooner = LotDefinition { lot_number: "L2".to_string(), manufactured_on: None, expires_on: Some(today + Duration::days(10)) }
+                .for_product(&milk).unwrap().create(&conn).unwrap();
+            InventoryBalance::apply(milk.id(), &bins[0], StockChange::on_hand(2), &conn).unwrap();
+            InventoryBalance::apply_to_lot(milk.id(), Some(later.id()), &bins[1], StockChange::on_hand(50), &conn).unwrap();
+            InventoryBalance::apply_to_lot(milk.id(), Some(sooner.id()), &bins[2], StockChange::on_hand(6), &conn).unwrap();
+            PickFaceDefinition { product_id: milk.id(), limits: limits(5, 20) }
+                .in_bin(&bins[0]).unwrap()
+                .create(&conn).unwrap();
+
+            let tasks = ReplenishmentTask::generate(warehouse.id(), &conn).unwrap();
+            assert_eq!(tasks.iter().map(|t| (t.from_location_id, t.lot_id, t.quantity)).collect::<Vec<_>>(),
+                       vec![(bins[2].id(), Some(sooner.id()), 6), (bins[1].id(), Some(later.id()), 12)]);
+            assert!(ReplenishmentTask::generate(warehouse.id(), &conn).unwrap().is_empty());
+            assert_eq!(ReplenishmentTask::queue(warehouse.id(), &conn).unwrap().len(), 2);
+
+            let driver = Operator::new("driver");
+            let started = tasks[1].clone().start(&driver, &conn).unwrap().unwrap();
+            assert_eq!(started.status(), TaskStatus::InProgress);
+            assert_eq!(tasks[1].clone().start(&driver, &conn).unwrap(), None);
+            started.complete(Vec::new(), &driver, &conn).unwrap().unwrap();
+            tasks[0].clone().cancel(&conn).unwrap().unwrap();
+
+            let face_stock = InventoryBalance::lookup(milk.id(), Some(later.id()), bins[0].id(), &conn).unwrap().unwrap();
+            assert_eq!(face_stock.on_hand(), 12);
+            let reserve = InventoryBalance::lookup(milk.id(), Some(sooner.id()), bins[2].id(), &conn).unwrap().unwrap();
+            assert_eq!((reserve.on_hand(), reserve.allocated()), (6, 0));
+            assert!(ReplenishmentTask::queue(warehouse.id(), &conn).unwrap().is_empty());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn only_pick_face_bins_get_pick_faces() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve]);
+            let milk = product(&conn, "MILK");
+            assert!(PickFaceDefinition { product_id: milk.id(), limits: limits(1, 2) }.in_bin(&bins[0]).is_err());
+            Ok(())
+        })
+    }
+}
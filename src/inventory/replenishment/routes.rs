@@ -0,0 +1,111 @@
+use crate::inventory::replenishment::models::{PickFace, PickFaceDefinition, PickFaceLimits, ReplenishmentTask, TaskCompletion};
+use crate::location::bin::models::BinLocation;
+use crate::location::warehouse::models::Warehouse;
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+use crate::operator::Operator;
+
+
+#[post("/bin/<id>/pick-face", format="application/json", data="<definition>")]
+pub fn post(id: i32, definition: Json<PickFaceDefinition>, conn: PostgresConnection) -> Result<Tagged<Created<Json<PickFace>>>, ApiError> {
+    let bin = BinLocation::find(id, &*conn)?;
+    let definition = definition.into_inner();
+    Product::find(definition.product_id, &*conn)?;
+    let created = definition
+        .in_bin(&bin)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/pick-face/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/warehouse/<id>/pick-face")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<Vec<PickFace>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(PickFace::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[get("/pick-face/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<PickFace>>, ApiError> {
+    let face = PickFace::find(id, &*conn)?;
+    Ok(Tagged(face.version(), Json(face)))
+}
+
+#[put("/pick-face/<id>", format="application/json", data="<limits>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, limits: Json<PickFaceLimits>, conn: PostgresConnection) -> Result<Tagged<Json<PickFace>>, ApiError> {
+    let expected_version = if_match?.version();
+    let limits = limits.into_inner();
+    limits.validate().map_err(ApiError::UnprocessableEntity)?;
+    let current = PickFace::find(id, &*conn)?;
+    match limits.for_pick_face(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(PickFace::find(id, &*conn), PickFace::version))
+    }
+}
+
+#[delete("/pick-face/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let face = PickFace::find(id, &*conn)?;
+    if face.version() != expected_version {
+        return Err(ApiError::precondition_failed(face.version(), &face));
+    }
+    match face.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(PickFace::find(id, &*conn), PickFace::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
+
+/// Creates replenishment tasks for the pick faces of the warehouse running low.
+#[post("/warehouse/<id>/replenishment-task")]
+pub fn generate(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ReplenishmentTask>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(ReplenishmentTask::generate(warehouse.id(), &*conn)?))
+}
+
+/// Tasks waiting for or being worked on by a forklift driver, oldest first.
+#[get("/warehouse/<id>/replenishment-task")]
+pub fn queue(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ReplenishmentTask>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(ReplenishmentTask::queue(warehouse.id(), &*conn)?))
+}
+
+#[get("/replenishment-task/<id>")]
+pub fn get_task(id: i32, conn: PostgresConnection) -> Result<Json<ReplenishmentTask>, ApiError> {
+    Ok(Json(ReplenishmentTask::find(id, &*conn)?))
+}
+
+#[post("/replenishment-task/<id>/start")]
+pub fn start(id: i32, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<ReplenishmentTask>, ApiError> {
+    let operator = operator?;
+    let task = ReplenishmentTask::find(id, &*conn)?;
+    match task.start(&operator, &*conn)? {
+        Some(started) => Ok(Json(started)),
+        None => Err(ApiError::Conflict(format!("Task {} is no longer pending", id)))
+    }
+}
+
+#[post("/replenishment-task/<id>/complete", data="<completion>")]
+pub fn complete(id: i32, completion: Option<Json<TaskCompletion>>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<ReplenishmentTask>, ApiError> {
+    let operator = operator?;
+    let task = ReplenishmentTask::find(id, &*conn)?;
+    let completion = completion.map(Json::into_inner).unwrap_or_default();
+    match task.complete(completion.serial_numbers, &operator, &*conn)? {
+        Some(completed) => Ok(Json(completed)),
+        None => Err(ApiError::Conflict(format!("Task {} is no longer open", id)))
+    }
+}
+
+#[delete("/replenishment-task/<id>")]
+pub fn cancel(id: i32, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let task = ReplenishmentTask::find(id, &*conn)?;
+    match task.cancel(&*conn)? {
+        Some(_) => Ok(Status::NoContent),
+        None => Err(ApiError::Conflict(format!("Task {} is no longer open", id)))
+    }
+}
@@ -0,0 +1,348 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use crate::schema::{stock_reservation, stock_reservation_line};
+use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError, Allocation};
+use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+use crate::inventory::serial::models::SerialUnit;
+use crate::location::bin::models::{BinLocation, PICKABLE};
+use crate::operator::Operator;
+use diesel::dsl::{now, IntervalDsl};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// How long a reservation holds its stock if the request does not say otherwise.
+pub const DEFAULT_TTL_MINUTES: i32 = 24 * 60;
+
+sql_enum! {
+    pub enum ReservationStatus {
+        Active => "active",
+        Picked => "picked",
+        Cancelled => "cancelled",
+        Expired => "expired"
+    }
+}
+
+/// Stock set aside for a pending order. While active its quantity is allocated on the
+/// balances it was taken from and therefore not available to anyone else.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="stock_reservation"]
+pub struct Reservation {
+    id: i32,
+    product_id: i32,
+    warehouse_id: i32,
+    quantity: i32,
+    reference: Option<String>,
+    status: ReservationStatus,
+    created_by: String,
+    created_at: NaiveDateTime,
+    expires_at: NaiveDateTime
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="stock_reservation_line"]
+pub struct ReservationLine {
+    id: i32,
+    reservation_id: i32,
+    bin_location_id: i32,
+    lot_id: Option<i32>,
+    quantity: i32
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="stock_reservation_line"]
+struct NewReservationLine {
+    reservation_id: i32,
+    bin_location_id: i32,
+    lot_id: Option<i32>,
+    quantity: i32
+}
+
+impl Reservation {
+
+    pub fn find(reservation_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Reservation, diesel::result::Error> {
+        use crate::schema::stock_reservation::dsl::*;
+        stock_reservation.find(reservation_id).first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn status(&self) -> ReservationStatus {
+        self.status
+    }
+
+    pub fn lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReservationLine>, diesel::result::Error> {
+        use crate::schema::stock_reservation_line::dsl::*;
+        stock_reservation_line.filter(reservation_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+
+    /// Releases the stock of every active reservation past its expiry.
+    pub fn release_expired(conn: &impl Connection<Backend=Pg>) -> Result<Vec<Reservation>, StockError> {
+        use crate::schema::stock_reservation::dsl::*;
+        conn.transaction(|| {
+            let expired: Vec<Reservation> = stock_reservation
+                .filter(status.eq(ReservationStatus::Active).and(expires_at.lt(now)))
+                .order(id.asc())
+                .load(conn)?;
+            let mut released = Vec::new();
+            for reservation in expired {
+                if let Some(reservation) = reservation.close(ReservationStatus::Expired, conn)? {
+                    released.push(reservation);
+                }
+            }
+            Ok(released)
+        })
+    }
+
+    /// Gives the reserved stock back. Returns `None` if the reservation is no longer active.
+    pub fn cancel(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Reservation>, StockError> {
+        self.close(ReservationStatus::Cancelled, conn)
+    }
+
+    /// Picks the reserved stock from the bins it was reserved in. Serialised products need the
+    /// serial numbers of the picked units, each is picked from the bin it is in.
+    pub fn pick(self, serial_numbers: &[String], operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<Reservation>, StockError> {
+        conn.transaction(|| {
+            Reservation::release_expired(conn)?;
+            let lines = self.lines(conn)?;
+            let picked = match self.close(ReservationStatus::Picked, conn)? {
+                Some(picked) => picked,
+                None => return Ok(None)
+            };
+            for line in lines {
+                let mut serials_in_bin = Vec::new();
+                for number in serial_numbers {
+                    if SerialUnit::find(picked.product_id, number, conn).optional()?
+                        .map_or(false, |unit| unit.bin_location_id() == Some(line.bin_location_id)) {
+                        serials_in_bin.push(number.clone());
+                    }
+                }
+                serials_in_bin.truncate(line.quantity as usize);
+                NewStockMovement::new(picked.product_id, Some(line.bin_location_id), None, line.quantity, MovementReason::Pick, operator)
+                    .in_lot(line.lot_id)
+                    .with_serials(serials_in_bin)
+                    .with_reference(&reservation_reference(picked.id))
+                    .post(conn)?;
+            }
+            Ok(Some(picked))
+        })
+    }
+
+    /// Moves an active reservation to `final_status` and deallocates its stock.
+    fn close(self, final_status: ReservationStatus, conn: &impl Connection<Backend=Pg>) -> Result<Option<Reservation>, StockError> {
+        use crate::schema::stock_reservation::dsl::*;
+        conn.transaction(|| {
+            let closed = diesel::update(stock_reservation.filter(id.eq(self.id).and(status.eq(ReservationStatus::Active))))
+                .set(status.eq(final_status))
+                .get_result::<Reservation>(conn)
+                .optional()?;
+            let closed = match closed {
+                Some(closed) => closed,
+                None => return Ok(None)
+            };
+            for line in closed.lines(conn)? {
+                let bin = BinLocation::find(line.bin_location_id, conn)?;
+                InventoryBalance::apply_to_lot(closed.product_id, line.lot_id, &bin, StockChange::allocated(-line.quantity), conn)?;
+            }
+            Ok(Some(closed))
+        })
+    }
+}
+
+/// A reservation together with the bins and lots its stock is allocated in.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReservationView {
+    #[serde(flatten)]
+    pub reservation: Reservation,
+    pub lines: Vec<ReservationLine>
+}
+
+impl ReservationView {
+    pub fn load(reservation: Reservation, conn: &impl Connection<Backend=Pg>) -> Result<ReservationView, diesel::result::Error> {
+        let lines = reservation.lines(conn)?;
+        Ok(ReservationView { reservation, lines })
+    }
+}
+
+/// Body of a `POST /inventory/reservation` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReservationRequest {
+    pub product_id: i32,
+    pub warehouse_id: i32,
+    pub quantity: crate::product::packaging::models::Quantity,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub ttl_minutes: Option<i32>
+}
+
+/// Body of a `POST /inventory/reservation/<id>/pick` request.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ReservationPick {
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+#[derive(Debug)]
+pub struct NewReservation {
+    product_id: i32,
+    warehouse_id: i32,
+    quantity: i32,
+    reference: Option<String>,
+    created_by: String,
+    ttl_minutes: i32
+}
+
+impl NewReservation {
+    pub fn new(product: i32, warehouse: i32, quantity: i32, operator: &Operator) -> NewReservation {
+        NewReservation {
+            product_id: product,
+            warehouse_id: warehouse,
+            quantity,
+            reference: None,
+            created_by: operator.name().to_string(),
+            ttl_minutes: DEFAULT_TTL_MINUTES
+        }
+    }
+
+    /// Order the stock is reserved for.
+    pub fn with_reference(self, reference: &str) -> NewReservation {
+        NewReservation { reference: Some(reference.to_string()), ..self }
+    }
+
+    pub fn expiring_after(self, ttl_minutes: i32) -> NewReservation {
+        NewReservation { ttl_minutes, ..self }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quantity <= 0 {
+            return Err(format!("Reserved quantity must be positive, got {}", self.quantity));
+        }
+        if self.ttl_minutes <= 0 {
+            return Err(format!("Reservations must live for at least a minute, got {} minutes", self.ttl_minutes));
+        }
+        Ok(())
+    }
+
+    /// Allocates the stock First-Expired-First-Out from pickable bins and records where it was
+    /// taken from. Expired reservations are released first so that their stock can be reserved
+    /// again.
+    pub fn reserve(self, conn: &impl Connection<Backend=Pg>) -> Result<ReservationView, StockError> {
+        use crate::schema::stock_reservation::dsl::*;
+        self.validate().map_err(StockError::InvalidMovement)?;
+        conn.transaction(|| {
+            let allocations = InventoryBalance::allocate_up_to(self.product_id, self.warehouse_id, &PICKABLE, self.quantity, conn)?;
+            let allocated: i32 = allocations.iter().map(|allocation| allocation.quantity).sum();
+            if allocated < self.quantity {
+                return Err(StockError::Shortage { product_id: self.product_id, warehouse_id: self.warehouse_id, available: allocated });
+            }
+            let reservation: Reservation = diesel::insert_into(stock_reservation)
+                .values((
+                    product_id.eq(self.product_id),
+                    warehouse_id.eq(self.warehouse_id),
+                    quantity.eq(self.quantity),
+                    reference.eq(&self.reference),
+                    created_by.eq(&self.created_by),
+                    expires_at.eq(now + self.ttl_minutes.minutes())
+                ))
+                .get_result(conn)?;
+            let lines: Vec<NewReservationLine> = allocations.into_iter()
+                .map(|allocation: Allocation| NewReservationLine {
+                    reservation_id: reservation.id,
+                    bin_location_id: allocation.bin_location_id,
+                    lot_id: allocation.lot_id,
+                    quantity: allocation.quantity
+                })
+                .collect();
+            diesel::insert_into(stock_reservation_line::table)
+                .values(&lines)
+                .execute(conn)?;
+            Ok(ReservationView::load(reservation, conn)?)
+        })
+    }
+}
+
+fn reservation_reference(reservation: i32) -> String {
+    format!("RESERVATION-{}", reservation)
+}
+
+#[cfg(test)]
+mod test {
+    use diesel::prelude::*;
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError};
+    use crate::inventory::reservation::models::{NewReservation, Reservation, ReservationStatus};
+    use crate::operator::Operator;
+
+    #[test]
+    fn reservations_reduce_availability_until_cancelled() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[1], StockChange::on_hand(5), &conn).unwrap();
+
+            let reserved = NewReservation::new(soap.id(), warehouse.id(), 5, &clerk).with_reference("SO-1").reserve(&conn).unwrap();
+            assert_eq!(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0].available(), 0);
+            let second = NewReservation::new(soap.id(), warehouse.id(), 1, &clerk).reserve(&conn);
+            assert!(matches!(second, Err(StockError::Shortage { available: 0, .. })));
+
+            let cancelled = reserved.reservation.clone().cancel(&conn).unwrap().unwrap();
+            assert_eq!(cancelled.status(), ReservationStatus::Cancelled);
+            assert_eq!(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0].available(), 5);
+            assert_eq!(reserved.reservation.cancel(&conn).unwrap(), None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn picking_a_reservation_takes_the_stock_out() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+
+            let reserved = NewReservation::new(soap.id(), warehouse.id(), 3, &clerk).reserve(&conn).unwrap();
+            let picked = reserved.reservation.pick(&[], &clerk, &conn).unwrap().unwrap();
+            assert_eq!(picked.status(), ReservationStatus::Picked);
+            let balance = &InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0];
+            assert_eq!((balance.on_hand(), balance.allocated()), (2, 0));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn expired_reservations_are_released() -> Result<(), String> {
+        use crate::schema::stock_reservation::dsl::*;
+        use diesel::dsl::{now, IntervalDsl};
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(2), &conn).unwrap();
+            let reserved = NewReservation::new(soap.id(), warehouse.id(), 2, &clerk).reserve(&conn).unwrap();
+            diesel::update(stock_reservation.find(reserved.reservation.id()))
+                .set(expires_at.eq(now - 1.minutes()))
+                .execute(&conn).unwrap();
+
+            let again = NewReservation::new(soap.id(), warehouse.id(), 2, &clerk).reserve(&conn).unwrap();
+            assert_eq!(again.lines.len(), 1);
+            assert_eq!(Reservation::find(reserved.reservation.id(), &conn).unwrap().status(), ReservationStatus::Expired);
+            Ok(())
+        })
+    }
+}
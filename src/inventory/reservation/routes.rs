@@ -0,0 +1,62 @@
+use crate::inventory::reservation::models::{Reservation, ReservationView, ReservationRequest, ReservationPick, NewReservation};
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+#[post("/inventory/reservation", format="application/json", data="<request>")]
+pub fn post(request: Json<ReservationRequest>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<ReservationView>>, ApiError> {
+    let operator = operator?;
+    let request = request.into_inner();
+    let product = Product::find(request.product_id, &*conn)?;
+    let warehouse = Warehouse::find(request.warehouse_id, &*conn)?;
+    let quantity = request.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    let mut reservation = NewReservation::new(product.id(), warehouse.id(), quantity, &operator);
+    if let Some(ref reference) = request.reference {
+        reservation = reservation.with_reference(reference);
+    }
+    if let Some(ttl_minutes) = request.ttl_minutes {
+        reservation = reservation.expiring_after(ttl_minutes);
+    }
+    let reserved = reservation.reserve(&*conn)?;
+    Ok(Created(format!("/inventory/reservation/{}", reserved.reservation.id()), Some(Json(reserved))))
+}
+
+#[get("/inventory/reservation/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<ReservationView>, ApiError> {
+    let reservation = Reservation::find(id, &*conn)?;
+    Ok(Json(ReservationView::load(reservation, &*conn)?))
+}
+
+#[post("/inventory/reservation/<id>/pick", data="<pick>")]
+pub fn pick(id: i32, pick: Option<Json<ReservationPick>>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<ReservationView>, ApiError> {
+    let operator = operator?;
+    let reservation = Reservation::find(id, &*conn)?;
+    let pick = pick.map(Json::into_inner).unwrap_or_default();
+    match reservation.pick(&pick.serial_numbers, &operator, &*conn)? {
+        Some(picked) => Ok(Json(ReservationView::load(picked, &*conn)?)),
+        None => Err(ApiError::Conflict(format!("Reservation {} is no longer active", id)))
+    }
+}
+
+#[delete("/inventory/reservation/<id>")]
+pub fn cancel(id: i32, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    operator?;
+    let reservation = Reservation::find(id, &*conn)?;
+    match reservation.cancel(&*conn)? {
+        Some(_) => Ok(Status::NoContent),
+        None => Err(ApiError::Conflict(format!("Reservation {} is no longer active", id)))
+    }
+}
+
+#[post("/inventory/reservation/expired")]
+pub fn release_expired(conn: PostgresConnection) -> Result<Json<Vec<Reservation>>, ApiError> {
+    Ok(Json(Reservation::release_expired(&*conn)?))
+}
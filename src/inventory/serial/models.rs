@@ -0,0 +1,231 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use crate::schema::{product_serial, stock_movement, stock_movement_serial};
+use crate::inventory::balance::models::StockError;
+use crate::inventory::movement::models::StockMovement;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// A single unit of a serialised product and the bin it currently is in, if it is in stock.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product_serial"]
+pub struct SerialUnit {
+    id: i32,
+    product_id: i32,
+    serial_number: String,
+    bin_location_id: Option<i32>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="product_serial"]
+struct NewSerialUnit<'a> {
+    product_id: i32,
+    serial_number: &'a str,
+    bin_location_id: Option<i32>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="stock_movement_serial"]
+struct MovedSerial {
+    movement_id: i32,
+    serial_id: i32
+}
+
+impl SerialUnit {
+
+    pub fn find(product: i32, number: &str, conn: &impl Connection<Backend=Pg>) -> Result<SerialUnit, diesel::result::Error> {
+        use crate::schema::product_serial::dsl::*;
+        product_serial
+            .filter(product_id.eq(product).and(serial_number.eq(normalized(number))))
+            .first(conn)
+    }
+
+    /// Units of `product` currently in stock, by serial number.
+    pub fn in_stock(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SerialUnit>, diesel::result::Error> {
+        use crate::schema::product_serial::dsl::*;
+        product_serial
+            .filter(product_id.eq(product).and(bin_location_id.is_not_null()))
+            .order(serial_number.asc())
+            .load(conn)
+    }
+
+    /// Serial numbers of the units moved by `movement`.
+    pub fn numbers_moved_by(movement: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<String>, diesel::result::Error> {
+        product_serial::table
+            .inner_join(stock_movement_serial::table)
+            .filter(stock_movement_serial::movement_id.eq(movement))
+            .order(product_serial::serial_number.asc())
+            .select(product_serial::serial_number)
+            .load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn bin_location_id(&self) -> Option<i32> {
+        self.bin_location_id
+    }
+
+    /// Moves the units named by `numbers` along with `movement`. Every unit must be in the bin
+    /// the movement takes stock from, units entering the warehouse must not be in stock yet.
+    pub fn move_with(movement: &StockMovement, numbers: &[String], conn: &impl Connection<Backend=Pg>) -> Result<Vec<SerialUnit>, StockError> {
+        use crate::schema::product_serial::dsl::*;
+        let numbers: Vec<String> = numbers.iter().map(|number| normalized(number)).collect();
+        validate_numbers(&numbers, movement.quantity())?;
+
+        let mut moved = Vec::with_capacity(numbers.len());
+        for number in &numbers {
+            let unit = match movement.from_location_id() {
+                Some(_) => SerialUnit::find(movement.product_id(), number, conn).optional()?,
+                None => {
+                    diesel::insert_into(product_serial)
+                        .values(NewSerialUnit { product_id: movement.product_id(), serial_number: number, bin_location_id: None })
+                        .on_conflict_do_nothing()
+                        .execute(conn)?;
+                    Some(SerialUnit::find(movement.product_id(), number, conn)?)
+                }
+            };
+            let unit = unit.filter(|unit| unit.bin_location_id == movement.from_location_id())
+                .ok_or_else(|| StockError::InvalidMovement(misplaced(number, movement.from_location_id())))?;
+
+            let updated = match unit.bin_location_id {
+                Some(bin) => diesel::update(product_serial.filter(id.eq(unit.id).and(bin_location_id.eq(bin))))
+                    .set(bin_location_id.eq(movement.to_location_id()))
+                    .get_result::<SerialUnit>(conn),
+                None => diesel::update(product_serial.filter(id.eq(unit.id).and(bin_location_id.is_null())))
+                    .set(bin_location_id.eq(movement.to_location_id()))
+                    .get_result::<SerialUnit>(conn)
+            };
+            let updated = updated.optional()?
+                .ok_or_else(|| StockError::InvalidMovement(misplaced(number, movement.from_location_id())))?;
+            moved.push(updated);
+        }
+
+        let links: Vec<MovedSerial> = moved.iter()
+            .map(|unit| MovedSerial { movement_id: movement.id(), serial_id: unit.id })
+            .collect();
+        diesel::insert_into(stock_movement_serial::table)
+            .values(&links)
+            .execute(conn)?;
+        Ok(moved)
+    }
+}
+
+/// A unit together with every movement it took part in, oldest first.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SerialHistory {
+    pub unit: SerialUnit,
+    pub movements: Vec<StockMovement>
+}
+
+impl SerialHistory {
+    pub fn load(product: i32, number: &str, conn: &impl Connection<Backend=Pg>) -> Result<SerialHistory, diesel::result::Error> {
+        let unit = SerialUnit::find(product, number, conn)?;
+        let movements = stock_movement::table
+            .inner_join(stock_movement_serial::table)
+            .filter(stock_movement_serial::serial_id.eq(unit.id))
+            .order(stock_movement::id.asc())
+            .select(stock_movement::all_columns)
+            .load(conn)?;
+        Ok(SerialHistory { unit, movements })
+    }
+}
+
+fn normalized(number: &str) -> String {
+    number.trim().to_uppercase()
+}
+
+fn validate_numbers(numbers: &[String], quantity: i32) -> Result<(), StockError> {
+    if numbers.len() != quantity as usize {
+        return Err(StockError::InvalidMovement(
+            format!("Moving {} units of a serialised product requires {} serial numbers, got {}", quantity, quantity, numbers.len())));
+    }
+    if numbers.iter().any(String::is_empty) {
+        return Err(StockError::InvalidMovement(String::from("Serial numbers must not be empty")));
+    }
+    for (position, number) in numbers.iter().enumerate() {
+        if numbers[..position].contains(number) {
+            return Err(StockError::InvalidMovement(format!("Serial number {} is given twice", number)));
+        }
+    }
+    Ok(())
+}
+
+fn misplaced(number: &str, expected_bin: Option<i32>) -> String {
+    match expected_bin {
+        Some(bin) => format!("Serial number {} is not in bin {}", number, bin),
+        None => format!("Serial number {} is already in stock", number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::warehouse_with_bins;
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::StockError;
+    use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+    use crate::inventory::serial::models::{validate_numbers, SerialHistory, SerialUnit};
+    use crate::product::models::NewProduct;
+    use crate::operator::Operator;
+
+    fn numbers(numbers: &[&str]) -> Vec<String> {
+        numbers.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn serial_numbers_must_match_quantity_and_be_distinct() {
+        assert!(validate_numbers(&numbers(&["A1", "A2"]), 2).is_ok());
+        assert!(validate_numbers(&numbers(&["A1"]), 2).is_err());
+        assert!(validate_numbers(&numbers(&["A1", "A1"]), 2).is_err());
+    }
+
+    #[test]
+    fn serialised_units_are_followed_through_their_movements() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::Reserve]);
+            let phone = NewProduct::new("PHONE", "phone").serialised().create(&conn).unwrap();
+            let clerk = Operator::new("clerk");
+
+            let receipt = NewStockMovement::new(phone.id(), None, Some(bins[0].id()), 2, MovementReason::Receipt, &clerk)
+                .with_serials(numbers(&["sn-1", "SN-2"]))
+                .post(&conn).unwrap();
+            let putaway = NewStockMovement::new(phone.id(), Some(bins[0].id()), Some(bins[1].id()), 1, MovementReason::Transfer, &clerk)
+                .with_serials(numbers(&["SN-1"]))
+                .post(&conn).unwrap();
+
+            let history = SerialHistory::load(phone.id(), "sn-1", &conn).unwrap();
+            assert_eq!(history.unit.bin_location_id(), Some(bins[1].id()));
+            assert_eq!(history.movements, vec![receipt.clone(), putaway]);
+            assert_eq!(SerialUnit::numbers_moved_by(receipt.id(), &conn).unwrap(), numbers(&["SN-1", "SN-2"]));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn serial_numbers_are_required_and_unique_in_stock() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::Reserve]);
+            let phone = NewProduct::new("PHONE", "phone").serialised().create(&conn).unwrap();
+            let clerk = Operator::new("clerk");
+
+            let without = NewStockMovement::new(phone.id(), None, Some(bins[0].id()), 1, MovementReason::Receipt, &clerk).post(&conn);
+            assert!(matches!(without, Err(StockError::InvalidMovement(_))));
+
+            NewStockMovement::new(phone.id(), None, Some(bins[0].id()), 1, MovementReason::Receipt, &clerk)
+                .with_serials(numbers(&["SN-1"]))
+                .post(&conn).unwrap();
+            let twice = NewStockMovement::new(phone.id(), None, Some(bins[1].id()), 1, MovementReason::Receipt, &clerk)
+                .with_serials(numbers(&["SN-1"]))
+                .post(&conn);
+            assert!(matches!(twice, Err(StockError::InvalidMovement(_))));
+
+            let wrong_bin = NewStockMovement::new(phone.id(), Some(bins[1].id()), None, 1, MovementReason::Pick, &clerk)
+                .with_serials(numbers(&["SN-1"]))
+                .post(&conn);
+            assert!(wrong_bin.is_err());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,19 @@
+use crate::inventory::serial::models::{SerialUnit, SerialHistory};
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+#[get("/product/<id>/serial")]
+pub fn in_stock(id: i32, conn: PostgresConnection) -> Result<Json<Vec<SerialUnit>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(SerialUnit::in_stock(product.id(), &*conn)?))
+}
+
+#[get("/product/<id>/serial/<number>")]
+pub fn history(id: i32, number: String, conn: PostgresConnection) -> Result<Json<SerialHistory>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(SerialHistory::load(product.id(), &number, &*conn)?))
+}
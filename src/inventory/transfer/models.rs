@@ -0,0 +1,277 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use crate::schema::stock_transfer;
+use crate::inventory::balance::models::StockError;
+use crate::inventory::movement::models::{StockMovement, NewStockMovement, MovementReason};
+use crate::inventory::serial::models::SerialUnit;
+use crate::location::bin::models::BinLocation;
+use crate::location::warehouse::models::Warehouse;
+use crate::operator::Operator;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    pub enum TransferStatus {
+        InTransit => "in_transit",
+        Received => "received"
+    }
+}
+
+/// Stock shipped from a bin of one warehouse to another warehouse. While in transit it is
+/// counted in neither warehouse's balances but in the transfer itself.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="stock_transfer"]
+pub struct StockTransfer {
+    id: i32,
+    product_id: i32,
+    from_warehouse_id: i32,
+    from_location_id: i32,
+    to_warehouse_id: i32,
+    to_location_id: Option<i32>,
+    quantity: i32,
+    status: TransferStatus,
+    shipped_by: String,
+    shipped_at: NaiveDateTime,
+    received_by: Option<String>,
+    received_at: Option<NaiveDateTime>,
+    lot_id: Option<i32>
+}
+
+impl StockTransfer {
+
+    pub fn find(transfer_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<StockTransfer, diesel::result::Error> {
+        use crate::schema::stock_transfer::dsl::*;
+        stock_transfer.find(transfer_id).first(conn)
+    }
+
+    /// Transfers still on their way, optionally only those heading to `warehouse`.
+    pub fn in_transit(warehouse: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<StockTransfer>, diesel::result::Error> {
+        use crate::schema::stock_transfer::dsl::*;
+        let mut query = stock_transfer
+            .filter(status.eq(TransferStatus::InTransit))
+            .into_boxed();
+        if let Some(warehouse) = warehouse {
+            query = query.filter(to_warehouse_id.eq(warehouse));
+        }
+        query.order(id.asc()).load(conn)
+    }
+
+    /// Quantity of `product` currently in transit, optionally only towards `warehouse`.
+    pub fn quantity_in_transit(product: i32, warehouse: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<i64, diesel::result::Error> {
+        use crate::schema::stock_transfer::dsl::*;
+        use diesel::dsl::sum;
+        let mut query = stock_transfer
+            .filter(product_id.eq(product).and(status.eq(TransferStatus::InTransit)))
+            .select(sum(quantity))
+            .into_boxed();
+        if let Some(warehouse) = warehouse {
+            query = query.filter(to_warehouse_id.eq(warehouse));
+        }
+        Ok(query.get_result::<Option<i64>>(conn)?.unwrap_or(0))
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn status(&self) -> TransferStatus {
+        self.status
+    }
+
+    pub fn to_warehouse_id(&self) -> i32 {
+        self.to_warehouse_id
+    }
+
+    /// Books the transfer into `bin` of the receiving warehouse. Returns `None` if the transfer
+    /// has been received in the meantime.
+    pub fn receive(self, bin: &BinLocation, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<StockTransfer>, StockError> {
+        use crate::schema::stock_transfer::dsl::*;
+        if bin.warehouse_id() != self.to_warehouse_id {
+            return Err(StockError::InvalidMovement(format!("Transfer {} must be received in warehouse {}", self.id, self.to_warehouse_id)));
+        }
+        conn.transaction(|| {
+            let received = diesel::update(stock_transfer.filter(id.eq(self.id).and(status.eq(TransferStatus::InTransit))))
+                .set((
+                    status.eq(TransferStatus::Received),
+                    to_location_id.eq(Some(bin.id())),
+                    received_by.eq(Some(operator.name())),
+                    received_at.eq(diesel::dsl::now.nullable())
+                ))
+                .get_result::<StockTransfer>(conn);
+            let received = match received {
+                Ok(received) => received,
+                Err(diesel::result::Error::NotFound) => return Ok(None),
+                Err(e) => return Err(e.into())
+            };
+            let shipment = StockMovement::for_document(&transfer_reference(self.id), MovementReason::TransferOut, conn)?;
+            NewStockMovement::new(self.product_id, None, Some(bin.id()), self.quantity, MovementReason::TransferIn, operator)
+                .in_lot(self.lot_id)
+                .with_serials(SerialUnit::numbers_moved_by(shipment.id(), conn)?)
+                .with_reference(&transfer_reference(self.id))
+                .post(conn)?;
+            Ok(Some(received))
+        })
+    }
+}
+
+/// Body of a `POST /inventory/transfer` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferShipment {
+    pub product_id: i32,
+    pub from_location_id: i32,
+    pub to_warehouse_id: i32,
+    pub quantity: crate::product::packaging::models::Quantity,
+    #[serde(default)]
+    pub lot_id: Option<i32>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="stock_transfer"]
+pub struct NewStockTransfer {
+    product_id: i32,
+    from_warehouse_id: i32,
+    from_location_id: i32,
+    to_warehouse_id: i32,
+    quantity: i32,
+    shipped_by: String,
+    lot_id: Option<i32>
+}
+
+impl NewStockTransfer {
+    pub fn new(product: i32, from: &BinLocation, to: &Warehouse, quantity: i32, operator: &Operator) -> NewStockTransfer {
+        NewStockTransfer {
+            product_id: product,
+            from_warehouse_id: from.warehouse_id(),
+            from_location_id: from.id(),
+            to_warehouse_id: to.id(),
+            quantity,
+            shipped_by: operator.name().to_string(),
+            lot_id: None
+        }
+    }
+
+    pub fn in_lot(self, lot: Option<i32>) -> NewStockTransfer {
+        NewStockTransfer { lot_id: lot, ..self }
+    }
+
+    /// Takes the stock out of the source bin and puts it in transit, serialised units are
+    /// received under the same serial numbers they were shipped with. The movement is booked
+    /// for the operator the transfer is shipped by.
+    pub fn ship(self, serial_numbers: Vec<String>, conn: &impl Connection<Backend=Pg>) -> Result<StockTransfer, StockError> {
+        use crate::schema::stock_transfer::dsl::*;
+        if self.from_warehouse_id == self.to_warehouse_id {
+            return Err(StockError::InvalidMovement(String::from("Stock moved within a warehouse is transferred between bins")));
+        }
+        conn.transaction(|| {
+            let shipped: StockTransfer = diesel::insert_into(stock_transfer)
+                .values(&self)
+                .get_result(conn)?;
+            NewStockMovement::new(self.product_id, Some(self.from_location_id), None, self.quantity, MovementReason::TransferOut, &Operator::new(&self.shipped_by))
+                .in_lot(self.lot_id)
+                .with_serials(serial_numbers)
+                .with_reference(&transfer_reference(shipped.id))
+                .post(conn)?;
+            Ok(shipped)
+        })
+    }
+}
+
+/// Body of a `POST /inventory/bin-transfer` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BinTransfer {
+    pub product_id: i32,
+    pub from_location_id: i32,
+    pub to_location_id: i32,
+    pub quantity: crate::product::packaging::models::Quantity,
+    #[serde(default)]
+    pub lot_id: Option<i32>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+/// Body of a `POST /inventory/transfer/<id>/receive` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferReceipt {
+    pub to_location_id: i32
+}
+
+fn transfer_reference(transfer: i32) -> String {
+    format!("TRANSFER-{}", transfer)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockError};
+    use crate::inventory::movement::models::{NewStockMovement, MovementReason, Drift};
+    use crate::inventory::transfer::models::{NewStockTransfer, StockTransfer, TransferStatus};
+    use crate::operator::Operator;
+
+    #[test]
+    fn shipped_stock_is_in_transit_until_received() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, source) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve]);
+            let (target, destination) = warehouse_with_bins(&conn, "WH2", &[LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+            NewStockMovement::new(soap.id(), None, Some(source[0].id()), 10, MovementReason::Receipt, &clerk)
+                .post(&conn).unwrap();
+
+            let transfer = NewStockTransfer::new(soap.id(), &source[0], &target, 4, &clerk)
+                .ship(Vec::new(), &conn).unwrap();
+            assert_eq!(transfer.status(), TransferStatus::InTransit);
+            assert_eq!(InventoryBalance::for_bin(source[0].id(), &conn).unwrap()[0].on_hand(), 6);
+            assert_eq!(StockTransfer::quantity_in_transit(soap.id(), None, &conn).unwrap(), 4);
+
+            let received = transfer.clone().receive(&destination[0], &clerk, &conn).unwrap().unwrap();
+            assert_eq!(received.status(), TransferStatus::Received);
+            assert_eq!(InventoryBalance::for_bin(destination[0].id(), &conn).unwrap()[0].on_hand(), 4);
+            assert_eq!(StockTransfer::quantity_in_transit(soap.id(), None, &conn).unwrap(), 0);
+            assert!(Drift::for_warehouse(target.id(), &conn).unwrap().is_empty());
+
+            assert_eq!(transfer.receive(&destination[0], &clerk, &conn).unwrap(), None);
+            assert_eq!(InventoryBalance::for_bin(destination[0].id(), &conn).unwrap()[0].on_hand(), 4);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn transfers_are_received_in_their_target_warehouse_only() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (source_warehouse, source) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve]);
+            let (target, _) = warehouse_with_bins(&conn, "WH2", &[LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+            NewStockMovement::new(soap.id(), None, Some(source[0].id()), 1, MovementReason::Receipt, &clerk)
+                .post(&conn).unwrap();
+
+            let within = NewStockTransfer::new(soap.id(), &source[0], &source_warehouse, 1, &clerk).ship(Vec::new(), &conn);
+            assert!(matches!(within, Err(StockError::InvalidMovement(_))));
+
+            let transfer = NewStockTransfer::new(soap.id(), &source[0], &target, 1, &clerk).ship(Vec::new(), &conn).unwrap();
+            let wrong_warehouse = transfer.receive(&source[0], &clerk, &conn);
+            assert!(matches!(wrong_warehouse, Err(StockError::InvalidMovement(_))));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn shipping_more_than_on_hand_puts_nothing_in_transit() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, source) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve]);
+            let (target, _) = warehouse_with_bins(&conn, "WH2", &[LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            let clerk = Operator::new("clerk");
+
+            let shipped = NewStockTransfer::new(soap.id(), &source[0], &target, 1, &clerk).ship(Vec::new(), &conn);
+            assert!(matches!(shipped, Err(StockError::InsufficientStock { .. })));
+            assert!(StockTransfer::in_transit(None, &conn).unwrap().is_empty());
+            Ok(())
+        })
+    }
+}
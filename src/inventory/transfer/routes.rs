@@ -0,0 +1,62 @@
+use crate::inventory::transfer::models::{StockTransfer, NewStockTransfer, TransferShipment, TransferReceipt, BinTransfer};
+use crate::inventory::movement::models::{StockMovement, NewStockMovement, MovementReason};
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+use crate::location::bin::models::BinLocation;
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+#[post("/inventory/bin-transfer", format="application/json", data="<transfer>")]
+pub fn between_bins(transfer: Json<BinTransfer>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<StockMovement>>, ApiError> {
+    let operator = operator?;
+    let transfer = transfer.into_inner();
+    let product = Product::find(transfer.product_id, &*conn)?;
+    let quantity = transfer.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    let moved = NewStockMovement::new(product.id(), Some(transfer.from_location_id), Some(transfer.to_location_id),
+                                      quantity, MovementReason::Transfer, &operator)
+        .in_lot(transfer.lot_id)
+        .with_serials(transfer.serial_numbers)
+        .post(&*conn)?;
+    Ok(Created(format!("/inventory/movement/{}", moved.id()), Some(Json(moved))))
+}
+
+#[post("/inventory/transfer", format="application/json", data="<shipment>")]
+pub fn ship(shipment: Json<TransferShipment>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<StockTransfer>>, ApiError> {
+    let operator = operator?;
+    let shipment = shipment.into_inner();
+    let product = Product::find(shipment.product_id, &*conn)?;
+    let source = BinLocation::find(shipment.from_location_id, &*conn)?;
+    let target = Warehouse::find(shipment.to_warehouse_id, &*conn)?;
+    let quantity = shipment.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    let shipped = NewStockTransfer::new(product.id(), &source, &target, quantity, &operator)
+        .in_lot(shipment.lot_id)
+        .ship(shipment.serial_numbers, &*conn)?;
+    Ok(Created(format!("/inventory/transfer/{}", shipped.id()), Some(Json(shipped))))
+}
+
+#[get("/inventory/transfer?<warehouse>")]
+pub fn in_transit(warehouse: Option<i32>, conn: PostgresConnection) -> Result<Json<Vec<StockTransfer>>, ApiError> {
+    Ok(Json(StockTransfer::in_transit(warehouse, &*conn)?))
+}
+
+#[get("/inventory/transfer/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<StockTransfer>, ApiError> {
+    Ok(Json(StockTransfer::find(id, &*conn)?))
+}
+
+#[post("/inventory/transfer/<id>/receive", format="application/json", data="<receipt>")]
+pub fn receive(id: i32, receipt: Json<TransferReceipt>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<StockTransfer>, ApiError> {
+    let operator = operator?;
+    let transfer = StockTransfer::find(id, &*conn)?;
+    let bin = BinLocation::find(receipt.to_location_id, &*conn)?;
+    match transfer.receive(&bin, &operator, &*conn)? {
+        Some(received) => Ok(Json(received)),
+        None => Err(ApiError::Conflict(format!("Transfer {} has already been received", id)))
+    }
+}
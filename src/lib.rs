@@ -10,8 +10,19 @@ extern crate rocket_contrib;
 extern crate diesel_migrations;
 extern crate dotenv;
 
+#[macro_use]
+mod sql_enum;
 mod schema;
+pub mod error;
+pub mod domain;
+pub mod etag;
+pub mod operator;
+pub mod pagination;
 pub mod product;
+pub mod location;
+pub mod inventory;
+pub mod purchasing;
+pub mod sales;
 pub mod configuration;
 
 pub(crate) mod testing;
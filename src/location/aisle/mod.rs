@@ -0,0 +1,2 @@
+pub(crate) mod models;
+pub mod routes;
\ No newline at end of file
@@ -0,0 +1,169 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::warehouse_aisle;
+use crate::location::warehouse::models::validate_code;
+use crate::location::zone::models::Zone;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::Integer;
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// A rack lined aisle within a zone, `sequence` orders aisles along the walking path.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="warehouse_aisle"]
+pub struct Aisle {
+    id: i32,
+    zone_id: i32,
+    code: String,
+    sequence: i32,
+    version: i32
+}
+
+impl AsChangeset for Aisle {
+    type Target = warehouse_aisle::table;
+    type Changeset = <(DieselEq<warehouse_aisle::sequence, Bound<Integer, i32>>,
+                       DieselEq<warehouse_aisle::version, DieselAdd<warehouse_aisle::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            warehouse_aisle::sequence.eq(self.sequence),
+            warehouse_aisle::version.eq(warehouse_aisle::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Aisle {
+
+    pub fn find(aisle_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Aisle, diesel::result::Error> {
+        use crate::schema::warehouse_aisle::dsl::*;
+        warehouse_aisle.find(aisle_id).first(conn)
+    }
+
+    pub fn for_zone(zone: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Aisle>, diesel::result::Error> {
+        use crate::schema::warehouse_aisle::dsl::*;
+        warehouse_aisle.filter(zone_id.eq(zone)).order((sequence.asc(), code.asc())).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn zone_id(&self) -> i32 {
+        self.zone_id
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Aisle>, diesel::result::Error> {
+        use crate::schema::warehouse_aisle::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(warehouse_aisle.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::warehouse_aisle::dsl::*;
+        conn.transaction(|| {
+            delete(warehouse_aisle.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `PUT /aisle/<id>` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AisleChanges {
+    sequence: i32
+}
+
+impl AisleChanges {
+    pub fn for_aisle(self, current: Aisle, expected_version: i32) -> Aisle {
+        Aisle {
+            sequence: self.sequence,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+/// Body of a `POST /zone/<id>/aisle` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AisleDefinition {
+    pub code: String,
+    #[serde(default)]
+    pub sequence: i32
+}
+
+impl AisleDefinition {
+    pub fn in_zone(self, zone: &Zone) -> Result<NewAisle, String> {
+        validate_code("Aisle", &self.code)?;
+        Ok(NewAisle {
+            zone_id: zone.id(),
+            code: self.code.trim().to_uppercase(),
+            sequence: self.sequence
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="warehouse_aisle"]
+pub struct NewAisle {
+    zone_id: i32,
+    code: String,
+    sequence: i32
+}
+
+impl NewAisle {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Aisle, diesel::result::Error> {
+        use crate::schema::warehouse_aisle::dsl::*;
+        use crate::schema::warehouse_aisle::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(warehouse_aisle)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::location::warehouse::models::NewWarehouse;
+    use crate::location::zone::models::ZoneDefinition;
+    use crate::location::aisle::models::{AisleChanges, AisleDefinition, Aisle};
+
+    #[test]
+    fn stale_aisle_changes_are_not_saved() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let warehouse = NewWarehouse::new("WH1", "first").create(&conn).unwrap();
+            let zone = ZoneDefinition { code: "A".to_string(), name: "ambient".to_string() }
+                .in_warehouse(&warehouse).unwrap()
+                .create(&conn).unwrap();
+            let aisle = AisleDefinition { code: "01".to_string(), sequence: 1 }
+                .in_zone(&zone).unwrap()
+                .create(&conn).unwrap();
+
+            let moved = AisleChanges { sequence: 5 }.for_aisle(aisle.clone(), 0).update(&conn).unwrap().unwrap();
+            assert_eq!((moved.sequence, moved.version()), (5, 1));
+            assert_eq!(AisleChanges { sequence: 7 }.for_aisle(aisle, 0).update(&conn).unwrap(), None);
+            assert_eq!(Aisle::find(moved.id(), &conn).unwrap(), moved);
+            Ok(())
+        })
+    }
+}
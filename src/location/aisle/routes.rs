@@ -0,0 +1,55 @@
+use crate::location::aisle::models::{Aisle, AisleChanges, AisleDefinition};
+use crate::location::zone::models::Zone;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/zone/<zone_id>/aisle", format="application/json", data="<aisle>")]
+pub fn post(zone_id: i32, aisle: Json<AisleDefinition>, conn: PostgresConnection) -> Result<Tagged<Created<Json<Aisle>>>, ApiError> {
+    let zone = Zone::find(zone_id, &*conn)?;
+    let created = aisle.into_inner()
+        .in_zone(&zone)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/aisle/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/zone/<zone_id>/aisle")]
+pub fn list(zone_id: i32, conn: PostgresConnection) -> Result<Json<Vec<Aisle>>, ApiError> {
+    let zone = Zone::find(zone_id, &*conn)?;
+    Ok(Json(Aisle::for_zone(zone.id(), &*conn)?))
+}
+
+#[get("/aisle/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<Aisle>>, ApiError> {
+    let aisle = Aisle::find(id, &*conn)?;
+    Ok(Tagged(aisle.version(), Json(aisle)))
+}
+
+#[put("/aisle/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<AisleChanges>, conn: PostgresConnection) -> Result<Tagged<Json<Aisle>>, ApiError> {
+    let expected_version = if_match?.version();
+    let current = Aisle::find(id, &*conn)?;
+    match changes.into_inner().for_aisle(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(Aisle::find(id, &*conn), Aisle::version))
+    }
+}
+
+#[delete("/aisle/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let aisle = Aisle::find(id, &*conn)?;
+    if aisle.version() != expected_version {
+        return Err(ApiError::precondition_failed(aisle.version(), &aisle));
+    }
+    match aisle.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(Aisle::find(id, &*conn), Aisle::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
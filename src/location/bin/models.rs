@@ -0,0 +1,400 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::bin_location;
+use crate::location::warehouse::models::{Warehouse, validate_code};
+use crate::location::zone::models::Zone;
+use crate::location::aisle::models::Aisle;
+use crate::product::models::Product;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Integer, BigInt, Text, Nullable};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    /// What a bin is used for within the flow of goods through the warehouse.
+    pub enum LocationType {
+        PickFace => "pick_face",
+        Reserve => "reserve",
+        Staging => "staging",
+        Dock => "dock",
+        Returns => "returns"
+    }
+}
+
+/// Bins stock can be allocated and picked from. Stock in dock, staging and returns bins is still
+/// being received or inspected, or on its way out, and is never available.
+pub const PICKABLE: [LocationType; 2] = [LocationType::Reserve, LocationType::PickFace];
+
+impl LocationType {
+    pub fn is_pickable(self) -> bool {
+        PICKABLE.contains(&self)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="bin_location"]
+pub struct BinLocation {
+    id: i32,
+    warehouse_id: i32,
+    zone_id: i32,
+    aisle_id: i32,
+    code: String,
+    location_code: String,
+    location_type: LocationType,
+    max_units: Option<i32>,
+    max_volume_cm3: Option<i64>,
+    max_weight_g: Option<i64>,
+    version: i32
+}
+
+impl AsChangeset for BinLocation {
+    type Target = bin_location::table;
+    type Changeset = <(DieselEq<bin_location::location_type, Bound<Text, LocationType>>,
+                       DieselEq<bin_location::max_units, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<bin_location::max_volume_cm3, Bound<Nullable<BigInt>, Option<i64>>>,
+                       DieselEq<bin_location::max_weight_g, Bound<Nullable<BigInt>, Option<i64>>>,
+                       DieselEq<bin_location::version, DieselAdd<bin_location::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            bin_location::location_type.eq(self.location_type),
+            bin_location::max_units.eq(self.max_units),
+            bin_location::max_volume_cm3.eq(self.max_volume_cm3),
+            bin_location::max_weight_g.eq(self.max_weight_g),
+            bin_location::version.eq(bin_location::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl BinLocation {
+
+    pub fn find(bin_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<BinLocation, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        bin_location.find(bin_id).first(conn)
+    }
+
+    pub fn find_by_code(code: &str, conn: &impl Connection<Backend=Pg>) -> Result<BinLocation, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        bin_location.filter(location_code.eq(code.trim().to_uppercase())).first(conn)
+    }
+
+    pub fn for_zone(zone: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<BinLocation>, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        bin_location.filter(zone_id.eq(zone)).order(location_code.asc()).load(conn)
+    }
+
+    pub fn of_type(warehouse: i32, kind: LocationType, conn: &impl Connection<Backend=Pg>) -> Result<Vec<BinLocation>, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        bin_location
+            .filter(warehouse_id.eq(warehouse).and(location_type.eq(kind)))
+            .order(location_code.asc())
+            .load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn zone_id(&self) -> i32 {
+        self.zone_id
+    }
+
+    pub fn aisle_id(&self) -> i32 {
+        self.aisle_id
+    }
+
+    pub fn location_code(&self) -> &str {
+        &self.location_code
+    }
+
+    pub fn location_type(&self) -> LocationType {
+        self.location_type
+    }
+
+    pub fn max_units(&self) -> Option<i32> {
+        self.max_units
+    }
+
+    pub fn max_volume_cm3(&self) -> Option<i64> {
+        self.max_volume_cm3
+    }
+
+    pub fn max_weight_g(&self) -> Option<i64> {
+        self.max_weight_g
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Base units of `product` that still fit into the bin next to what it holds already, `None`
+    /// if no capacity limit applies. Dimensions or weight unknown for a product do not count.
+    pub fn room_for(&self, product: &Product, conn: &impl Connection<Backend=Pg>) -> Result<Option<i64>, diesel::result::Error> {
+        self.room_besides(product, BinLoad::default(), conn)
+    }
+
+    /// Like `room_for`, with `planned` counted as if it was in the bin already.
+    pub fn room_besides(&self, product: &Product, planned: BinLoad, conn: &impl Connection<Backend=Pg>) -> Result<Option<i64>, diesel::result::Error> {
+        let contents: BinLoad = diesel::sql_query(
+            "select coalesce(sum(b.on_hand), 0)::bigint as units, \
+                    coalesce(sum(b.on_hand::bigint * p.length_mm * p.width_mm * p.height_mm / 1000), 0)::bigint as volume_cm3, \
+                    coalesce(sum(b.on_hand::bigint * p.weight_g), 0)::bigint as weight_g \
+             from inventory_balance b join product p on p.id = b.product_id \
+             where b.bin_location_id = $1")
+            .bind::<Integer, _>(self.id)
+            .get_result(conn)?;
+        let contents = contents.plus(planned);
+        let limits = [
+            self.max_units.map(|max| i64::from(max) - contents.units),
+            self.max_volume_cm3.and_then(|max| product.volume_cm3().filter(|volume| *volume > 0)
+                .map(|volume| (max - contents.volume_cm3) / volume)),
+            self.max_weight_g.and_then(|max| product.weight_g().filter(|weight| *weight > 0)
+                .map(|weight| (max - contents.weight_g) / i64::from(weight)))
+        ];
+        Ok(limits.iter().flatten().min().map(|room| (*room).max(0)))
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<BinLocation>, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(bin_location.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        conn.transaction(|| {
+            delete(bin_location.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Units, volume and weight of the stock in a bin or planned to go there.
+#[derive(Debug, PartialEq, Clone, Copy, Default, QueryableByName)]
+pub struct BinLoad {
+    #[sql_type="BigInt"]
+    units: i64,
+    #[sql_type="BigInt"]
+    volume_cm3: i64,
+    #[sql_type="BigInt"]
+    weight_g: i64
+}
+
+impl BinLoad {
+    /// The load of `quantity` base units of `product`, dimensions or weight unknown do not count.
+    pub fn of(product: &Product, quantity: i32) -> BinLoad {
+        let quantity = i64::from(quantity);
+        BinLoad {
+            units: quantity,
+            volume_cm3: product.volume_cm3().unwrap_or(0).saturating_mul(quantity),
+            weight_g: i64::from(product.weight_g().unwrap_or(0)).saturating_mul(quantity)
+        }
+    }
+
+    pub fn plus(self, other: BinLoad) -> BinLoad {
+        BinLoad {
+            units: self.units.saturating_add(other.units),
+            volume_cm3: self.volume_cm3.saturating_add(other.volume_cm3),
+            weight_g: self.weight_g.saturating_add(other.weight_g)
+        }
+    }
+}
+
+/// Limits on what a bin can hold, a missing limit is not enforced.
+#[derive(Debug, PartialEq, Clone, Default, Serialize, Deserialize)]
+pub struct Capacity {
+    #[serde(default)]
+    pub max_units: Option<i32>,
+    #[serde(default)]
+    pub max_volume_cm3: Option<i64>,
+    #[serde(default)]
+    pub max_weight_g: Option<i64>
+}
+
+impl Capacity {
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = self.max_units.map_or(true, |m| m > 0)
+            && self.max_volume_cm3.map_or(true, |m| m > 0)
+            && self.max_weight_g.map_or(true, |m| m > 0);
+        if positive {
+            Ok(())
+        } else {
+            Err(String::from("Bin capacity limits must be positive"))
+        }
+    }
+}
+
+/// Body of a `PUT /bin/<id>` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BinChanges {
+    location_type: LocationType,
+    #[serde(flatten)]
+    pub capacity: Capacity
+}
+
+impl BinChanges {
+    pub fn for_bin(self, current: BinLocation, expected_version: i32) -> BinLocation {
+        BinLocation {
+            location_type: self.location_type,
+            max_units: self.capacity.max_units,
+            max_volume_cm3: self.capacity.max_volume_cm3,
+            max_weight_g: self.capacity.max_weight_g,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+/// Body of a `POST /aisle/<id>/bin` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BinDefinition {
+    pub code: String,
+    pub location_type: LocationType,
+    #[serde(flatten)]
+    pub capacity: Capacity
+}
+
+impl BinDefinition {
+    pub fn in_aisle(self, warehouse: &Warehouse, zone: &Zone, aisle: &Aisle) -> Result<NewBinLocation, String> {
+        validate_code("Bin", &self.code)?;
+        self.capacity.validate()?;
+        let code = self.code.trim().to_uppercase();
+        Ok(NewBinLocation {
+            warehouse_id: warehouse.id(),
+            zone_id: zone.id(),
+            aisle_id: aisle.id(),
+            location_code: location_code(&[warehouse.code(), zone.code(), aisle.code(), &code]),
+            code,
+            location_type: self.location_type,
+            max_units: self.capacity.max_units,
+            max_volume_cm3: self.capacity.max_volume_cm3,
+            max_weight_g: self.capacity.max_weight_g
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="bin_location"]
+pub struct NewBinLocation {
+    warehouse_id: i32,
+    zone_id: i32,
+    aisle_id: i32,
+    code: String,
+    location_code: String,
+    location_type: LocationType,
+    max_units: Option<i32>,
+    max_volume_cm3: Option<i64>,
+    max_weight_g: Option<i64>
+}
+
+impl NewBinLocation {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<BinLocation, diesel::result::Error> {
+        use crate::schema::bin_location::dsl::*;
+        use crate::schema::bin_location::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(bin_location)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Joins the codes of the warehouse, zone, aisle and bin into a code such as `WH1-A-03-02`.
+pub fn location_code(segments: &[&str]) -> String {
+    segments.iter()
+        .map(|segment| segment.trim().to_uppercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::location::warehouse::models::NewWarehouse;
+    use crate::location::zone::models::ZoneDefinition;
+    use crate::location::aisle::models::AisleDefinition;
+    use crate::location::bin::models::{BinDefinition, BinLocation, BinChanges, Capacity, LocationType, location_code};
+    use crate::testing::fixtures::warehouse_with_bins;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::product::models::NewProduct;
+
+    #[test]
+    fn location_code_joins_uppercased_segments() {
+        assert_eq!(location_code(&["wh1", "a", "03", "02"]), "WH1-A-03-02");
+    }
+
+    #[test]
+    fn location_types_round_trip_through_their_names() {
+        for location_type in LocationType::all() {
+            assert_eq!(LocationType::parse(location_type.as_str()), Some(*location_type));
+        }
+        assert_eq!(serde_json::to_string(&LocationType::PickFace).unwrap(), "\"pick_face\"");
+    }
+
+    #[test]
+    fn negative_capacity_is_rejected() {
+        assert!(Capacity { max_units: Some(0), ..Default::default() }.validate().is_err());
+        assert!(Capacity::default().validate().is_ok());
+    }
+
+    #[test]
+    fn bin_gets_hierarchical_location_code_and_can_be_found_by_it() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let warehouse = NewWarehouse::new("WH1", "main").create(&conn).unwrap();
+            let zone = ZoneDefinition { code: "A".to_string(), name: "ambient".to_string() }
+                .in_warehouse(&warehouse).unwrap()
+                .create(&conn).unwrap();
+            let aisle = AisleDefinition { code: "03".to_string(), sequence: 3 }
+                .in_zone(&zone).unwrap()
+                .create(&conn).unwrap();
+            let bin = BinDefinition { code: "02".to_string(), location_type: LocationType::PickFace, capacity: Capacity::default() }
+                .in_aisle(&warehouse, &zone, &aisle).unwrap()
+                .create(&conn).unwrap();
+
+            assert_eq!(bin.location_code(), "WH1-A-03-02");
+            assert_eq!(BinLocation::find_by_code("wh1-a-03-02", &conn).unwrap(), bin);
+            assert_eq!(BinLocation::of_type(warehouse.id(), LocationType::PickFace, &conn).unwrap().len(), 1);
+            assert_eq!(BinLocation::of_type(warehouse.id(), LocationType::Dock, &conn).unwrap().len(), 0);
+
+            let changes = BinChanges { location_type: LocationType::Reserve, capacity: Capacity { max_units: Some(10), ..Default::default() } };
+            let updated = changes.for_bin(bin, 0).update(&conn).unwrap().unwrap();
+            assert_eq!(updated.location_type(), LocationType::Reserve);
+            assert_eq!(updated.max_units(), Some(10));
+            assert_eq!(updated.version(), 1);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn room_is_limited_by_the_tightest_capacity() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (_, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let crate_of_soap = NewProduct::new("SOAP", "soap").with_dimensions(100, 100, 100, 500).create(&conn).unwrap();
+            assert_eq!(bins[0].room_for(&crate_of_soap, &conn).unwrap(), None);
+
+            let changes = BinChanges {
+                location_type: LocationType::PickFace,
+                capacity: Capacity { max_units: Some(20), max_volume_cm3: Some(12_000), max_weight_g: Some(5_000) }
+            };
+            let bin = changes.for_bin(bins[0].clone(), 0).update(&conn).unwrap().unwrap();
+            InventoryBalance::apply(crate_of_soap.id(), &bin, StockChange::on_hand(4), &conn).unwrap();
+            assert_eq!(bin.room_for(&crate_of_soap, &conn).unwrap(), Some(6));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,67 @@
+use crate::location::bin::models::{BinLocation, BinChanges, BinDefinition};
+use crate::location::warehouse::models::Warehouse;
+use crate::location::zone::models::Zone;
+use crate::location::aisle::models::Aisle;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/aisle/<aisle_id>/bin", format="application/json", data="<bin>")]
+pub fn post(aisle_id: i32, bin: Json<BinDefinition>, conn: PostgresConnection) -> Result<Tagged<Created<Json<BinLocation>>>, ApiError> {
+    let aisle = Aisle::find(aisle_id, &*conn)?;
+    let zone = Zone::find(aisle.zone_id(), &*conn)?;
+    let warehouse = Warehouse::find(zone.warehouse_id(), &*conn)?;
+    let created = bin.into_inner()
+        .in_aisle(&warehouse, &zone, &aisle)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/bin/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/zone/<zone_id>/bin")]
+pub fn list(zone_id: i32, conn: PostgresConnection) -> Result<Json<Vec<BinLocation>>, ApiError> {
+    let zone = Zone::find(zone_id, &*conn)?;
+    Ok(Json(BinLocation::for_zone(zone.id(), &*conn)?))
+}
+
+#[get("/bin?<code>")]
+pub fn find_by_code(code: String, conn: PostgresConnection) -> Result<Tagged<Json<BinLocation>>, ApiError> {
+    let bin = BinLocation::find_by_code(&code, &*conn)?;
+    Ok(Tagged(bin.version(), Json(bin)))
+}
+
+#[get("/bin/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<BinLocation>>, ApiError> {
+    let bin = BinLocation::find(id, &*conn)?;
+    Ok(Tagged(bin.version(), Json(bin)))
+}
+
+#[put("/bin/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<BinChanges>, conn: PostgresConnection) -> Result<Tagged<Json<BinLocation>>, ApiError> {
+    let expected_version = if_match?.version();
+    let changes = changes.into_inner();
+    changes.capacity.validate().map_err(ApiError::UnprocessableEntity)?;
+    let current = BinLocation::find(id, &*conn)?;
+    match changes.for_bin(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(BinLocation::find(id, &*conn), BinLocation::version))
+    }
+}
+
+#[delete("/bin/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let bin = BinLocation::find(id, &*conn)?;
+    if bin.version() != expected_version {
+        return Err(ApiError::precondition_failed(bin.version(), &bin));
+    }
+    match bin.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(BinLocation::find(id, &*conn), BinLocation::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod warehouse;
+pub mod zone;
+pub mod aisle;
+pub mod bin;
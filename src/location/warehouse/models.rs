@@ -0,0 +1,177 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::warehouse;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Integer, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="warehouse"]
+pub struct Warehouse {
+    id: i32,
+    code: String,
+    name: String,
+    version: i32
+}
+
+impl AsChangeset for Warehouse {
+    type Target = warehouse::table;
+    type Changeset = <(DieselEq<warehouse::name, Bound<Text, String>>,
+                       DieselEq<warehouse::version, DieselAdd<warehouse::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            warehouse::name.eq(self.name),
+            warehouse::version.eq(warehouse::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Warehouse {
+
+    pub fn find(warehouse_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Warehouse, diesel::result::Error> {
+        use crate::schema::warehouse::dsl::*;
+        warehouse.find(warehouse_id).first(conn)
+    }
+
+    pub fn all(conn: &impl Connection<Backend=Pg>) -> Result<Vec<Warehouse>, diesel::result::Error> {
+        use crate::schema::warehouse::dsl::*;
+        warehouse.order(code.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Warehouse>, diesel::result::Error> {
+        use crate::schema::warehouse::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(warehouse.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::warehouse::dsl::*;
+        conn.transaction(|| {
+            delete(warehouse.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `PUT /warehouse/<id>` request, the code is part of every location code in the
+/// warehouse and can therefore not be changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WarehouseChanges {
+    name: String
+}
+
+impl WarehouseChanges {
+    pub fn for_warehouse(self, current: Warehouse, expected_version: i32) -> Warehouse {
+        Warehouse {
+            name: self.name,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+#[derive(Debug, Insertable, Serialize, Deserialize)]
+#[table_name="warehouse"]
+pub struct NewWarehouse {
+    code: String,
+    name: String
+}
+
+impl NewWarehouse {
+    pub fn new(code: &str, name: &str) -> NewWarehouse {
+        NewWarehouse {
+            code: code.to_string(),
+            name: name.to_string()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        validate_code("Warehouse", &self.code)
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Warehouse, diesel::result::Error> {
+        use crate::schema::warehouse::dsl::*;
+        use crate::schema::warehouse::all_columns;
+        let normalized = NewWarehouse { code: self.code.trim().to_uppercase(), ..self };
+        conn.transaction(|| {
+            diesel::insert_into(warehouse)
+                .values(normalized)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Segments of a location code such as `WH1-A-03-02` are joined by dashes, so they may not
+/// contain one themselves.
+pub fn validate_code(kind: &str, code: &str) -> Result<(), String> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Err(format!("{} code must not be empty", kind));
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("{} code {} may only contain letters and digits", kind, code));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::location::warehouse::models::{NewWarehouse, Warehouse, WarehouseChanges, validate_code};
+
+    #[test]
+    fn warehouse_code_is_stored_uppercase() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let saved = NewWarehouse::new(" wh1 ", "Main warehouse").create(&conn).unwrap();
+            assert_eq!(saved.code, "WH1");
+            assert_eq!(Warehouse::all(&conn).unwrap(), vec![saved]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn optimistically_locked_warehouse_does_not_get_saved_on_conflict() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let saved = NewWarehouse::new("WH1", "Main").create(&conn).unwrap();
+            let stale = Warehouse::find(saved.id, &conn).unwrap();
+            let renamed = WarehouseChanges { name: "North".to_string() }.for_warehouse(saved, 0).update(&conn).unwrap();
+            let stale_renamed = WarehouseChanges { name: "South".to_string() }.for_warehouse(stale, 0).update(&conn).unwrap();
+
+            assert_eq!(renamed.map(|w| w.name), Some("North".to_string()));
+            assert_eq!(stale_renamed, None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn codes_with_separators_are_rejected() {
+        assert!(validate_code("Zone", "A-1").is_err());
+        assert!(validate_code("Zone", " ").is_err());
+        assert!(validate_code("Zone", "A1").is_ok());
+    }
+}
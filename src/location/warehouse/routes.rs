@@ -0,0 +1,51 @@
+use crate::location::warehouse::models::{Warehouse, WarehouseChanges, NewWarehouse};
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/warehouse", format="application/json", data="<warehouse>")]
+pub fn post(warehouse: Json<NewWarehouse>, conn: PostgresConnection) -> Result<Tagged<Created<Json<Warehouse>>>, ApiError> {
+    let warehouse = warehouse.into_inner();
+    warehouse.validate().map_err(ApiError::UnprocessableEntity)?;
+    let created = warehouse.create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/warehouse/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/warehouse")]
+pub fn list(conn: PostgresConnection) -> Result<Json<Vec<Warehouse>>, ApiError> {
+    Ok(Json(Warehouse::all(&*conn)?))
+}
+
+#[get("/warehouse/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<Warehouse>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Tagged(warehouse.version(), Json(warehouse)))
+}
+
+#[put("/warehouse/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<WarehouseChanges>, conn: PostgresConnection) -> Result<Tagged<Json<Warehouse>>, ApiError> {
+    let expected_version = if_match?.version();
+    let current = Warehouse::find(id, &*conn)?;
+    match changes.into_inner().for_warehouse(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(Warehouse::find(id, &*conn), Warehouse::version))
+    }
+}
+
+#[delete("/warehouse/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let warehouse = Warehouse::find(id, &*conn)?;
+    if warehouse.version() != expected_version {
+        return Err(ApiError::precondition_failed(warehouse.version(), &warehouse));
+    }
+    match warehouse.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(Warehouse::find(id, &*conn), Warehouse::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
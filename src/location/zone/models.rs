@@ -0,0 +1,179 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::warehouse_zone;
+use crate::location::warehouse::models::{Warehouse, validate_code};
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Integer, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="warehouse_zone"]
+pub struct Zone {
+    id: i32,
+    warehouse_id: i32,
+    code: String,
+    name: String,
+    version: i32
+}
+
+impl AsChangeset for Zone {
+    type Target = warehouse_zone::table;
+    type Changeset = <(DieselEq<warehouse_zone::name, Bound<Text, String>>,
+                       DieselEq<warehouse_zone::version, DieselAdd<warehouse_zone::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            warehouse_zone::name.eq(self.name),
+            warehouse_zone::version.eq(warehouse_zone::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Zone {
+
+    pub fn find(zone_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Zone, diesel::result::Error> {
+        use crate::schema::warehouse_zone::dsl::*;
+        warehouse_zone.find(zone_id).first(conn)
+    }
+
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Zone>, diesel::result::Error> {
+        use crate::schema::warehouse_zone::dsl::*;
+        warehouse_zone.filter(warehouse_id.eq(warehouse)).order(code.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Zone>, diesel::result::Error> {
+        use crate::schema::warehouse_zone::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(warehouse_zone.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::warehouse_zone::dsl::*;
+        conn.transaction(|| {
+            delete(warehouse_zone.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `PUT /zone/<id>` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZoneChanges {
+    name: String
+}
+
+impl ZoneChanges {
+    pub fn for_zone(self, current: Zone, expected_version: i32) -> Zone {
+        Zone {
+            name: self.name,
+            version: expected_version,
+            ..current
+        }
+    }
+}
+
+/// Body of a `POST /warehouse/<id>/zone` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZoneDefinition {
+    pub code: String,
+    pub name: String
+}
+
+impl ZoneDefinition {
+    pub fn in_warehouse(self, warehouse: &Warehouse) -> Result<NewZone, String> {
+        validate_code("Zone", &self.code)?;
+        Ok(NewZone {
+            warehouse_id: warehouse.id(),
+            code: self.code.trim().to_uppercase(),
+            name: self.name
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="warehouse_zone"]
+pub struct NewZone {
+    warehouse_id: i32,
+    code: String,
+    name: String
+}
+
+impl NewZone {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Zone, diesel::result::Error> {
+        use crate::schema::warehouse_zone::dsl::*;
+        use crate::schema::warehouse_zone::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(warehouse_zone)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::location::warehouse::models::NewWarehouse;
+    use crate::location::zone::models::{ZoneDefinition, Zone};
+
+    #[test]
+    fn zones_are_listed_per_warehouse() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let first = NewWarehouse::new("WH1", "first").create(&conn).unwrap();
+            let second = NewWarehouse::new("WH2", "second").create(&conn).unwrap();
+            let zone = ZoneDefinition { code: "a".to_string(), name: "ambient".to_string() }
+                .in_warehouse(&first).unwrap()
+                .create(&conn).unwrap();
+            ZoneDefinition { code: "A".to_string(), name: "ambient".to_string() }
+                .in_warehouse(&second).unwrap()
+                .create(&conn).unwrap();
+
+            assert_eq!(zone.code, "A");
+            assert_eq!(Zone::for_warehouse(first.id(), &conn).unwrap(), vec![zone]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn zone_codes_are_unique_within_a_warehouse() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let warehouse = NewWarehouse::new("WH1", "first").create(&conn).unwrap();
+            ZoneDefinition { code: "A".to_string(), name: "ambient".to_string() }
+                .in_warehouse(&warehouse).unwrap()
+                .create(&conn).unwrap();
+            let duplicate = ZoneDefinition { code: "a".to_string(), name: "again".to_string() }
+                .in_warehouse(&warehouse).unwrap()
+                .create(&conn);
+            assert!(duplicate.is_err());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use crate::location::zone::models::{Zone, ZoneChanges, ZoneDefinition};
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/warehouse/<warehouse_id>/zone", format="application/json", data="<zone>")]
+pub fn post(warehouse_id: i32, zone: Json<ZoneDefinition>, conn: PostgresConnection) -> Result<Tagged<Created<Json<Zone>>>, ApiError> {
+    let warehouse = Warehouse::find(warehouse_id, &*conn)?;
+    let created = zone.into_inner()
+        .in_warehouse(&warehouse)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/zone/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/warehouse/<warehouse_id>/zone")]
+pub fn list(warehouse_id: i32, conn: PostgresConnection) -> Result<Json<Vec<Zone>>, ApiError> {
+    let warehouse = Warehouse::find(warehouse_id, &*conn)?;
+    Ok(Json(Zone::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[get("/zone/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<Zone>>, ApiError> {
+    let zone = Zone::find(id, &*conn)?;
+    Ok(Tagged(zone.version(), Json(zone)))
+}
+
+#[put("/zone/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<ZoneChanges>, conn: PostgresConnection) -> Result<Tagged<Json<Zone>>, ApiError> {
+    let expected_version = if_match?.version();
+    let current = Zone::find(id, &*conn)?;
+    match changes.into_inner().for_zone(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(Zone::find(id, &*conn), Zone::version))
+    }
+}
+
+#[delete("/zone/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let zone = Zone::find(id, &*conn)?;
+    if zone.version() != expected_version {
+        return Err(ApiError::precondition_failed(zone.version(), &zone));
+    }
+    match zone.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(Zone::find(id, &*conn), Zone::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
@@ -0,0 +1,56 @@
+use rocket::http::Status;
+use rocket::request::{self, FromRequest, Request};
+use rocket::Outcome;
+use crate::error::ApiError;
+
+/// Name of the clerk performing a request, taken from the `X-User` header and recorded on
+/// every stock movement. Routes take it as `Result<Operator, ApiError>` so a missing header is
+/// answered with a JSON error body instead of Rocket's default page.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Operator(String);
+
+impl Operator {
+    pub fn new(name: &str) -> Operator {
+        Operator(name.trim().to_string())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Operator {
+    type Error = ApiError;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        match request.headers().get_one("X-User").map(str::trim) {
+            Some(name) if !name.is_empty() => Outcome::Success(Operator::new(name)),
+            _ => Outcome::Failure((Status::BadRequest, ApiError::BadRequest(String::from("The X-User header is required for this request"))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{with_migrated_database_information, with_rocket_configured};
+    use crate::error::ErrorBody;
+    use rocket::local::Client;
+    use rocket::http::{Header, Status};
+
+    #[test]
+    fn missing_or_blank_operator_is_answered_with_a_json_bad_request() -> Result<(), String> {
+        with_migrated_database_information(|_, db_url| {
+            with_rocket_configured(db_url, |rocket| {
+                let client = Client::new(rocket).expect("Valid rocket instance");
+                let missing = client.post("/replenishment-task/1/start").dispatch();
+                let blank = client.post("/replenishment-task/1/start").header(Header::new("X-User", "  ")).dispatch();
+                for mut response in vec![missing, blank] {
+                    assert_eq!(response.status(), Status::BadRequest);
+                    let body: ErrorBody = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+                    assert_eq!(body.message, "The X-User header is required for this request");
+                }
+                Ok(())
+            })
+        })
+    }
+}
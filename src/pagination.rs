@@ -0,0 +1,117 @@
+use serde::{Serialize, Deserialize};
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 500;
+
+/// Offset based window into a listing, clamped to sane bounds.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub offset: i64
+}
+
+impl PageRequest {
+    pub fn new(limit: Option<i64>, offset: Option<i64>) -> PageRequest {
+        PageRequest {
+            limit: limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT),
+            offset: offset.unwrap_or(0).max(0)
+        }
+    }
+}
+
+impl Default for PageRequest {
+    fn default() -> Self {
+        PageRequest::new(None, None)
+    }
+}
+
+/// One page of a listing together with the total number of matching rows and links to
+/// the neighbouring pages.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+    pub next: Option<String>,
+    pub previous: Option<String>
+}
+
+impl<T> Page<T> {
+    /// `link` renders the URI of the page starting at the given offset.
+    pub fn new(items: Vec<T>, total: i64, request: PageRequest, link: impl Fn(PageRequest) -> String) -> Page<T> {
+        let next_offset = request.offset.saturating_add(request.limit);
+        let next = if next_offset < total {
+            Some(link(PageRequest { offset: next_offset, ..request }))
+        } else {
+            None
+        };
+        let previous = if request.offset > 0 {
+            Some(link(PageRequest { offset: (request.offset - request.limit).max(0), ..request }))
+        } else {
+            None
+        };
+        Page {
+            items,
+            total,
+            limit: request.limit,
+            offset: request.offset,
+            next,
+            previous
+        }
+    }
+}
+
+/// Escapes `LIKE` wildcards so user input is matched literally.
+pub fn escape_like(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if c == '%' || c == '_' || c == '\\' {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pagination::{PageRequest, Page, escape_like, MAX_LIMIT};
+
+    fn link(request: PageRequest) -> String {
+        format!("/things?limit={}&offset={}", request.limit, request.offset)
+    }
+
+    #[test]
+    fn page_request_is_clamped() {
+        assert_eq!(PageRequest::new(Some(0), Some(-5)), PageRequest { limit: 1, offset: 0 });
+        assert_eq!(PageRequest::new(Some(100_000), None).limit, MAX_LIMIT);
+    }
+
+    #[test]
+    fn middle_page_links_both_neighbours() {
+        let page = Page::new(vec![1, 2], 10, PageRequest::new(Some(2), Some(4)), link);
+        assert_eq!(page.next, Some("/things?limit=2&offset=6".to_string()));
+        assert_eq!(page.previous, Some("/things?limit=2&offset=2".to_string()));
+    }
+
+    #[test]
+    fn first_and_last_pages_have_no_outer_links() {
+        let first = Page::new(vec![1, 2], 3, PageRequest::new(Some(2), None), link);
+        let last = Page::new(vec![3], 3, PageRequest::new(Some(2), Some(2)), link);
+        assert_eq!(first.previous, None);
+        assert_eq!(last.next, None);
+    }
+
+    #[test]
+    fn offsets_near_the_largest_value_have_no_next_page() {
+        let page = Page::new(Vec::<i32>::new(), 3, PageRequest::new(Some(10), Some(i64::MAX)), link);
+        assert_eq!(page.next, None);
+        assert_eq!(page.offset, i64::MAX);
+    }
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+}
@@ -0,0 +1,2 @@
+mod models;
+pub mod routes;
\ No newline at end of file
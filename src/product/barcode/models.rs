@@ -0,0 +1,215 @@
+use diesel::prelude::*;
+use std::fmt::{self, Debug, Display};
+use diesel::delete;
+use crate::schema::product_barcode;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// GS1 symbologies accepted for product barcodes, told apart by the number of digits.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum Symbology {
+    Ean8,
+    UpcA,
+    Ean13,
+    Gtin14
+}
+
+impl Symbology {
+    fn for_length(length: usize) -> Option<Symbology> {
+        match length {
+            8 => Some(Symbology::Ean8),
+            12 => Some(Symbology::UpcA),
+            13 => Some(Symbology::Ean13),
+            14 => Some(Symbology::Gtin14),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Symbology::Ean8 => "ean8",
+            Symbology::UpcA => "upc_a",
+            Symbology::Ean13 => "ean13",
+            Symbology::Gtin14 => "gtin14"
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum BarcodeError {
+    InvalidCharacters,
+    InvalidLength(usize),
+    InvalidCheckDigit { expected: u32, found: u32 }
+}
+
+impl Display for BarcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BarcodeError::InvalidCharacters => write!(f, "Barcodes may only contain digits"),
+            BarcodeError::InvalidLength(length) =>
+                write!(f, "Barcodes must have 8, 12, 13 or 14 digits, got {}", length),
+            BarcodeError::InvalidCheckDigit { expected, found } =>
+                write!(f, "Barcode check digit should be {} but is {}", expected, found)
+        }
+    }
+}
+
+/// A validated GS1 trade item number as scanned from a barcode.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Gtin {
+    code: String,
+    symbology: Symbology
+}
+
+impl Gtin {
+    pub fn parse(raw: &str) -> Result<Gtin, BarcodeError> {
+        let code = raw.trim();
+        if !code.chars().all(|c| c.is_ascii_digit()) {
+            return Err(BarcodeError::InvalidCharacters);
+        }
+        let symbology = Symbology::for_length(code.len())
+            .ok_or(BarcodeError::InvalidLength(code.len()))?;
+        let digits: Vec<u32> = code.chars().filter_map(|c| c.to_digit(10)).collect();
+        let (payload, check) = digits.split_at(digits.len() - 1);
+        let expected = check_digit(payload);
+        if expected != check[0] {
+            return Err(BarcodeError::InvalidCheckDigit { expected, found: check[0] });
+        }
+        Ok(Gtin { code: code.to_string(), symbology })
+    }
+
+    /// Zero padded 14 digit form under which equal items compare equal across symbologies,
+    /// e.g. UPC-A `036000291452` and EAN-13 `0036000291452`.
+    pub fn normalized(&self) -> String {
+        format!("{:0>14}", self.code)
+    }
+
+    pub fn symbology(&self) -> Symbology {
+        self.symbology
+    }
+}
+
+/// GS1 mod 10 check digit: payload digits weighted 3 and 1 alternating from the right.
+fn check_digit(payload: &[u32]) -> u32 {
+    let sum: u32 = payload.iter()
+        .rev()
+        .enumerate()
+        .map(|(position, digit)| if position % 2 == 0 { digit * 3 } else { *digit })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+#[derive(Debug, PartialEq, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product_barcode"]
+pub struct Barcode {
+    id: i32,
+    product_id: i32,
+    code: String,
+    gtin: String,
+    symbology: String
+}
+
+impl Barcode {
+
+    pub fn for_product(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Barcode>, diesel::result::Error> {
+        use crate::schema::product_barcode::dsl::*;
+        product_barcode.filter(product_id.eq(product)).order(id.asc()).load(conn)
+    }
+
+    pub fn find(scanned: &Gtin, conn: &impl Connection<Backend=Pg>) -> Result<Barcode, diesel::result::Error> {
+        use crate::schema::product_barcode::dsl::*;
+        product_barcode.filter(gtin.eq(scanned.normalized())).first(conn)
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::product_barcode::dsl::*;
+        conn.transaction(|| {
+            delete(product_barcode.filter(id.eq(self.id))).execute(conn)
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="product_barcode"]
+pub struct NewBarcode {
+    product_id: i32,
+    code: String,
+    gtin: String,
+    symbology: String
+}
+
+impl NewBarcode {
+    pub fn new(product_id: i32, scanned: &Gtin) -> NewBarcode {
+        NewBarcode {
+            product_id,
+            code: scanned.code.clone(),
+            gtin: scanned.normalized(),
+            symbology: scanned.symbology.as_str().to_string()
+        }
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Barcode, diesel::result::Error> {
+        use crate::schema::product_barcode::dsl::*;
+        use crate::schema::product_barcode::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(product_barcode)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Body of a `POST /product/<id>/barcode` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BarcodeRegistration {
+    pub code: String
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::product::barcode::models::{Gtin, BarcodeError, Symbology, NewBarcode, Barcode};
+    use crate::product::models::NewProduct;
+
+    #[test]
+    fn valid_barcodes_of_every_symbology_are_accepted() {
+        assert_eq!(Gtin::parse("96385074").map(|g| g.symbology()), Ok(Symbology::Ean8));
+        assert_eq!(Gtin::parse("036000291452").map(|g| g.symbology()), Ok(Symbology::UpcA));
+        assert_eq!(Gtin::parse("4006381333931").map(|g| g.symbology()), Ok(Symbology::Ean13));
+        assert_eq!(Gtin::parse("10012345678902").map(|g| g.symbology()), Ok(Symbology::Gtin14));
+    }
+
+    #[test]
+    fn barcodes_with_wrong_check_digit_length_or_characters_are_rejected() {
+        assert_eq!(Gtin::parse("4006381333932"), Err(BarcodeError::InvalidCheckDigit { expected: 1, found: 2 }));
+        assert_eq!(Gtin::parse("123456789"), Err(BarcodeError::InvalidLength(9)));
+        assert_eq!(Gtin::parse("40063813339A1"), Err(BarcodeError::InvalidCharacters));
+    }
+
+    #[test]
+    fn upc_and_ean_forms_of_same_item_normalize_equally() {
+        let upc = Gtin::parse("036000291452").unwrap();
+        let ean = Gtin::parse("0036000291452").unwrap();
+        assert_eq!(upc.normalized(), "00036000291452");
+        assert_eq!(upc.normalized(), ean.normalized());
+    }
+
+    #[test]
+    fn barcode_resolves_to_product_and_is_unique_across_symbologies() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let product = NewProduct::new("SKU-1", "cereal").create(&conn).unwrap();
+            NewBarcode::new(product.id(), &Gtin::parse("036000291452").unwrap()).create(&conn).unwrap();
+
+            let scanned = Gtin::parse("0036000291452").unwrap();
+            assert_eq!(Barcode::find(&scanned, &conn).unwrap().product_id(), product.id());
+            assert!(NewBarcode::new(product.id(), &scanned).create(&conn).is_err());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,44 @@
+use crate::product::barcode::models::{Barcode, BarcodeRegistration, Gtin, NewBarcode};
+use crate::product::models::{Product, ProductView};
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+#[post("/product/<id>/barcode", format="application/json", data="<registration>")]
+pub fn post(id: i32, registration: Json<BarcodeRegistration>, conn: PostgresConnection) -> Result<Created<Json<Barcode>>, ApiError> {
+    let scanned = Gtin::parse(&registration.code)
+        .map_err(|e| ApiError::UnprocessableEntity(e.to_string()))?;
+    let product = Product::find(id, &*conn)?;
+    let created = NewBarcode::new(product.id(), &scanned).create(&*conn)?;
+    Ok(Created(format!("/barcode/{}", scanned.normalized()), Some(Json(created))))
+}
+
+#[get("/product/<id>/barcode")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Barcode>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(Barcode::for_product(product.id(), &*conn)?))
+}
+
+#[delete("/product/<id>/barcode/<code>")]
+pub fn delete(id: i32, code: String, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let scanned = Gtin::parse(&code)
+        .map_err(|e| ApiError::UnprocessableEntity(e.to_string()))?;
+    let barcode = Barcode::find(&scanned, &*conn)?;
+    if barcode.product_id() != id {
+        return Err(ApiError::NotFound);
+    }
+    barcode.delete(&*conn)?;
+    Ok(Status::NoContent)
+}
+
+#[get("/barcode/<code>")]
+pub fn lookup(code: String, conn: PostgresConnection) -> Result<Json<ProductView>, ApiError> {
+    let scanned = Gtin::parse(&code)
+        .map_err(|e| ApiError::UnprocessableEntity(e.to_string()))?;
+    let barcode = Barcode::find(&scanned, &*conn)?;
+    Ok(Json(ProductView::load(Product::find(barcode.product_id(), &*conn)?, &*conn)?))
+}
@@ -1,2 +1,2 @@
-mod models;
+pub(crate) mod models;
 pub mod routes;
\ No newline at end of file
@@ -3,19 +3,92 @@ use std::fmt::{Debug};
 use diesel::{update, delete};
 use crate::schema::product_category;
 use diesel::query_builder::AsChangeset;
-use diesel::sql_types::{Integer, Text};
+use diesel::sql_types::{Integer, Text, BigInt};
 use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
 use serde::{Serialize, Deserialize};
 use diesel::pg::Pg;
+use crate::pagination::{PageRequest, escape_like};
 
-#[derive(Debug, PartialEq, Queryable, Identifiable, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Queryable, QueryableByName, Identifiable, Deserialize, Serialize)]
 #[table_name="product_category"]
 pub struct ProductCategory{
     id: i32,
     name: String,
-    version: i32
+    version: i32,
+    parent_id: Option<i32>
 }
 
+/// Result of re-parenting a category in the category tree.
+#[derive(Debug, PartialEq)]
+pub enum MoveOutcome {
+    Moved(ProductCategory),
+    Stale,
+    ParentNotFound,
+    WouldCreateCycle
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CategorySort {
+    IdAsc,
+    IdDesc,
+    NameAsc,
+    NameDesc
+}
+
+impl CategorySort {
+    /// Parses `id`, `name`, `-id` or `-name`, a leading `-` sorting descending.
+    pub fn parse(sort: &str) -> Option<CategorySort> {
+        match sort {
+            "id" => Some(CategorySort::IdAsc),
+            "-id" => Some(CategorySort::IdDesc),
+            "name" => Some(CategorySort::NameAsc),
+            "-name" => Some(CategorySort::NameDesc),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CategorySort::IdAsc => "id",
+            CategorySort::IdDesc => "-id",
+            CategorySort::NameAsc => "name",
+            CategorySort::NameDesc => "-name"
+        }
+    }
+}
+
+impl Default for CategorySort {
+    fn default() -> Self {
+        CategorySort::IdAsc
+    }
+}
+
+/// Case-insensitive restrictions on the category name used when listing categories.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct CategoryFilter {
+    pub prefix: Option<String>,
+    pub contains: Option<String>,
+    pub sort: CategorySort
+}
+
+impl CategoryFilter {
+    fn query(&self) -> product_category::BoxedQuery<'static, Pg> {
+        use crate::schema::product_category::dsl::*;
+        let mut query = product_category.into_boxed();
+        if let Some(ref starts_with) = self.prefix {
+            query = query.filter(name.ilike(format!("{}%", escape_like(starts_with))));
+        }
+        if let Some(ref substring) = self.contains {
+            query = query.filter(name.ilike(format!("%{}%", escape_like(substring))));
+        }
+        query
+    }
+}
+
+/// Key under which moves within the category tree are serialized, so that two concurrent
+/// moves can not together introduce a cycle which neither of them would create alone.
+const CATEGORY_TREE_LOCK: i64 = 0x7072_6f64_6361_74;
+
 
 impl AsChangeset for ProductCategory {
     type Target = product_category::table;
@@ -32,6 +105,70 @@ impl AsChangeset for ProductCategory {
 
 impl ProductCategory {
 
+    pub fn find(category_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<ProductCategory, diesel::result::Error> {
+        use crate::schema::product_category::dsl::*;
+        product_category.find(category_id).first(conn)
+    }
+
+    pub fn all(conn: &impl Connection<Backend=Pg>) -> Result<Vec<ProductCategory>, diesel::result::Error> {
+        use crate::schema::product_category::dsl::*;
+        product_category.order(id.asc()).load(conn)
+    }
+
+    /// Returns one page of categories matching the filter together with the number of all matches.
+    pub fn page(filter: &CategoryFilter, request: PageRequest, conn: &impl Connection<Backend=Pg>) -> Result<(Vec<ProductCategory>, i64), diesel::result::Error> {
+        use crate::schema::product_category::dsl::*;
+        let total = filter.query().count().get_result::<i64>(conn)?;
+        let ordered = match filter.sort {
+            CategorySort::IdAsc => filter.query().order(id.asc()),
+            CategorySort::IdDesc => filter.query().order(id.desc()),
+            CategorySort::NameAsc => filter.query().order((name.asc(), id.asc())),
+            CategorySort::NameDesc => filter.query().order((name.desc(), id.asc()))
+        };
+        let categories = ordered
+            .limit(request.limit)
+            .offset(request.offset)
+            .load(conn)?;
+        Ok((categories, total))
+    }
+
+    pub fn subtree(root_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ProductCategory>, diesel::result::Error> {
+        diesel::sql_query(
+            "with recursive subtree as ( \
+                select id, name, version, parent_id, 0 as depth from product_category where id = $1 \
+                union all \
+                select c.id, c.name, c.version, c.parent_id, s.depth + 1 \
+                from product_category c join subtree s on c.parent_id = s.id \
+            ) select id, name, version, parent_id from subtree order by depth, name")
+            .bind::<Integer, _>(root_id)
+            .load(conn)
+    }
+
+    /// Path from the root of the tree down to and including the given category.
+    pub fn ancestors(category_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ProductCategory>, diesel::result::Error> {
+        diesel::sql_query(
+            "with recursive ancestors as ( \
+                select id, name, version, parent_id, 0 as depth from product_category where id = $1 \
+                union all \
+                select p.id, p.name, p.version, p.parent_id, a.depth + 1 \
+                from product_category p join ancestors a on p.id = a.parent_id \
+            ) select id, name, version, parent_id from ancestors order by depth desc")
+            .bind::<Integer, _>(category_id)
+            .load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
     pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<ProductCategory>, diesel::result::Error> {
         use crate::schema::product_category::dsl::*;
         conn.transaction(|| {
@@ -47,6 +184,36 @@ impl ProductCategory {
         })
     }
 
+    /// Moves the category under a new parent, or to the root when `new_parent` is `None`.
+    pub fn move_to(self, new_parent: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<MoveOutcome, diesel::result::Error> {
+        use crate::schema::product_category::dsl::*;
+        conn.transaction(|| {
+            diesel::sql_query("select pg_advisory_xact_lock($1)")
+                .bind::<BigInt, _>(CATEGORY_TREE_LOCK)
+                .execute(conn)?;
+
+            if let Some(new_parent_id) = new_parent {
+                let path = ProductCategory::ancestors(new_parent_id, conn)?;
+                if path.is_empty() {
+                    return Ok(MoveOutcome::ParentNotFound);
+                }
+                if path.iter().any(|ancestor| ancestor.id == self.id) {
+                    return Ok(MoveOutcome::WouldCreateCycle);
+                }
+            }
+
+            let moved = update(product_category.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set((parent_id.eq(new_parent), version.eq(version + 1)))
+                .get_result(conn);
+
+            match moved {
+                Ok(e) => Ok(MoveOutcome::Moved(e)),
+                Err(diesel::result::Error::NotFound) => Ok(MoveOutcome::Stale),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
     pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
         use crate::schema::product_category::dsl::*;
         conn.transaction(|| {
@@ -63,10 +230,36 @@ impl ProductCategory {
     }
 }
 
+/// Body of a `PUT /product-category/<id>` request, the id is taken from the path and the
+/// expected version from the `If-Match` header.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductCategoryChanges {
+    name: String
+}
+
+impl ProductCategoryChanges {
+    pub fn for_category(self, category_id: i32, expected_version: i32) -> ProductCategory {
+        ProductCategory {
+            id: category_id,
+            name: self.name.to_lowercase(),
+            version: expected_version,
+            parent_id: None
+        }
+    }
+}
+
+/// Body of a `PUT /product-category/<id>/parent` request, `null` moves the category to the root.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ParentChange {
+    pub parent_id: Option<i32>
+}
+
 #[derive(Debug, Insertable, Serialize, Deserialize)]
 #[table_name="product_category"]
 pub struct NewProductCategory{
-    name: String
+    name: String,
+    #[serde(default)]
+    parent_id: Option<i32>
 }
 
 impl NewProductCategory {
@@ -77,7 +270,6 @@ impl NewProductCategory {
         conn.transaction(|| {
             diesel::insert_into(product_category)
                 .values(self)
-                .on_conflict_do_nothing()
                 .returning(all_columns)
                 .get_result(conn)
         })
@@ -87,7 +279,15 @@ impl NewProductCategory {
 impl NewProductCategory {
     pub fn new(name: &str) -> NewProductCategory {
         NewProductCategory {
-            name: name.to_lowercase()
+            name: name.to_lowercase(),
+            parent_id: None
+        }
+    }
+
+    pub fn child_of(name: &str, parent: i32) -> NewProductCategory {
+        NewProductCategory {
+            parent_id: Some(parent),
+            ..NewProductCategory::new(name)
         }
     }
 }
@@ -99,7 +299,8 @@ mod test {
     use diesel::{prelude::*, RunQueryDsl};
     use testcontainers::Image;
     use crate::testing::with_migrated_database_connection;
-    use crate::product::category::models::{NewProductCategory, ProductCategory};
+    use crate::product::category::models::{NewProductCategory, ProductCategory, ProductCategoryChanges, MoveOutcome, CategoryFilter, CategorySort};
+    use crate::pagination::PageRequest;
     use diesel::sql_types::HasSqlType;
     use diesel::query_builder::{QueryId, AsQuery, QueryFragment};
     use diesel::backend::Backend;
@@ -113,7 +314,7 @@ mod test {
     fn test_can_create_new_product_category_in_db() -> Result<(), String> {
         use crate::schema::product_category::dsl::*;
         with_migrated_database_connection(|conn| {
-            let test_category = NewProductCategory{name: "testing".to_string() };
+            let test_category = NewProductCategory{name: "testing".to_string(), parent_id: None };
             let saved_product_category = test_category.create(&conn).unwrap();
 
             let product_categories: Vec<ProductCategory> = product_category.load(&conn).unwrap();
@@ -125,11 +326,22 @@ mod test {
         })
     }
 
+    #[test]
+    fn creating_duplicate_product_category_reports_unique_violation() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            NewProductCategory::new("duplicate").create(&conn).unwrap();
+            match NewProductCategory::new("duplicate").create(&conn) {
+                Err(diesel::result::Error::DatabaseError(diesel::result::DatabaseErrorKind::UniqueViolation, _)) => Ok(()),
+                other => Err(format!("Expected unique violation, got {:?}", other))
+            }
+        })
+    }
+
     #[test]
     fn optimistically_locked_product_category_gets_saved_correctly_when_no_conflict() {
         use crate::schema::product_category::dsl::*;
         with_migrated_database_connection(|conn| {
-            let test_category = NewProductCategory{name:"testing".to_string()};
+            let test_category = NewProductCategory{name:"testing".to_string(), parent_id: None};
             let mut saved_product_category = test_category.create(&conn).unwrap();
             saved_product_category.name = "testing_updated".to_string();
             let updated_product_category = saved_product_category.update(&conn);
@@ -150,7 +362,7 @@ mod test {
         use crate::schema::product_category::dsl::*;
         with_migrated_database_connection(|conn| {
 
-            let first_cat = NewProductCategory{name:"first".to_string()};
+            let first_cat = NewProductCategory{name:"first".to_string(), parent_id: None};
             let mut first_cat = first_cat.create(&conn).unwrap();
             let mut first_cat_second = product_category.first::<ProductCategory>(&conn).unwrap();
             first_cat.name = "first_updated".to_string();
@@ -167,6 +379,135 @@ mod test {
         })
     }
 
+    #[test]
+    fn product_category_can_be_found_by_id() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let saved = NewProductCategory::new("first").create(&conn).unwrap();
+            let found = ProductCategory::find(saved.id, &conn).unwrap();
+            assert_eq!(saved, found);
+            assert_eq!(Err(diesel::result::Error::NotFound), ProductCategory::find(saved.id + 1, &conn));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn all_product_categories_are_listed_ordered_by_id() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let first = NewProductCategory::new("first").create(&conn).unwrap();
+            let second = NewProductCategory::new("second").create(&conn).unwrap();
+            assert_eq!(vec![first, second], ProductCategory::all(&conn).unwrap());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn categories_are_paged_filtered_and_sorted() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            for category_name in &["headphones", "headsets", "speakers", "subwoofers", "earphones"] {
+                NewProductCategory::new(category_name).create(&conn).unwrap();
+            }
+
+            let filter = CategoryFilter { contains: Some("PHONE".to_string()), sort: CategorySort::NameDesc, ..Default::default() };
+            let (first_page, total) = ProductCategory::page(&filter, PageRequest::new(Some(1), None), &conn).unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(first_page.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["headphones"]);
+
+            let (second_page, _) = ProductCategory::page(&filter, PageRequest::new(Some(1), Some(1)), &conn).unwrap();
+            assert_eq!(second_page.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["earphones"]);
+
+            let prefix = CategoryFilter { prefix: Some("Head".to_string()), sort: CategorySort::NameAsc, ..Default::default() };
+            let (heads, total) = ProductCategory::page(&prefix, PageRequest::default(), &conn).unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(heads.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["headphones", "headsets"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn category_sort_parses_known_fields_only() {
+        assert_eq!(CategorySort::parse("-name"), Some(CategorySort::NameDesc));
+        assert_eq!(CategorySort::parse("id"), Some(CategorySort::IdAsc));
+        assert_eq!(CategorySort::parse("version"), None);
+    }
+
+    #[test]
+    fn product_category_changes_take_id_and_version_from_request_and_lowercase_name() {
+        let changes = ProductCategoryChanges { name: "UpDaTeD".to_string() };
+        let category = changes.for_category(7, 3);
+        assert_eq!(category, ProductCategory { id: 7, name: "updated".to_string(), version: 3, parent_id: None });
+    }
+
+    #[test]
+    fn subtree_contains_category_and_all_descendants() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let electronics = NewProductCategory::new("electronics").create(&conn).unwrap();
+            let audio = NewProductCategory::child_of("audio", electronics.id).create(&conn).unwrap();
+            let headphones = NewProductCategory::child_of("headphones", audio.id).create(&conn).unwrap();
+            NewProductCategory::new("garden").create(&conn).unwrap();
+
+            let subtree = ProductCategory::subtree(audio.id, &conn).unwrap();
+            assert_eq!(subtree, vec![audio, headphones]);
+            assert_eq!(ProductCategory::subtree(electronics.id, &conn).unwrap().len(), 3);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn ancestors_are_listed_from_root_to_category() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let electronics = NewProductCategory::new("electronics").create(&conn).unwrap();
+            let audio = NewProductCategory::child_of("audio", electronics.id).create(&conn).unwrap();
+            let headphones = NewProductCategory::child_of("headphones", audio.id).create(&conn).unwrap();
+
+            let headphones_id = headphones.id;
+            assert_eq!(ProductCategory::ancestors(headphones_id, &conn).unwrap(), vec![electronics, audio, headphones]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn category_can_be_moved_under_new_parent_and_bumps_version() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let electronics = NewProductCategory::new("electronics").create(&conn).unwrap();
+            let audio = NewProductCategory::new("audio").create(&conn).unwrap();
+
+            match audio.move_to(Some(electronics.id), &conn).unwrap() {
+                MoveOutcome::Moved(moved) => {
+                    assert_eq!(moved.parent_id, Some(electronics.id));
+                    assert_eq!(moved.version, 1);
+                    Ok(())
+                },
+                other => Err(format!("Expected a move, got {:?}", other))
+            }
+        })
+    }
+
+    #[test]
+    fn category_can_not_be_moved_under_its_own_descendant() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let electronics = NewProductCategory::new("electronics").create(&conn).unwrap();
+            let audio = NewProductCategory::child_of("audio", electronics.id).create(&conn).unwrap();
+            let headphones = NewProductCategory::child_of("headphones", audio.id).create(&conn).unwrap();
+
+            assert_eq!(electronics.move_to(Some(headphones.id), &conn), Ok(MoveOutcome::WouldCreateCycle));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn moving_stale_category_does_not_change_tree() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let electronics = NewProductCategory::new("electronics").create(&conn).unwrap();
+            let mut audio = NewProductCategory::new("audio").create(&conn).unwrap();
+            let stale_audio = ProductCategory::find(audio.id, &conn).unwrap();
+            audio.name = "sound".to_string();
+            audio.update(&conn).unwrap();
+
+            assert_eq!(stale_audio.move_to(Some(electronics.id), &conn), Ok(MoveOutcome::Stale));
+            Ok(())
+        })
+    }
+
     #[test]
     fn new_product_category_saves_string_as_lowercase() {
         let new_product_category = NewProductCategory::new("FiRsTcAtEgOrY");
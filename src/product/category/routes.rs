@@ -1,16 +1,127 @@
-use crate::product::category::models::{ProductCategory};
+use crate::product::category::models::{ProductCategory, ProductCategoryChanges, ParentChange, MoveOutcome, CategoryFilter, CategorySort};
 use crate::product::category::models::NewProductCategory;
 
 use rocket_contrib::json::Json;
 use rocket::http::Status;
-use diesel::prelude::*;
+use rocket::response::status::Created;
+use rocket::request::LenientForm;
+use rocket::http::uri::Uri;
 use crate::configuration::PostgresConnection;
-use diesel::pg::Pg;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+use crate::pagination::{Page, PageRequest};
 
 
 #[post("/product-category", format="application/json", data="<category>")]
-pub fn post(category: Json<NewProductCategory>, conn: PostgresConnection) -> Json<ProductCategory> {
-    Json(category.into_inner().create(&*conn).unwrap())
+pub fn post(category: Json<NewProductCategory>, conn: PostgresConnection) -> Result<Tagged<Created<Json<ProductCategory>>>, ApiError> {
+    let created = category.into_inner().create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/product-category/{}", created.id()), Some(Json(created)))))
+}
+
+#[derive(Debug, FromForm)]
+pub struct ListQuery {
+    limit: Option<i64>,
+    offset: Option<i64>,
+    sort: Option<String>,
+    q: Option<String>,
+    prefix: Option<String>
+}
+
+impl ListQuery {
+    fn filter(&self) -> Result<CategoryFilter, ApiError> {
+        let sort = match self.sort {
+            Some(ref sort) => CategorySort::parse(sort)
+                .ok_or_else(|| ApiError::BadRequest(format!("Can not sort product categories by {}", sort)))?,
+            None => CategorySort::default()
+        };
+        Ok(CategoryFilter {
+            prefix: self.prefix.clone(),
+            contains: self.q.clone(),
+            sort
+        })
+    }
+
+    fn link(&self, request: PageRequest) -> String {
+        let mut link = format!("/product-category?limit={}&offset={}", request.limit, request.offset);
+        if let Some(ref sort) = self.sort {
+            link.push_str(&format!("&sort={}", Uri::percent_encode(sort)));
+        }
+        if let Some(ref q) = self.q {
+            link.push_str(&format!("&q={}", Uri::percent_encode(q)));
+        }
+        if let Some(ref prefix) = self.prefix {
+            link.push_str(&format!("&prefix={}", Uri::percent_encode(prefix)));
+        }
+        link
+    }
+}
+
+#[get("/product-category?<query..>")]
+pub fn list(query: LenientForm<ListQuery>, conn: PostgresConnection) -> Result<Json<Page<ProductCategory>>, ApiError> {
+    let filter = query.filter()?;
+    let request = PageRequest::new(query.limit, query.offset);
+    let (categories, total) = ProductCategory::page(&filter, request, &*conn)?;
+    Ok(Json(Page::new(categories, total, request, |page| query.link(page))))
+}
+
+#[get("/product-category/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<ProductCategory>>, ApiError> {
+    let category = ProductCategory::find(id, &*conn)?;
+    Ok(Tagged(category.version(), Json(category)))
+}
+
+#[put("/product-category/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<ProductCategoryChanges>, conn: PostgresConnection) -> Result<Tagged<Json<ProductCategory>>, ApiError> {
+    match changes.into_inner().for_category(id, if_match?.version()).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(ProductCategory::find(id, &*conn), ProductCategory::version))
+    }
+}
+
+#[get("/product-category/<id>/subtree")]
+pub fn subtree(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ProductCategory>>, ApiError> {
+    match ProductCategory::subtree(id, &*conn)? {
+        ref categories if categories.is_empty() => Err(ApiError::NotFound),
+        categories => Ok(Json(categories))
+    }
+}
+
+#[get("/product-category/<id>/ancestors")]
+pub fn ancestors(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ProductCategory>>, ApiError> {
+    match ProductCategory::ancestors(id, &*conn)? {
+        ref categories if categories.is_empty() => Err(ApiError::NotFound),
+        categories => Ok(Json(categories))
+    }
+}
+
+#[put("/product-category/<id>/parent", format="application/json", data="<change>")]
+pub fn move_to(id: i32, if_match: Result<IfMatch, ApiError>, change: Json<ParentChange>, conn: PostgresConnection) -> Result<Tagged<Json<ProductCategory>>, ApiError> {
+    let expected_version = if_match?.version();
+    let category = ProductCategory::find(id, &*conn)?;
+    if category.version() != expected_version {
+        return Err(ApiError::precondition_failed(category.version(), &category));
+    }
+    match category.move_to(change.parent_id, &*conn)? {
+        MoveOutcome::Moved(moved) => Ok(Tagged(moved.version(), Json(moved))),
+        MoveOutcome::Stale => Err(ApiError::precondition_failed_with(ProductCategory::find(id, &*conn), ProductCategory::version)),
+        MoveOutcome::ParentNotFound =>
+            Err(ApiError::UnprocessableEntity(format!("Parent category {} does not exist", change.parent_id.unwrap_or_default()))),
+        MoveOutcome::WouldCreateCycle =>
+            Err(ApiError::UnprocessableEntity(format!("Moving product category {} under {} would create a cycle", id, change.parent_id.unwrap_or_default())))
+    }
+}
+
+#[delete("/product-category/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let category = ProductCategory::find(id, &*conn)?;
+    if category.version() != expected_version {
+        return Err(ApiError::precondition_failed(category.version(), &category));
+    }
+    match category.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(ProductCategory::find(id, &*conn), ProductCategory::version)),
+        _ => Ok(Status::NoContent)
+    }
 }
 
 // #[cfg(test)]
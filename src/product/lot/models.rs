@@ -0,0 +1,205 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDate;
+use crate::schema::product_lot;
+use crate::product::models::Product;
+use diesel::sql_types::{Integer, BigInt, Text, Date, Nullable, Bool};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// A batch of a product made together, tracked through the warehouse by its lot number.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product_lot"]
+pub struct Lot {
+    id: i32,
+    product_id: i32,
+    lot_number: String,
+    manufactured_on: Option<NaiveDate>,
+    expires_on: Option<NaiveDate>
+}
+
+impl Lot {
+
+    pub fn find(lot_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Lot, diesel::result::Error> {
+        use crate::schema::product_lot::dsl::*;
+        product_lot.find(lot_id).first(conn)
+    }
+
+    /// Lots of a product, those expiring first listed first.
+    pub fn for_product(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Lot>, diesel::result::Error> {
+        use crate::schema::product_lot::dsl::*;
+        product_lot
+            .filter(product_id.eq(product))
+            .order((expires_on.is_null().asc(), expires_on.asc(), id.asc()))
+            .load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn lot_number(&self) -> &str {
+        &self.lot_number
+    }
+
+    pub fn expires_on(&self) -> Option<NaiveDate> {
+        self.expires_on
+    }
+
+    /// A lot is expired from the day after its expiry date on.
+    pub fn is_expired(&self, today: NaiveDate) -> bool {
+        self.expires_on.map_or(false, |expiry| expiry < today)
+    }
+}
+
+/// Body of a `POST /product/<id>/lot` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LotDefinition {
+    pub lot_number: String,
+    #[serde(default)]
+    pub manufactured_on: Option<NaiveDate>,
+    #[serde(default)]
+    pub expires_on: Option<NaiveDate>
+}
+
+impl LotDefinition {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.lot_number.trim().is_empty() {
+            return Err(String::from("Lot number must not be empty"));
+        }
+        match (self.manufactured_on, self.expires_on) {
+            (Some(manufactured), Some(expires)) if expires < manufactured =>
+                Err(format!("Lot {} can not expire before it is manufactured", self.lot_number.trim())),
+            _ => Ok(())
+        }
+    }
+
+    pub fn for_product(self, product: &Product) -> Result<NewLot, String> {
+        self.validate()?;
+        Ok(NewLot {
+            product_id: product.id(),
+            lot_number: self.lot_number.trim().to_uppercase(),
+            manufactured_on: self.manufactured_on,
+            expires_on: self.expires_on
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="product_lot"]
+pub struct NewLot {
+    product_id: i32,
+    lot_number: String,
+    manufactured_on: Option<NaiveDate>,
+    expires_on: Option<NaiveDate>
+}
+
+impl NewLot {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Lot, diesel::result::Error> {
+        use crate::schema::product_lot::dsl::*;
+        conn.transaction(|| {
+            diesel::insert_into(product_lot)
+                .values(self)
+                .get_result(conn)
+        })
+    }
+
+    /// The product's lot with this number, created on first use, e.g. when goods of a new lot arrive.
+    pub fn find_or_create(self, conn: &impl Connection<Backend=Pg>) -> Result<Lot, diesel::result::Error> {
+        use crate::schema::product_lot::dsl::*;
+        conn.transaction(|| {
+            diesel::insert_into(product_lot)
+                .values(&self)
+                .on_conflict((product_id, lot_number))
+                .do_nothing()
+                .execute(conn)?;
+            product_lot
+                .filter(product_id.eq(self.product_id).and(lot_number.eq(&self.lot_number)))
+                .first(conn)
+        })
+    }
+}
+
+/// A lot with stock left which expires within the reported period or already has.
+#[derive(Debug, PartialEq, QueryableByName, Serialize, Deserialize)]
+pub struct ExpiringLot {
+    #[sql_type="Integer"]
+    pub lot_id: i32,
+    #[sql_type="Integer"]
+    pub product_id: i32,
+    #[sql_type="Text"]
+    pub lot_number: String,
+    #[sql_type="Date"]
+    pub expires_on: NaiveDate,
+    #[sql_type="Bool"]
+    pub expired: bool,
+    #[sql_type="BigInt"]
+    pub on_hand: i64
+}
+
+impl ExpiringLot {
+    pub fn within(days: i32, warehouse: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ExpiringLot>, diesel::result::Error> {
+        diesel::sql_query(
+            "select l.id as lot_id, l.product_id, l.lot_number, l.expires_on, \
+                    l.expires_on < current_date as expired, sum(b.on_hand)::bigint as on_hand \
+             from product_lot l join inventory_balance b on b.lot_id = l.id \
+             where l.expires_on <= current_date + $1 and ($2::int is null or b.warehouse_id = $2) \
+             group by l.id having sum(b.on_hand) > 0 \
+             order by l.expires_on, l.id")
+            .bind::<Integer, _>(days)
+            .bind::<Nullable<Integer>, _>(warehouse)
+            .load(conn)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{Duration, Local, NaiveDate};
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::product::lot::models::{LotDefinition, Lot, ExpiringLot};
+
+    fn lot(lot_number: &str, expires_on: Option<NaiveDate>) -> LotDefinition {
+        LotDefinition { lot_number: lot_number.to_string(), manufactured_on: None, expires_on }
+    }
+
+    #[test]
+    fn lots_can_not_expire_before_manufacture() {
+        let definition = LotDefinition {
+            lot_number: "L1".to_string(),
+            manufactured_on: Some(NaiveDate::from_ymd(2021, 6, 1)),
+            expires_on: Some(NaiveDate::from_ymd(2021, 5, 1))
+        };
+        assert!(definition.validate().is_err());
+        assert!(lot(" ", None).validate().is_err());
+        assert!(lot("L1", None).validate().is_ok());
+    }
+
+    #[test]
+    fn lots_expiring_soon_are_reported_with_their_stock() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let milk = product(&conn, "MILK");
+            let today = Local::today().naive_local();
+            let expired = lot("L1", Some(today - Duration::days(2))).for_product(&milk).unwrap().create(&conn).unwrap();
+            let soon = lot("L2", Some(today + Duration::days(5))).for_product(&milk).unwrap().create(&conn).unwrap();
+            let later = lot("L3", Some(today + Duration::days(60))).for_product(&milk).unwrap().create(&conn).unwrap();
+            for lot in &[&expired, &soon, &later] {
+                InventoryBalance::apply_to_lot(milk.id(), Some(lot.id()), &bins[0], StockChange::on_hand(3), &conn).unwrap();
+            }
+
+            let expiring = ExpiringLot::within(30, Some(warehouse.id()), &conn).unwrap();
+            assert_eq!(expiring.iter().map(|l| (l.lot_id, l.expired, l.on_hand)).collect::<Vec<_>>(),
+                       vec![(expired.id(), true, 3), (soon.id(), false, 3)]);
+            assert!(expired.is_expired(today));
+            assert_eq!(Lot::for_product(milk.id(), &conn).unwrap().len(), 3);
+            Ok(())
+        })
+    }
+}
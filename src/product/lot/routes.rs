@@ -0,0 +1,41 @@
+use crate::product::lot::models::{Lot, LotDefinition, ExpiringLot};
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+/// Period `GET /lot/expiring` looks ahead if no number of days is given.
+const DEFAULT_EXPIRY_WINDOW_DAYS: i32 = 30;
+
+
+#[post("/product/<id>/lot", format="application/json", data="<definition>")]
+pub fn post(id: i32, definition: Json<LotDefinition>, conn: PostgresConnection) -> Result<Created<Json<Lot>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    let created = definition.into_inner()
+        .for_product(&product)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Created(format!("/lot/{}", created.id()), Some(Json(created))))
+}
+
+#[get("/product/<id>/lot")]
+pub fn for_product(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Lot>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(Lot::for_product(product.id(), &*conn)?))
+}
+
+#[get("/lot/<id>", rank = 2)]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<Lot>, ApiError> {
+    Ok(Json(Lot::find(id, &*conn)?))
+}
+
+#[get("/lot/expiring?<days>&<warehouse>")]
+pub fn expiring(days: Option<i32>, warehouse: Option<i32>, conn: PostgresConnection) -> Result<Json<Vec<ExpiringLot>>, ApiError> {
+    let days = days.unwrap_or(DEFAULT_EXPIRY_WINDOW_DAYS);
+    if days < 0 {
+        return Err(ApiError::BadRequest(format!("Can not report lots expiring within {} days", days)));
+    }
+    Ok(Json(ExpiringLot::within(days, warehouse, &*conn)?))
+}
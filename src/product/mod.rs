@@ -1 +1,7 @@
-pub mod category;
\ No newline at end of file
+pub mod category;
+pub mod barcode;
+pub mod variant;
+pub mod packaging;
+pub mod lot;
+pub(crate) mod models;
+pub mod routes;
\ No newline at end of file
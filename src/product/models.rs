@@ -0,0 +1,482 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::{product, product_category_assignment};
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Bool, Integer, Text, Nullable};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+use crate::pagination::{PageRequest, escape_like};
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product"]
+pub struct Product {
+    id: i32,
+    sku: String,
+    name: String,
+    description: String,
+    unit_of_measure: String,
+    length_mm: Option<i32>,
+    width_mm: Option<i32>,
+    height_mm: Option<i32>,
+    weight_g: Option<i32>,
+    version: i32,
+    parent_id: Option<i32>,
+    serialised: bool,
+    requires_inspection: bool
+}
+
+impl AsChangeset for Product {
+    type Target = product::table;
+    type Changeset = <(DieselEq<product::sku, Bound<Text, String>>,
+                       DieselEq<product::name, Bound<Text, String>>,
+                       DieselEq<product::description, Bound<Text, String>>,
+                       DieselEq<product::unit_of_measure, Bound<Text, String>>,
+                       DieselEq<product::length_mm, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<product::width_mm, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<product::height_mm, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<product::weight_g, Bound<Nullable<Integer>, Option<i32>>>,
+                       DieselEq<product::requires_inspection, Bound<Bool, bool>>,
+                       DieselEq<product::version, DieselAdd<product::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            product::sku.eq(self.sku),
+            product::name.eq(self.name),
+            product::description.eq(self.description),
+            product::unit_of_measure.eq(self.unit_of_measure),
+            product::length_mm.eq(self.length_mm),
+            product::width_mm.eq(self.width_mm),
+            product::height_mm.eq(self.height_mm),
+            product::weight_g.eq(self.weight_g),
+            product::requires_inspection.eq(self.requires_inspection),
+            product::version.eq(product::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Product {
+
+    pub fn find(product_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Product, diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        product.find(product_id).first(conn)
+    }
+
+    pub fn find_by_sku(product_sku: &str, conn: &impl Connection<Backend=Pg>) -> Result<Product, diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        product.filter(sku.eq(product_sku)).first(conn)
+    }
+
+    /// Returns one page of products whose SKU or name contains `search`, ordered by SKU.
+    pub fn page(search: Option<&str>, request: PageRequest, conn: &impl Connection<Backend=Pg>) -> Result<(Vec<Product>, i64), diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        let filtered = || {
+            let mut query = product.into_boxed();
+            if let Some(term) = search {
+                let pattern = format!("%{}%", escape_like(term));
+                query = query.filter(sku.ilike(pattern.clone()).or(name.ilike(pattern)));
+            }
+            query
+        };
+        let total = filtered().count().get_result::<i64>(conn)?;
+        let products = filtered()
+            .order(sku.asc())
+            .limit(request.limit)
+            .offset(request.offset)
+            .load(conn)?;
+        Ok((products, total))
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn sku(&self) -> &str {
+        &self.sku
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn unit_of_measure(&self) -> &str {
+        &self.unit_of_measure
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Volume of one base unit in whole cubic centimetres, if all dimensions are known.
+    pub fn volume_cm3(&self) -> Option<i64> {
+        match (self.length_mm, self.width_mm, self.height_mm) {
+            (Some(length), Some(width), Some(height)) => Some(i64::from(length) * i64::from(width) * i64::from(height) / 1000),
+            _ => None
+        }
+    }
+
+    pub fn weight_g(&self) -> Option<i32> {
+        self.weight_g
+    }
+
+    /// The product this one is a variant of, if any.
+    pub fn parent_id(&self) -> Option<i32> {
+        self.parent_id
+    }
+
+    pub fn is_variant(&self) -> bool {
+        self.parent_id.is_some()
+    }
+
+    /// Whether every unit of the product is tracked by its serial number.
+    pub fn is_serialised(&self) -> bool {
+        self.serialised
+    }
+
+    /// Received stock of the product is held for quality inspection before it can be used.
+    pub fn requires_inspection(&self) -> bool {
+        self.requires_inspection
+    }
+
+    /// Categories of the product, variants inherit the categories of their parent.
+    pub fn category_ids(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<i32>, diesel::result::Error> {
+        use crate::schema::product_category_assignment::dsl::*;
+        product_category_assignment
+            .filter(product_id.eq(self.parent_id.unwrap_or(self.id)))
+            .select(category_id)
+            .order(category_id.asc())
+            .load(conn)
+    }
+
+    /// Replaces the categories the product is assigned to. Returns `None` for a variant, which
+    /// inherits the categories of its parent and can not be assigned any of its own.
+    pub fn assign_categories(&self, categories: &[i32], conn: &impl Connection<Backend=Pg>) -> Result<Option<Vec<i32>>, diesel::result::Error> {
+        use crate::schema::product_category_assignment::dsl::*;
+        if self.is_variant() {
+            return Ok(None);
+        }
+        conn.transaction(|| {
+            delete(product_category_assignment.filter(product_id.eq(self.id))).execute(conn)?;
+            let assignments = CategoryAssignment::for_product(self.id, categories);
+            diesel::insert_into(product_category_assignment)
+                .values(&assignments)
+                .on_conflict_do_nothing()
+                .execute(conn)?;
+            self.category_ids(conn).map(Some)
+        })
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Product>, diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(product.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        conn.transaction(|| {
+            delete(product.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Queryable, Insertable)]
+#[table_name="product_category_assignment"]
+pub struct CategoryAssignment {
+    product_id: i32,
+    category_id: i32
+}
+
+impl CategoryAssignment {
+    fn for_product(product: i32, categories: &[i32]) -> Vec<CategoryAssignment> {
+        categories.iter()
+            .map(|&category| CategoryAssignment { product_id: product, category_id: category })
+            .collect()
+    }
+}
+
+/// A product as it is exposed over HTTP, together with the categories it belongs to.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProductView {
+    #[serde(flatten)]
+    pub product: Product,
+    pub category_ids: Vec<i32>
+}
+
+impl ProductView {
+    pub fn load(product: Product, conn: &impl Connection<Backend=Pg>) -> Result<ProductView, diesel::result::Error> {
+        let category_ids = product.category_ids(conn)?;
+        Ok(ProductView { product, category_ids })
+    }
+}
+
+/// Body of a `PUT /product/<id>` request, the id is taken from the path and the
+/// expected version from the `If-Match` header.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProductChanges {
+    sku: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "default_unit_of_measure")]
+    unit_of_measure: String,
+    #[serde(default)]
+    length_mm: Option<i32>,
+    #[serde(default)]
+    width_mm: Option<i32>,
+    #[serde(default)]
+    height_mm: Option<i32>,
+    #[serde(default)]
+    weight_g: Option<i32>,
+    #[serde(default)]
+    requires_inspection: bool,
+    #[serde(default)]
+    pub category_ids: Vec<i32>
+}
+
+impl ProductChanges {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_product(&self.sku, &self.name, &[self.length_mm, self.width_mm, self.height_mm, self.weight_g])
+    }
+
+    pub fn for_product(self, product_id: i32, expected_version: i32) -> Product {
+        Product {
+            id: product_id,
+            sku: self.sku.trim().to_string(),
+            name: self.name,
+            description: self.description,
+            unit_of_measure: self.unit_of_measure.to_lowercase(),
+            length_mm: self.length_mm,
+            width_mm: self.width_mm,
+            height_mm: self.height_mm,
+            weight_g: self.weight_g,
+            version: expected_version,
+            parent_id: None,
+            serialised: false,
+            requires_inspection: self.requires_inspection
+        }
+    }
+}
+
+#[derive(Debug, Insertable, Serialize, Deserialize)]
+#[table_name="product"]
+pub struct NewProduct {
+    sku: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "default_unit_of_measure")]
+    unit_of_measure: String,
+    #[serde(default)]
+    length_mm: Option<i32>,
+    #[serde(default)]
+    width_mm: Option<i32>,
+    #[serde(default)]
+    height_mm: Option<i32>,
+    #[serde(default)]
+    weight_g: Option<i32>,
+    #[serde(skip)]
+    parent_id: Option<i32>,
+    #[serde(default)]
+    serialised: bool,
+    #[serde(default)]
+    requires_inspection: bool
+}
+
+impl NewProduct {
+    pub fn new(sku: &str, name: &str) -> NewProduct {
+        NewProduct {
+            sku: sku.trim().to_string(),
+            name: name.to_string(),
+            description: String::new(),
+            unit_of_measure: default_unit_of_measure(),
+            length_mm: None,
+            width_mm: None,
+            height_mm: None,
+            weight_g: None,
+            parent_id: None,
+            serialised: false,
+            requires_inspection: false
+        }
+    }
+
+    /// A variant of `parent` sharing its description, unit of measure and measurements.
+    pub fn variant_of(parent: &Product, sku: &str, name: &str) -> NewProduct {
+        NewProduct {
+            sku: sku.trim().to_string(),
+            name: name.to_string(),
+            description: parent.description.clone(),
+            unit_of_measure: parent.unit_of_measure.clone(),
+            length_mm: parent.length_mm,
+            width_mm: parent.width_mm,
+            height_mm: parent.height_mm,
+            weight_g: parent.weight_g,
+            parent_id: Some(parent.id),
+            serialised: parent.serialised,
+            requires_inspection: parent.requires_inspection
+        }
+    }
+
+    pub fn with_dimensions(self, length_mm: i32, width_mm: i32, height_mm: i32, weight_g: i32) -> NewProduct {
+        NewProduct {
+            length_mm: Some(length_mm),
+            width_mm: Some(width_mm),
+            height_mm: Some(height_mm),
+            weight_g: Some(weight_g),
+            ..self
+        }
+    }
+
+    pub fn serialised(self) -> NewProduct {
+        NewProduct { serialised: true, ..self }
+    }
+
+    pub fn requiring_inspection(self) -> NewProduct {
+        NewProduct { requires_inspection: true, ..self }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        validate_product(&self.sku, &self.name, &[self.length_mm, self.width_mm, self.height_mm, self.weight_g])
+    }
+
+    /// Stores the product with its SKU trimmed and its unit of measure in lower case, the same
+    /// way changes to it are stored.
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Product, diesel::result::Error> {
+        use crate::schema::product::dsl::*;
+        use crate::schema::product::all_columns;
+        let normalized = NewProduct {
+            sku: self.sku.trim().to_string(),
+            unit_of_measure: self.unit_of_measure.to_lowercase(),
+            ..self
+        };
+        conn.transaction(|| {
+            diesel::insert_into(product)
+                .values(normalized)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Body of a `POST /product` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NewProductRequest {
+    #[serde(flatten)]
+    pub product: NewProduct,
+    #[serde(default)]
+    pub category_ids: Vec<i32>
+}
+
+fn default_unit_of_measure() -> String {
+    String::from("each")
+}
+
+fn validate_product(sku: &str, name: &str, measures: &[Option<i32>]) -> Result<(), String> {
+    if sku.trim().is_empty() {
+        return Err(String::from("Product SKU must not be empty"));
+    }
+    if name.trim().is_empty() {
+        return Err(String::from("Product name must not be empty"));
+    }
+    if measures.iter().any(|measure| measure.map_or(false, |m| m <= 0)) {
+        return Err(String::from("Product dimensions and weight must be positive"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::product::models::{NewProduct, Product, ProductView};
+    use crate::product::category::models::NewProductCategory;
+    use crate::pagination::PageRequest;
+
+    #[test]
+    fn product_can_be_created_with_categories() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let audio = NewProductCategory::new("audio").create(&conn).unwrap();
+            let sale = NewProductCategory::new("sale").create(&conn).unwrap();
+            let headphones = NewProduct::new(" HP-100 ", "Headphones")
+                .with_dimensions(200, 180, 90, 350)
+                .create(&conn)
+                .unwrap();
+            headphones.assign_categories(&[sale.id(), audio.id()], &conn).unwrap();
+
+            let view = ProductView::load(Product::find_by_sku("HP-100", &conn).unwrap(), &conn).unwrap();
+            assert_eq!(view.product.version, 0);
+            assert_eq!(view.product.unit_of_measure, "each");
+            assert_eq!(view.category_ids, vec![audio.id(), sale.id()]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn posted_products_are_normalized_like_changes() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let posted: NewProduct = serde_json::from_str(r#"{"sku": " BOX-1 ", "name": "Box", "unit_of_measure": "Each"}"#).unwrap();
+            let created = posted.create(&conn).unwrap();
+            assert_eq!((created.sku(), created.unit_of_measure()), ("BOX-1", "each"));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn optimistically_locked_product_does_not_get_saved_on_conflict() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let mut first = NewProduct::new("SKU-1", "first").create(&conn).unwrap();
+            let mut second = Product::find(first.id, &conn).unwrap();
+            first.name = "first updated".to_string();
+            second.name = "second updated".to_string();
+
+            let first_updated = first.update(&conn).unwrap();
+            let second_updated = second.update(&conn).unwrap();
+            assert_eq!(first_updated.map(|p| p.version), Some(1));
+            assert_eq!(second_updated, None);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn reassigning_categories_replaces_previous_assignment() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let audio = NewProductCategory::new("audio").create(&conn).unwrap();
+            let video = NewProductCategory::new("video").create(&conn).unwrap();
+            let product = NewProduct::new("SKU-1", "cable").create(&conn).unwrap();
+            product.assign_categories(&[audio.id()], &conn).unwrap();
+
+            assert_eq!(product.assign_categories(&[video.id()], &conn), Ok(Some(vec![video.id()])));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn products_are_searched_by_sku_or_name() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            NewProduct::new("HP-100", "Headphones").create(&conn).unwrap();
+            NewProduct::new("SP-200", "Speaker").create(&conn).unwrap();
+            NewProduct::new("CB-300", "Headphone cable").create(&conn).unwrap();
+
+            let (found, total) = Product::page(Some("headphone"), PageRequest::default(), &conn).unwrap();
+            assert_eq!(total, 2);
+            assert_eq!(found.iter().map(|p| p.sku.as_str()).collect::<Vec<_>>(), vec!["CB-300", "HP-100"]);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn products_with_non_positive_measures_are_rejected() {
+        assert!(NewProduct::new("SKU", "name").with_dimensions(1, 0, 1, 1).validate().is_err());
+        assert!(NewProduct::new("  ", "name").validate().is_err());
+        assert!(NewProduct::new("SKU", "name").validate().is_ok());
+    }
+}
@@ -0,0 +1,342 @@
+use diesel::prelude::*;
+use std::fmt::{self, Debug, Display};
+use std::convert::TryFrom;
+use diesel::delete;
+use crate::schema::product_packaging;
+use crate::product::models::Product;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product_packaging"]
+pub struct PackagingLevel {
+    id: i32,
+    product_id: i32,
+    name: String,
+    quantity: i32,
+    contained_packaging_id: Option<i32>
+}
+
+impl PackagingLevel {
+    pub fn for_product(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PackagingLevel>, diesel::result::Error> {
+        use crate::schema::product_packaging::dsl::*;
+        product_packaging.filter(product_id.eq(product)).order(id.asc()).load(conn)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::product_packaging::dsl::*;
+        conn.transaction(|| {
+            delete(product_packaging.filter(id.eq(self.id))).execute(conn)
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="product_packaging"]
+pub struct NewPackagingLevel {
+    product_id: i32,
+    name: String,
+    quantity: i32,
+    contained_packaging_id: Option<i32>
+}
+
+impl NewPackagingLevel {
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<PackagingLevel, diesel::result::Error> {
+        use crate::schema::product_packaging::dsl::*;
+        use crate::schema::product_packaging::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(product_packaging)
+                .values(self)
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// Body of a `POST /product/<id>/packaging` request, e.g. a `case` which contains 12 `each`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackagingDefinition {
+    pub name: String,
+    pub quantity: i32,
+    pub contains: String
+}
+
+impl PackagingDefinition {
+    /// Resolves the contained level against the product's packaging hierarchy.
+    pub fn for_product(self, product: &Product, hierarchy: &PackagingHierarchy) -> Result<NewPackagingLevel, ConversionError> {
+        let name = self.name.trim().to_lowercase();
+        if self.quantity <= 0 {
+            return Err(ConversionError::InvalidQuantity(self.quantity));
+        }
+        if hierarchy.factor(&name).is_ok() {
+            return Err(ConversionError::DuplicateUnit(name));
+        }
+        let contained = self.contains.trim().to_lowercase();
+        let contained_packaging_id = match hierarchy.level(&contained) {
+            Some(level) => Some(level.id),
+            None if hierarchy.is_base_unit(&contained) => None,
+            None => return Err(ConversionError::UnknownUnit(self.contains))
+        };
+        hierarchy.factor(&contained)?
+            .checked_mul(i64::from(self.quantity))
+            .ok_or(ConversionError::Overflow)?;
+        Ok(NewPackagingLevel {
+            product_id: product.id(),
+            name,
+            quantity: self.quantity,
+            contained_packaging_id
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConversionError {
+    UnknownUnit(String),
+    DuplicateUnit(String),
+    InvalidQuantity(i32),
+    NotWholeMultiple { unit: String, remainder: i64 },
+    Overflow
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::UnknownUnit(unit) => write!(f, "Unknown unit of measure {}", unit),
+            ConversionError::DuplicateUnit(unit) => write!(f, "Unit of measure {} is already defined", unit),
+            ConversionError::InvalidQuantity(quantity) => write!(f, "Packaging quantity must be positive, got {}", quantity),
+            ConversionError::NotWholeMultiple { unit, remainder } =>
+                write!(f, "Quantity is not a whole number of {}, {} base units remain", unit, remainder),
+            ConversionError::Overflow => write!(f, "Quantity is too large")
+        }
+    }
+}
+
+/// Packaging levels of a product with the number of base units each of them holds.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PackagingHierarchy {
+    base_unit: String,
+    levels: Vec<PackagingFactor>
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PackagingFactor {
+    #[serde(flatten)]
+    level: PackagingLevel,
+    /// Base units in one package, `None` if there are more than an `i64` holds.
+    base_quantity: Option<i64>
+}
+
+/// A part of a quantity broken down into packaging levels, e.g. `3 case`.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PackagedQuantity {
+    pub unit: String,
+    pub quantity: i64
+}
+
+impl PackagingHierarchy {
+    pub fn load(product: &Product, conn: &impl Connection<Backend=Pg>) -> Result<PackagingHierarchy, diesel::result::Error> {
+        let levels = PackagingLevel::for_product(product.id(), conn)?;
+        Ok(PackagingHierarchy::new(product.unit_of_measure(), levels))
+    }
+
+    pub fn new(base_unit: &str, levels: Vec<PackagingLevel>) -> PackagingHierarchy {
+        let factors = levels.iter()
+            .map(|level| PackagingFactor {
+                level: level.clone(),
+                base_quantity: base_quantity(level, &levels)
+            })
+            .collect();
+        PackagingHierarchy {
+            base_unit: base_unit.to_lowercase(),
+            levels: factors
+        }
+    }
+
+    pub fn is_base_unit(&self, unit: &str) -> bool {
+        self.base_unit == unit.trim().to_lowercase()
+    }
+
+    fn level(&self, unit: &str) -> Option<&PackagingLevel> {
+        self.levels.iter()
+            .map(|factor| &factor.level)
+            .find(|level| level.name == unit)
+    }
+
+    /// Number of base units in one `unit`.
+    pub fn factor(&self, unit: &str) -> Result<i64, ConversionError> {
+        let unit = unit.trim().to_lowercase();
+        if self.is_base_unit(&unit) {
+            return Ok(1);
+        }
+        self.levels.iter()
+            .find(|factor| factor.level.name == unit)
+            .ok_or(ConversionError::UnknownUnit(unit))?
+            .base_quantity
+            .ok_or(ConversionError::Overflow)
+    }
+
+    pub fn to_base(&self, quantity: i64, unit: &str) -> Result<i64, ConversionError> {
+        quantity.checked_mul(self.factor(unit)?).ok_or(ConversionError::Overflow)
+    }
+
+    /// Expresses base units in `unit`, failing unless they make up a whole number of it.
+    pub fn from_base(&self, base_quantity: i64, unit: &str) -> Result<i64, ConversionError> {
+        let factor = self.factor(unit)?;
+        match base_quantity % factor {
+            0 => Ok(base_quantity / factor),
+            remainder => Err(ConversionError::NotWholeMultiple { unit: unit.trim().to_lowercase(), remainder })
+        }
+    }
+
+    pub fn convert(&self, quantity: i64, from: &str, to: &str) -> Result<i64, ConversionError> {
+        self.from_base(self.to_base(quantity, from)?, to)
+    }
+
+    /// Number of whole `unit` packages needed to hold `base_quantity`, e.g. 13 each need 2 case.
+    pub fn round_up(&self, base_quantity: i64, unit: &str) -> Result<i64, ConversionError> {
+        let factor = self.factor(unit)?;
+        Ok(base_quantity.checked_add(factor - 1).ok_or(ConversionError::Overflow)?.div_euclid(factor))
+    }
+
+    /// Splits base units into the largest packaging levels first, e.g. 1 pallet, 3 case, 5 each.
+    pub fn breakdown(&self, base_quantity: i64) -> Vec<PackagedQuantity> {
+        let mut factors: Vec<(&str, i64)> = self.levels.iter()
+            .filter_map(|factor| factor.base_quantity.map(|quantity| (factor.level.name.as_str(), quantity)))
+            .collect();
+        factors.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let mut remaining = base_quantity;
+        let mut parts = Vec::new();
+        for (unit, factor) in factors {
+            if remaining >= factor {
+                parts.push(PackagedQuantity { unit: unit.to_string(), quantity: remaining / factor });
+                remaining %= factor;
+            }
+        }
+        if remaining > 0 || parts.is_empty() {
+            parts.push(PackagedQuantity { unit: self.base_unit.clone(), quantity: remaining });
+        }
+        parts
+    }
+}
+
+fn base_quantity(level: &PackagingLevel, levels: &[PackagingLevel]) -> Option<i64> {
+    let contained = match level.contained_packaging_id.and_then(|contained_id| levels.iter().find(|l| l.id == contained_id)) {
+        Some(contained) => base_quantity(contained, levels)?,
+        None => 1
+    };
+    i64::from(level.quantity).checked_mul(contained)
+}
+
+/// Quantity as accepted by the API, expressed in the product's base unit unless `unit` names
+/// one of its packaging levels.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Quantity {
+    pub amount: i32,
+    #[serde(default)]
+    pub unit: Option<String>
+}
+
+impl Quantity {
+    pub fn base(amount: i32) -> Quantity {
+        Quantity { amount, unit: None }
+    }
+
+    pub fn in_base_units(&self, hierarchy: &PackagingHierarchy) -> Result<i32, ConversionError> {
+        let base = match self.unit {
+            Some(ref unit) => hierarchy.to_base(i64::from(self.amount), unit)?,
+            None => i64::from(self.amount)
+        };
+        i32::try_from(base).map_err(|_| ConversionError::Overflow)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::product::packaging::models::{PackagingLevel, PackagingHierarchy, PackagingDefinition, PackagedQuantity, ConversionError, Quantity};
+    use crate::product::models::NewProduct;
+
+    fn warehouse_packaging() -> PackagingHierarchy {
+        PackagingHierarchy::new("each", vec![
+            PackagingLevel { id: 1, product_id: 1, name: "inner".to_string(), quantity: 6, contained_packaging_id: None },
+            PackagingLevel { id: 2, product_id: 1, name: "case".to_string(), quantity: 2, contained_packaging_id: Some(1) },
+            PackagingLevel { id: 3, product_id: 1, name: "pallet".to_string(), quantity: 40, contained_packaging_id: Some(2) }
+        ])
+    }
+
+    #[test]
+    fn factors_multiply_along_the_hierarchy() {
+        let hierarchy = warehouse_packaging();
+        assert_eq!(hierarchy.factor("each"), Ok(1));
+        assert_eq!(hierarchy.factor("Case"), Ok(12));
+        assert_eq!(hierarchy.factor("pallet"), Ok(480));
+        assert_eq!(hierarchy.factor("crate"), Err(ConversionError::UnknownUnit("crate".to_string())));
+    }
+
+    #[test]
+    fn conversions_are_exact() {
+        let hierarchy = warehouse_packaging();
+        assert_eq!(hierarchy.convert(2, "pallet", "case"), Ok(80));
+        assert_eq!(hierarchy.convert(3, "inner", "case"),
+                   Err(ConversionError::NotWholeMultiple { unit: "case".to_string(), remainder: 6 }));
+        assert_eq!(hierarchy.round_up(13, "case"), Ok(2));
+        assert_eq!(hierarchy.round_up(24, "case"), Ok(2));
+    }
+
+    #[test]
+    fn factors_and_roundings_beyond_i64_are_overflows() {
+        let huge = PackagingHierarchy::new("each", vec![
+            PackagingLevel { id: 1, product_id: 1, name: "inner".to_string(), quantity: i32::MAX, contained_packaging_id: None },
+            PackagingLevel { id: 2, product_id: 1, name: "case".to_string(), quantity: i32::MAX, contained_packaging_id: Some(1) },
+            PackagingLevel { id: 3, product_id: 1, name: "pallet".to_string(), quantity: i32::MAX, contained_packaging_id: Some(2) }
+        ]);
+        assert_eq!(huge.factor("case"), Ok(i64::from(i32::MAX) * i64::from(i32::MAX)));
+        assert_eq!(huge.factor("pallet"), Err(ConversionError::Overflow));
+        assert_eq!(huge.round_up(i64::MAX, "inner"), Err(ConversionError::Overflow));
+    }
+
+    #[test]
+    fn quantities_are_broken_down_largest_level_first() {
+        let hierarchy = warehouse_packaging();
+        let parts = hierarchy.breakdown(480 + 3 * 12 + 5);
+        assert_eq!(parts, vec![
+            PackagedQuantity { unit: "pallet".to_string(), quantity: 1 },
+            PackagedQuantity { unit: "case".to_string(), quantity: 3 },
+            PackagedQuantity { unit: "each".to_string(), quantity: 5 }
+        ]);
+    }
+
+    #[test]
+    fn api_quantities_default_to_base_unit() {
+        let hierarchy = warehouse_packaging();
+        assert_eq!(Quantity::base(7).in_base_units(&hierarchy), Ok(7));
+        assert_eq!(Quantity { amount: 2, unit: Some("case".to_string()) }.in_base_units(&hierarchy), Ok(24));
+        assert_eq!(Quantity { amount: i32::MAX, unit: Some("pallet".to_string()) }.in_base_units(&hierarchy),
+                   Err(ConversionError::Overflow));
+    }
+
+    #[test]
+    fn packaging_levels_are_defined_on_top_of_each_other() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let product = NewProduct::new("SKU-1", "soda").create(&conn).unwrap();
+            let case = PackagingDefinition { name: "Case".to_string(), quantity: 12, contains: "each".to_string() };
+            case.for_product(&product, &PackagingHierarchy::load(&product, &conn).unwrap()).unwrap()
+                .create(&conn).unwrap();
+            let pallet = PackagingDefinition { name: "pallet".to_string(), quantity: 40, contains: "case".to_string() };
+            pallet.for_product(&product, &PackagingHierarchy::load(&product, &conn).unwrap()).unwrap()
+                .create(&conn).unwrap();
+
+            let hierarchy = PackagingHierarchy::load(&product, &conn).unwrap();
+            assert_eq!(hierarchy.factor("pallet"), Ok(480));
+
+            let duplicate = PackagingDefinition { name: "case".to_string(), quantity: 6, contains: "each".to_string() };
+            assert_eq!(duplicate.for_product(&product, &hierarchy).err(), Some(ConversionError::DuplicateUnit("case".to_string())));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,56 @@
+use crate::product::packaging::models::{PackagingDefinition, PackagingHierarchy, PackagingLevel, PackagedQuantity};
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use serde::{Serialize, Deserialize};
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+#[post("/product/<id>/packaging", format="application/json", data="<definition>")]
+pub fn post(id: i32, definition: Json<PackagingDefinition>, conn: PostgresConnection) -> Result<Created<Json<PackagingHierarchy>>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    let hierarchy = PackagingHierarchy::load(&product, &*conn)?;
+    definition.into_inner().for_product(&product, &hierarchy)?.create(&*conn)?;
+    Ok(Created(format!("/product/{}/packaging", id), Some(Json(PackagingHierarchy::load(&product, &*conn)?))))
+}
+
+#[get("/product/<id>/packaging")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<PackagingHierarchy>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    Ok(Json(PackagingHierarchy::load(&product, &*conn)?))
+}
+
+#[delete("/product/<id>/packaging/<name>")]
+pub fn delete(id: i32, name: String, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let level = PackagingLevel::for_product(id, &*conn)?
+        .into_iter()
+        .find(|level| level.name() == name.to_lowercase())
+        .ok_or(ApiError::NotFound)?;
+    level.delete(&*conn)?;
+    Ok(Status::NoContent)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conversion {
+    quantity: i64,
+    unit: String,
+    base_quantity: i64,
+    breakdown: Vec<PackagedQuantity>
+}
+
+#[get("/product/<id>/packaging/convert?<quantity>&<from>&<to>")]
+pub fn convert(id: i32, quantity: i64, from: String, to: Option<String>, conn: PostgresConnection) -> Result<Json<Conversion>, ApiError> {
+    let product = Product::find(id, &*conn)?;
+    let hierarchy = PackagingHierarchy::load(&product, &*conn)?;
+    let base_quantity = hierarchy.to_base(quantity, &from)?;
+    let unit = to.unwrap_or_else(|| product.unit_of_measure().to_string());
+    Ok(Json(Conversion {
+        quantity: hierarchy.from_base(base_quantity, &unit)?,
+        unit,
+        base_quantity,
+        breakdown: hierarchy.breakdown(base_quantity)
+    }))
+}
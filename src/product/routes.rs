@@ -0,0 +1,88 @@
+use crate::product::models::{Product, ProductView, ProductChanges, NewProductRequest};
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::http::uri::Uri;
+use rocket::response::status::Created;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+use crate::pagination::{Page, PageRequest};
+use diesel::pg::Pg;
+
+const VARIANT_CATEGORIES: &str = "Product variants inherit the categories of their parent product";
+
+#[post("/product", format="application/json", data="<request>")]
+pub fn post(request: Json<NewProductRequest>, conn: PostgresConnection) -> Result<Tagged<Created<Json<ProductView>>>, ApiError> {
+    let NewProductRequest { product, category_ids } = request.into_inner();
+    product.validate().map_err(ApiError::UnprocessableEntity)?;
+    let created = conn.transaction::<_, ApiError, _>(|| {
+        let created = product.create(&*conn)?;
+        let category_ids = created.assign_categories(&category_ids, &*conn)?
+            .ok_or_else(|| ApiError::UnprocessableEntity(String::from(VARIANT_CATEGORIES)))?;
+        Ok(ProductView { product: created, category_ids })
+    })?;
+    let location = format!("/product/{}", created.product.id());
+    Ok(Tagged(created.product.version(), Created(location, Some(Json(created)))))
+}
+
+#[get("/product?<limit>&<offset>&<q>")]
+pub fn list(limit: Option<i64>, offset: Option<i64>, q: Option<String>, conn: PostgresConnection) -> Result<Json<Page<Product>>, ApiError> {
+    let request = PageRequest::new(limit, offset);
+    let (products, total) = Product::page(q.as_ref().map(String::as_str), request, &*conn)?;
+    Ok(Json(Page::new(products, total, request, |page| {
+        let mut link = format!("/product?limit={}&offset={}", page.limit, page.offset);
+        if let Some(ref q) = q {
+            link.push_str(&format!("&q={}", Uri::percent_encode(q)));
+        }
+        link
+    })))
+}
+
+#[get("/product/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<ProductView>>, ApiError> {
+    let view = ProductView::load(Product::find(id, &*conn)?, &*conn)?;
+    Ok(Tagged(view.product.version(), Json(view)))
+}
+
+#[put("/product/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<ProductChanges>, conn: PostgresConnection) -> Result<Tagged<Json<ProductView>>, ApiError> {
+    let expected_version = if_match?.version();
+    let changes = changes.into_inner();
+    changes.validate().map_err(ApiError::UnprocessableEntity)?;
+    let category_ids = changes.category_ids.clone();
+    if !category_ids.is_empty() && Product::find(id, &*conn)?.is_variant() {
+        return Err(ApiError::UnprocessableEntity(String::from(VARIANT_CATEGORIES)));
+    }
+    conn.transaction::<_, ApiError, _>(|| {
+        match changes.for_product(id, expected_version).update(&*conn)? {
+            Some(updated) => {
+                let category_ids = match updated.assign_categories(&category_ids, &*conn)? {
+                    Some(category_ids) => category_ids,
+                    None => updated.category_ids(&*conn)?
+                };
+                Ok(Tagged(updated.version(), Json(ProductView { product: updated, category_ids })))
+            },
+            None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.product.version()))
+        }
+    })
+}
+
+#[delete("/product/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let product = Product::find(id, &*conn)?;
+    if product.version() != expected_version {
+        return Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.product.version()));
+    }
+    match product.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.product.version())),
+        _ => Ok(Status::NoContent)
+    }
+}
+
+/// The product as it is now, to answer a lost optimistic lock with.
+fn reloaded(id: i32, conn: &impl Connection<Backend=Pg>) -> Result<ProductView, diesel::result::Error> {
+    Product::find(id, conn).and_then(|current| ProductView::load(current, conn))
+}
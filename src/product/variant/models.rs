@@ -0,0 +1,251 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use crate::schema::{product, product_variant_axis, product_variant_option};
+use crate::product::models::{Product, NewProduct};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="product_variant_axis"]
+pub struct VariantAxis {
+    id: i32,
+    product_id: i32,
+    name: String,
+    position: i32
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="product_variant_axis"]
+struct NewVariantAxis<'a> {
+    product_id: i32,
+    name: &'a str,
+    position: i32
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Insertable, Deserialize, Serialize)]
+#[table_name="product_variant_option"]
+pub struct VariantOption {
+    variant_id: i32,
+    axis_id: i32,
+    value: String
+}
+
+/// An option axis such as size or colour together with the values variants can take on it.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AxisDefinition {
+    pub name: String,
+    pub values: Vec<String>
+}
+
+impl AxisDefinition {
+    fn normalized(&self) -> AxisDefinition {
+        let mut values: Vec<String> = Vec::new();
+        for value in self.values.iter().map(|v| v.trim().to_string()) {
+            if !value.is_empty() && !values.contains(&value) {
+                values.push(value);
+            }
+        }
+        AxisDefinition {
+            name: self.name.trim().to_lowercase(),
+            values
+        }
+    }
+}
+
+/// Body of a `POST /product/<id>/variants` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VariantGeneration {
+    pub axes: Vec<AxisDefinition>
+}
+
+impl VariantGeneration {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.axes.is_empty() {
+            return Err(String::from("At least one option axis is required to generate variants"));
+        }
+        let mut names: Vec<String> = Vec::new();
+        for axis in self.axes.iter().map(AxisDefinition::normalized) {
+            if axis.name.is_empty() {
+                return Err(String::from("Option axes must be named"));
+            }
+            if axis.values.is_empty() {
+                return Err(format!("Option axis {} has no values", axis.name));
+            }
+            if names.contains(&axis.name) {
+                return Err(format!("Option axis {} is defined twice", axis.name));
+            }
+            names.push(axis.name);
+        }
+        Ok(())
+    }
+
+    /// Generates the axes on `parent` and one variant product for every combination of axis values.
+    pub fn generate(self, parent: &Product, conn: &impl Connection<Backend=Pg>) -> Result<Vec<VariantView>, diesel::result::Error> {
+        let axes: Vec<AxisDefinition> = self.axes.iter().map(AxisDefinition::normalized).collect();
+        conn.transaction(|| {
+            let new_axes: Vec<NewVariantAxis> = axes.iter()
+                .enumerate()
+                .map(|(position, axis)| NewVariantAxis { product_id: parent.id(), name: &axis.name, position: position as i32 })
+                .collect();
+            let saved_axes: Vec<VariantAxis> = diesel::insert_into(product_variant_axis::table)
+                .values(&new_axes)
+                .get_results(conn)?;
+
+            let axis_values: Vec<(i32, Vec<String>)> = saved_axes.iter()
+                .zip(axes.iter())
+                .map(|(saved, definition)| (saved.id, definition.values.clone()))
+                .collect();
+
+            combinations(&axis_values).into_iter()
+                .map(|combination| -> Result<VariantView, diesel::result::Error> {
+                    let values: Vec<&str> = combination.iter().map(|(_, value)| value.as_str()).collect();
+                    let variant = NewProduct::variant_of(parent, &variant_sku(parent.sku(), &values),
+                                                         &format!("{} ({})", parent.name(), values.join(", ")))
+                        .create(conn)?;
+                    let options: Vec<VariantOption> = combination.into_iter()
+                        .map(|(axis_id, value)| VariantOption { variant_id: variant.id(), axis_id, value })
+                        .collect();
+                    diesel::insert_into(product_variant_option::table)
+                        .values(&options)
+                        .execute(conn)?;
+                    Ok(VariantView { product: variant, options })
+                })
+                .collect()
+        })
+    }
+}
+
+/// A variant product together with the option values that tell it apart from its siblings.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct VariantView {
+    pub product: Product,
+    pub options: Vec<VariantOption>
+}
+
+impl VariantView {
+    pub fn for_parent(parent: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<VariantView>, diesel::result::Error> {
+        let variants: Vec<Product> = product::table
+            .filter(product::parent_id.eq(parent))
+            .order(product::id.asc())
+            .load(conn)?;
+        let variant_ids: Vec<i32> = variants.iter().map(Product::id).collect();
+        let options: Vec<VariantOption> = product_variant_option::table
+            .inner_join(product_variant_axis::table)
+            .filter(product_variant_option::variant_id.eq_any(variant_ids))
+            .order(product_variant_axis::position.asc())
+            .select(product_variant_option::all_columns)
+            .load(conn)?;
+
+        Ok(variants.into_iter()
+            .map(|variant| {
+                let options = options.iter()
+                    .filter(|option| option.variant_id == variant.id())
+                    .cloned()
+                    .collect();
+                VariantView { product: variant, options }
+            })
+            .collect())
+    }
+}
+
+/// All option axes of a product and the variants generated along them.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct VariantListing {
+    pub axes: Vec<VariantAxis>,
+    pub variants: Vec<VariantView>
+}
+
+impl VariantListing {
+    pub fn load(parent: i32, conn: &impl Connection<Backend=Pg>) -> Result<VariantListing, diesel::result::Error> {
+        Ok(VariantListing {
+            axes: VariantAxis::for_product(parent, conn)?,
+            variants: VariantView::for_parent(parent, conn)?
+        })
+    }
+}
+
+impl VariantAxis {
+    pub fn for_product(product: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<VariantAxis>, diesel::result::Error> {
+        use crate::schema::product_variant_axis::dsl::*;
+        product_variant_axis.filter(product_id.eq(product)).order(position.asc()).load(conn)
+    }
+}
+
+/// Cartesian product of the axis values, the first axis varying slowest.
+fn combinations(axes: &[(i32, Vec<String>)]) -> Vec<Vec<(i32, String)>> {
+    axes.iter().fold(vec![Vec::new()], |partial, (axis_id, values)| {
+        partial.iter()
+            .flat_map(|prefix| values.iter().map(move |value| {
+                let mut combination = prefix.clone();
+                combination.push((*axis_id, value.clone()));
+                combination
+            }))
+            .collect()
+    })
+}
+
+fn variant_sku(parent_sku: &str, values: &[&str]) -> String {
+    let suffix: Vec<String> = values.iter()
+        .map(|value| value.split_whitespace().collect::<Vec<_>>().join("_").to_uppercase())
+        .collect();
+    format!("{}-{}", parent_sku, suffix.join("-"))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::product::variant::models::{combinations, variant_sku, AxisDefinition, VariantGeneration, VariantView};
+    use crate::product::models::{NewProduct, Product};
+    use crate::product::category::models::NewProductCategory;
+
+    fn axis(name: &str, values: &[&str]) -> AxisDefinition {
+        AxisDefinition { name: name.to_string(), values: values.iter().map(|v| v.to_string()).collect() }
+    }
+
+    #[test]
+    fn combinations_are_the_cartesian_product_of_axis_values() {
+        let axes = vec![(1, vec!["S".to_string(), "M".to_string()]),
+                        (2, vec!["red".to_string(), "blue".to_string(), "green".to_string()])];
+        let generated = combinations(&axes);
+        assert_eq!(generated.len(), 6);
+        assert_eq!(generated[0], vec![(1, "S".to_string()), (2, "red".to_string())]);
+        assert_eq!(generated[5], vec![(1, "M".to_string()), (2, "green".to_string())]);
+    }
+
+    #[test]
+    fn variant_sku_appends_uppercased_option_values() {
+        assert_eq!(variant_sku("TSHIRT", &["xl", "navy blue"]), "TSHIRT-XL-NAVY_BLUE");
+    }
+
+    #[test]
+    fn axes_without_values_or_defined_twice_are_rejected() {
+        let empty = VariantGeneration { axes: vec![axis("size", &[" "])] };
+        let twice = VariantGeneration { axes: vec![axis("size", &["S"]), axis("Size", &["M"])] };
+        assert!(empty.validate().is_err());
+        assert!(twice.validate().is_err());
+    }
+
+    #[test]
+    fn generated_variants_inherit_parent_categories() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let apparel = NewProductCategory::new("apparel").create(&conn).unwrap();
+            let parent = NewProduct::new("TSHIRT", "T-Shirt").create(&conn).unwrap();
+            parent.assign_categories(&[apparel.id()], &conn).unwrap();
+
+            let generation = VariantGeneration { axes: vec![axis("size", &["S", "M"]), axis("colour", &["red", "blue"])] };
+            let generated = generation.generate(&parent, &conn).unwrap();
+            assert_eq!(generated.len(), 4);
+
+            let variants = VariantView::for_parent(parent.id(), &conn).unwrap();
+            assert_eq!(variants, generated);
+            let small_red = Product::find_by_sku("TSHIRT-S-RED", &conn).unwrap();
+            assert_eq!(small_red.parent_id(), Some(parent.id()));
+            assert_eq!(small_red.category_ids(&conn).unwrap(), vec![apparel.id()]);
+
+            let sale = NewProductCategory::new("sale").create(&conn).unwrap();
+            assert_eq!(small_red.assign_categories(&[sale.id()], &conn).unwrap(), None);
+            assert_eq!(parent.category_ids(&conn).unwrap(), vec![apparel.id()]);
+            Ok(())
+        })
+    }
+}
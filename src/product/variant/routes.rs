@@ -0,0 +1,25 @@
+use crate::product::variant::models::{VariantGeneration, VariantListing, VariantView};
+use crate::product::models::Product;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+#[post("/product/<id>/variants", format="application/json", data="<generation>")]
+pub fn post(id: i32, generation: Json<VariantGeneration>, conn: PostgresConnection) -> Result<Created<Json<Vec<VariantView>>>, ApiError> {
+    generation.validate().map_err(ApiError::UnprocessableEntity)?;
+    let parent = Product::find(id, &*conn)?;
+    if parent.is_variant() {
+        return Err(ApiError::UnprocessableEntity(format!("Product {} is itself a variant", id)));
+    }
+    let variants = generation.into_inner().generate(&parent, &*conn)?;
+    Ok(Created(format!("/product/{}/variants", id), Some(Json(variants))))
+}
+
+#[get("/product/<id>/variants")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<VariantListing>, ApiError> {
+    let parent = Product::find(id, &*conn)?;
+    Ok(Json(VariantListing::load(parent.id(), &*conn)?))
+}
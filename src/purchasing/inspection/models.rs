@@ -0,0 +1,277 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use diesel::update;
+use crate::schema::{goods_receipt_line, inspection};
+use crate::inventory::balance::models::StockError;
+use crate::inventory::movement::models::{NewStockMovement, MovementReason};
+use crate::location::bin::models::{BinLocation, LocationType};
+use crate::product::models::Product;
+use crate::purchasing::receiving::models::GoodsReceiptLine;
+use crate::operator::Operator;
+use diesel::dsl::now;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    pub enum InspectionStatus {
+        Pending => "pending",
+        Released => "released",
+        Rejected => "rejected"
+    }
+}
+
+/// Received stock held in quarantine until an inspector decides on it. While pending it stays in
+/// the dock or staging bin it was received into, which is not pickable, and can not be put away.
+/// Rejected units are moved to a returns bin, which is not pickable either, until they are sent
+/// back to the supplier or written off.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="inspection"]
+pub struct Inspection {
+    id: i32,
+    goods_receipt_line_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    bin_location_id: i32,
+    quantity: i32,
+    status: InspectionStatus,
+    rejected_quantity: i32,
+    inspected_by: Option<String>,
+    inspected_at: Option<NaiveDateTime>,
+    notes: Option<String>,
+    created_at: NaiveDateTime
+}
+
+impl Inspection {
+
+    pub fn find(inspection_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Inspection, diesel::result::Error> {
+        use crate::schema::inspection::dsl::*;
+        inspection.find(inspection_id).first(conn)
+    }
+
+    /// Inspections of the warehouse still waiting for a decision, oldest first.
+    pub fn pending(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Inspection>, diesel::result::Error> {
+        use crate::schema::bin_location;
+        inspection::table
+            .inner_join(bin_location::table)
+            .filter(bin_location::warehouse_id.eq(warehouse).and(inspection::status.eq(InspectionStatus::Pending)))
+            .select(inspection::all_columns)
+            .order(inspection::id.asc())
+            .load(conn)
+    }
+
+    /// Whether units of the receipt line are still waiting for inspection.
+    pub fn is_holding(line: &GoodsReceiptLine, conn: &impl Connection<Backend=Pg>) -> Result<bool, diesel::result::Error> {
+        use crate::schema::inspection::dsl::*;
+        diesel::select(diesel::dsl::exists(
+            inspection.filter(goods_receipt_line_id.eq(line.id()).and(status.eq(InspectionStatus::Pending)))))
+            .get_result(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn bin_location_id(&self) -> i32 {
+        self.bin_location_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn status(&self) -> InspectionStatus {
+        self.status
+    }
+
+    /// Releases all held units for putaway. Returns `None` if the inspection was already decided.
+    pub fn release(self, notes: Option<String>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<Inspection>, StockError> {
+        Ok(self.decide(InspectionStatus::Released, 0, notes, operator, conn)?)
+    }
+
+    /// Rejects `quantity` of the held units and moves them to the returns bin `returns`, the
+    /// rest is released. Stock in returns bins is never allocated or reported as available, so
+    /// the rejected units can be shipped back or written off there like any other stock. Returns
+    /// `None` if the inspection was already decided.
+    pub fn reject(self, quantity: i32, returns: &BinLocation, serial_numbers: Vec<String>, notes: Option<String>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<Inspection>, StockError> {
+        if quantity <= 0 || quantity > self.quantity {
+            return Err(StockError::InvalidMovement(format!("Can reject between 1 and {} units, got {}", self.quantity, quantity)));
+        }
+        if returns.location_type() != LocationType::Returns {
+            return Err(StockError::InvalidMovement(format!("Rejected stock can only be moved to returns bins, {} is {}", returns.location_code(), returns.location_type())));
+        }
+        conn.transaction(|| {
+            let rejected = match self.decide(InspectionStatus::Rejected, quantity, notes, operator, conn)? {
+                Some(rejected) => rejected,
+                None => return Ok(None)
+            };
+            NewStockMovement::new(self.product_id, Some(self.bin_location_id), Some(returns.id()), quantity, MovementReason::Transfer, operator)
+                .in_lot(self.lot_id)
+                .with_serials(serial_numbers)
+                .with_reference(&format!("INSPECTION-{}", self.id))
+                .post(conn)?;
+            update(goods_receipt_line::table.find(self.goods_receipt_line_id))
+                .set(goods_receipt_line::rejected_quantity.eq(goods_receipt_line::rejected_quantity + quantity))
+                .execute(conn)?;
+            Ok(Some(rejected))
+        })
+    }
+
+    fn decide(&self, decision: InspectionStatus, rejected: i32, decision_notes: Option<String>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Option<Inspection>, diesel::result::Error> {
+        use crate::schema::inspection::dsl::*;
+        update(inspection.filter(id.eq(self.id).and(status.eq(InspectionStatus::Pending))))
+            .set((
+                status.eq(decision),
+                rejected_quantity.eq(rejected),
+                inspected_by.eq(Some(operator.name())),
+                inspected_at.eq(now.nullable()),
+                notes.eq(decision_notes)
+            ))
+            .get_result(conn)
+            .optional()
+    }
+}
+
+/// Body of a `POST /inspection/<id>/release` request.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InspectionRelease {
+    #[serde(default)]
+    pub notes: Option<String>
+}
+
+/// Body of a `POST /inspection/<id>/reject` request. Without a quantity all held units are
+/// rejected, without a bin they go to the first returns bin of the warehouse.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InspectionRejection {
+    #[serde(default)]
+    pub quantity: Option<i32>,
+    #[serde(default)]
+    pub return_location_id: Option<i32>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>,
+    #[serde(default)]
+    pub notes: Option<String>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="inspection"]
+pub struct NewInspection {
+    goods_receipt_line_id: i32,
+    product_id: i32,
+    lot_id: Option<i32>,
+    bin_location_id: i32,
+    quantity: i32
+}
+
+impl NewInspection {
+    /// Inspection of everything received on `line` into `bin`.
+    pub fn for_line(line: &GoodsReceiptLine, product: &Product, bin: &BinLocation) -> NewInspection {
+        NewInspection {
+            goods_receipt_line_id: line.id(),
+            product_id: product.id(),
+            lot_id: line.lot_id(),
+            bin_location_id: bin.id(),
+            quantity: line.quantity()
+        }
+    }
+
+    /// Quarantines the units in the bin they were received into until the inspection is decided.
+    pub fn hold(self, conn: &impl Connection<Backend=Pg>) -> Result<Inspection, StockError> {
+        Ok(diesel::insert_into(inspection::table)
+            .values(self)
+            .get_result(conn)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::warehouse_with_bins;
+    use crate::location::bin::models::{BinLocation, LocationType};
+    use crate::location::warehouse::models::Warehouse;
+    use crate::inventory::balance::models::{InventoryBalance, ProductStock, StockError, StockLevel};
+    use crate::inventory::movement::models::{MovementReason, NewStockMovement, StockMovement};
+    use crate::inventory::putaway::models::PutawayConfirmation;
+    use crate::product::models::{NewProduct, Product};
+    use crate::purchasing::supplier::models::SupplierDetails;
+    use crate::purchasing::order::models::{NewPurchaseOrder, PurchaseOrderStatus};
+    use crate::purchasing::receiving::models::{GoodsReceipt, GoodsReceiptView, ReceivedLine};
+    use crate::purchasing::inspection::models::{Inspection, InspectionStatus};
+    use crate::operator::Operator;
+    use diesel::PgConnection;
+
+    fn received_for_inspection(conn: &PgConnection, warehouse: &Warehouse, dock: &BinLocation, acid: &Product, quantity: i32) -> GoodsReceiptView {
+        let supplier = SupplierDetails {
+            name: "Acme".to_string(), contact_name: None, contact_email: None, lead_time_days: 5,
+            currency: "EUR".to_string(), over_receipt_percent: 0, under_receipt_percent: 0, requires_inspection: false
+        }.create(conn).unwrap();
+        let order = NewPurchaseOrder::new(&supplier, warehouse, &Operator::new("buyer")).create(conn).unwrap()
+            .add_line(acid.id(), quantity, 100, conn).unwrap().unwrap()
+            .transition(PurchaseOrderStatus::Sent, conn).unwrap().unwrap();
+        let line = ReceivedLine {
+            purchase_order_line_id: order.lines(conn).unwrap()[0].id(),
+            quantity, damaged_quantity: 0, lot: None, serial_numbers: Vec::new()
+        };
+        GoodsReceipt::record(order.id(), dock, None, vec![line], &Operator::new("clerk"), conn).unwrap()
+    }
+
+    #[test]
+    fn quarantined_stock_is_not_available_until_released() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::Reserve]);
+            let acid = NewProduct::new("ACID", "acid").requiring_inspection().create(&conn).unwrap();
+            let receipt = received_for_inspection(&conn, &warehouse, &bins[0], &acid, 10);
+
+            let balance = InventoryBalance::lookup(acid.id(), None, bins[0].id(), &conn).unwrap().unwrap();
+            assert_eq!((balance.on_hand(), balance.allocated()), (10, 0));
+            assert_eq!(StockLevel::of(vec![balance], &conn).unwrap()[0].available, 0);
+            assert_eq!(ProductStock::for_warehouse(warehouse.id(), &conn).unwrap()[0].available, 0);
+            assert!(matches!(InventoryBalance::allocate(acid.id(), warehouse.id(), 1, &conn), Err(StockError::Shortage { available: 0, .. })));
+            let held = PutawayConfirmation { goods_receipt_line_id: receipt.lines[0].id(), to_location_id: bins[1].id(), quantity: None, serial_numbers: Vec::new() }
+                .confirm(&receipt.receipt, &Operator::new("driver"), &conn);
+            assert!(matches!(held, Err(StockError::InvalidMovement(_))));
+
+            let pending = Inspection::pending(warehouse.id(), &conn).unwrap();
+            let released = pending[0].clone().release(None, &Operator::new("inspector"), &conn).unwrap().unwrap();
+            assert_eq!(released.status(), InspectionStatus::Released);
+            assert!(pending[0].clone().release(None, &Operator::new("inspector"), &conn).unwrap().is_none());
+
+            PutawayConfirmation { goods_receipt_line_id: receipt.lines[0].id(), to_location_id: bins[1].id(), quantity: None, serial_numbers: Vec::new() }
+                .confirm(&receipt.receipt, &Operator::new("driver"), &conn).unwrap();
+            assert_eq!(ProductStock::for_warehouse(warehouse.id(), &conn).unwrap()[0].available, 10);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn rejected_units_move_to_returns_and_can_be_written_off_there() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::Returns, LocationType::Reserve]);
+            let acid = NewProduct::new("ACID", "acid").requiring_inspection().create(&conn).unwrap();
+            received_for_inspection(&conn, &warehouse, &bins[0], &acid, 10);
+
+            let inspection = Inspection::pending(warehouse.id(), &conn).unwrap().remove(0);
+            let not_returns = inspection.clone().reject(3, &bins[2], Vec::new(), None, &Operator::new("inspector"), &conn);
+            assert!(matches!(not_returns, Err(StockError::InvalidMovement(_))));
+
+            let rejected = inspection.reject(3, &bins[1], Vec::new(), Some("leaking".to_string()), &Operator::new("inspector"), &conn).unwrap().unwrap();
+            assert_eq!(rejected.status(), InspectionStatus::Rejected);
+
+            let dock = InventoryBalance::lookup(acid.id(), None, bins[0].id(), &conn).unwrap().unwrap();
+            let returns = InventoryBalance::lookup(acid.id(), None, bins[1].id(), &conn).unwrap().unwrap();
+            assert_eq!((dock.on_hand(), dock.allocated()), (7, 0));
+            assert_eq!((returns.on_hand(), returns.allocated()), (3, 0));
+            let levels = StockLevel::of(vec![returns], &conn).unwrap();
+            assert_eq!(levels[0].available, 0);
+            let movement = StockMovement::for_document(&format!("INSPECTION-{}", rejected.id()), MovementReason::Transfer, &conn).unwrap();
+            assert_eq!(movement.quantity(), 3);
+
+            NewStockMovement::new(acid.id(), Some(bins[1].id()), None, 3, MovementReason::Adjustment, &Operator::new("inspector"))
+                .with_reference("SCRAP-1")
+                .post(&conn).unwrap();
+            let returns = InventoryBalance::lookup(acid.id(), None, bins[1].id(), &conn).unwrap().unwrap();
+            assert_eq!((returns.on_hand(), returns.allocated()), (0, 0));
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,55 @@
+use crate::purchasing::inspection::models::{Inspection, InspectionRejection, InspectionRelease};
+use crate::location::bin::models::{BinLocation, LocationType};
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+/// Inspections of the warehouse waiting for a decision, oldest first.
+#[get("/warehouse/<id>/inspection")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Inspection>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(Inspection::pending(warehouse.id(), &*conn)?))
+}
+
+#[get("/inspection/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<Inspection>, ApiError> {
+    Ok(Json(Inspection::find(id, &*conn)?))
+}
+
+#[post("/inspection/<id>/release", data="<release>")]
+pub fn release(id: i32, release: Option<Json<InspectionRelease>>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<Inspection>, ApiError> {
+    let operator = operator?;
+    let inspection = Inspection::find(id, &*conn)?;
+    let release = release.map(Json::into_inner).unwrap_or_default();
+    match inspection.release(release.notes, &operator, &*conn)? {
+        Some(released) => Ok(Json(released)),
+        None => Err(ApiError::Conflict(format!("Inspection {} was already decided", id)))
+    }
+}
+
+#[post("/inspection/<id>/reject", data="<rejection>")]
+pub fn reject(id: i32, rejection: Option<Json<InspectionRejection>>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Json<Inspection>, ApiError> {
+    let operator = operator?;
+    let inspection = Inspection::find(id, &*conn)?;
+    let rejection = rejection.map(Json::into_inner).unwrap_or_default();
+    let held_in = BinLocation::find(inspection.bin_location_id(), &*conn)?;
+    let returns = match rejection.return_location_id {
+        Some(bin) => BinLocation::find(bin, &*conn)
+            .optional()?
+            .ok_or_else(|| ApiError::UnprocessableEntity(format!("Bin {} does not exist", bin)))?,
+        None => BinLocation::of_type(held_in.warehouse_id(), LocationType::Returns, &*conn)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ApiError::UnprocessableEntity(format!("Warehouse {} has no returns bin", held_in.warehouse_id())))?
+    };
+    let quantity = rejection.quantity.unwrap_or_else(|| inspection.quantity());
+    match inspection.reject(quantity, &returns, rejection.serial_numbers, rejection.notes, &operator, &*conn)? {
+        Some(rejected) => Ok(Json(rejected)),
+        None => Err(ApiError::Conflict(format!("Inspection {} was already decided", id)))
+    }
+}
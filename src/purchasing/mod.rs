@@ -0,0 +1,4 @@
+pub mod supplier;
+pub mod order;
+pub mod receiving;
+pub mod inspection;
\ No newline at end of file
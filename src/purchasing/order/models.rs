@@ -0,0 +1,406 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use diesel::update;
+use crate::schema::{purchase_order, purchase_order_line};
+use crate::purchasing::supplier::models::Supplier;
+use crate::location::warehouse::models::Warehouse;
+use crate::operator::Operator;
+use crate::domain::DomainError;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Integer, Nullable, Date, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    pub enum PurchaseOrderStatus {
+        Draft => "draft",
+        Sent => "sent",
+        PartiallyReceived => "partially_received",
+        Received => "received",
+        Closed => "closed",
+        Cancelled => "cancelled"
+    }
+}
+
+impl PurchaseOrderStatus {
+    /// Orders are sent or cancelled as drafts, received while sent and closed once something
+    /// arrived. Orders nothing was received for yet can still be cancelled.
+    pub fn can_become(self, next: PurchaseOrderStatus) -> bool {
+        use PurchaseOrderStatus::*;
+        matches!((self, next),
+            (Draft, Sent) | (Draft, Cancelled) | (Sent, Cancelled)
+            | (Sent, PartiallyReceived) | (Sent, Received)
+            | (PartiallyReceived, PartiallyReceived) | (PartiallyReceived, Received)
+            | (PartiallyReceived, Closed) | (Received, Closed))
+    }
+}
+
+/// Header of an order placed with a supplier for delivery to one warehouse. Its version is
+/// incremented by every change to the header or its lines.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="purchase_order"]
+pub struct PurchaseOrder {
+    id: i32,
+    supplier_id: i32,
+    warehouse_id: i32,
+    currency: String,
+    status: PurchaseOrderStatus,
+    expected_on: Option<NaiveDate>,
+    created_by: String,
+    created_at: NaiveDateTime,
+    version: i32
+}
+
+impl AsChangeset for PurchaseOrder {
+    type Target = purchase_order::table;
+    type Changeset = <(DieselEq<purchase_order::status, Bound<Text, PurchaseOrderStatus>>,
+                       DieselEq<purchase_order::expected_on, Bound<Nullable<Date>, Option<NaiveDate>>>,
+                       DieselEq<purchase_order::version, DieselAdd<purchase_order::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            purchase_order::status.eq(self.status),
+            purchase_order::expected_on.eq(self.expected_on),
+            purchase_order::version.eq(purchase_order::version + 1)
+        ).as_changeset()
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="purchase_order_line"]
+pub struct PurchaseOrderLine {
+    id: i32,
+    purchase_order_id: i32,
+    product_id: i32,
+    quantity: i32,
+    /// Cost of one base unit in the minor unit of the order's currency, e.g. cents.
+    unit_cost_minor: i64,
+    received_quantity: i32,
+    /// Units delivered damaged, recorded for the supplier but never booked into stock.
+    damaged_quantity: i32
+}
+
+impl PurchaseOrderLine {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn received_quantity(&self) -> i32 {
+        self.received_quantity
+    }
+
+    pub fn damaged_quantity(&self) -> i32 {
+        self.damaged_quantity
+    }
+
+    /// Quantity still to be delivered, never negative even if the supplier sent too much.
+    pub fn outstanding(&self) -> i32 {
+        (self.quantity - self.received_quantity).max(0)
+    }
+}
+
+impl PurchaseOrder {
+
+    pub fn find(order_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<PurchaseOrder, diesel::result::Error> {
+        use crate::schema::purchase_order::dsl::*;
+        purchase_order.find(order_id).first(conn)
+    }
+
+    /// Orders, newest first, optionally only those in `order_status` or placed with `supplier`.
+    pub fn list(order_status: Option<PurchaseOrderStatus>, supplier: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PurchaseOrder>, diesel::result::Error> {
+        use crate::schema::purchase_order::dsl::*;
+        let mut query = purchase_order.into_boxed();
+        if let Some(order_status) = order_status {
+            query = query.filter(status.eq(order_status));
+        }
+        if let Some(supplier) = supplier {
+            query = query.filter(supplier_id.eq(supplier));
+        }
+        query.order(id.desc()).load(conn)
+    }
+
+    /// Loads the order and locks its row until the surrounding transaction ends.
+    pub fn lock(order_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<PurchaseOrder, diesel::result::Error> {
+        use crate::schema::purchase_order::dsl::*;
+        purchase_order.find(order_id).for_update().first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn supplier_id(&self) -> i32 {
+        self.supplier_id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn status(&self) -> PurchaseOrderStatus {
+        self.status
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PurchaseOrderLine>, diesel::result::Error> {
+        use crate::schema::purchase_order_line::dsl::*;
+        purchase_order_line.filter(purchase_order_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<PurchaseOrder>, diesel::result::Error> {
+        use crate::schema::purchase_order::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(purchase_order.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    /// Moves the order to `next`. Sent orders need at least one line and are expected after the
+    /// supplier's lead time unless a date was agreed. Returns `None` if the order changed meanwhile.
+    pub fn transition(self, next: PurchaseOrderStatus, conn: &impl Connection<Backend=Pg>) -> Result<Option<PurchaseOrder>, DomainError> {
+        if !self.status.can_become(next) {
+            return Err(DomainError::Rejected(format!("Purchase order {} is {} and can not become {}", self.id, self.status, next)));
+        }
+        conn.transaction(|| {
+            let mut expected_on = self.expected_on;
+            if next == PurchaseOrderStatus::Sent {
+                if self.lines(conn)?.is_empty() {
+                    return Err(DomainError::Rejected(format!("Purchase order {} has no lines to send", self.id)));
+                }
+                if expected_on.is_none() {
+                    let supplier = Supplier::find(self.supplier_id, conn)?;
+                    expected_on = Some(Local::today().naive_local() + Duration::days(i64::from(supplier.lead_time_days())));
+                }
+            }
+            Ok(PurchaseOrder { status: next, expected_on, ..self }.update(conn)?)
+        })
+    }
+
+    /// Orders `quantity` base units of `product` at `unit_cost_minor` each. The total cost of the
+    /// order has to stay within what the minor unit of its currency can count.
+    pub fn add_line(self, product: i32, quantity: i32, unit_cost_minor: i64, conn: &impl Connection<Backend=Pg>) -> Result<Option<PurchaseOrder>, DomainError> {
+        if quantity <= 0 || unit_cost_minor < 0 {
+            return Err(DomainError::Invalid(String::from("Purchase order lines need a positive quantity and a unit cost of at least zero")));
+        }
+        let order_id = self.id;
+        self.revise_draft(|conn| {
+            let costs: Vec<(i32, i64)> = purchase_order_line::table
+                .filter(purchase_order_line::purchase_order_id.eq(order_id))
+                .select((purchase_order_line::quantity, purchase_order_line::unit_cost_minor))
+                .load(conn)?;
+            if total_cost_minor(costs.into_iter().chain(std::iter::once((quantity, unit_cost_minor)))).is_none() {
+                return Err(DomainError::Invalid(format!("Adding {} units at {} would overflow the total cost of purchase order {}", quantity, unit_cost_minor, order_id)));
+            }
+            diesel::insert_into(purchase_order_line::table)
+                .values((
+                    purchase_order_line::purchase_order_id.eq(order_id),
+                    purchase_order_line::product_id.eq(product),
+                    purchase_order_line::quantity.eq(quantity),
+                    purchase_order_line::unit_cost_minor.eq(unit_cost_minor)
+                ))
+                .execute(conn)?;
+            Ok(())
+        }, conn)
+    }
+
+    pub fn remove_line(self, line_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Option<PurchaseOrder>, DomainError> {
+        let order_id = self.id;
+        self.revise_draft(|conn| {
+            use crate::schema::purchase_order_line::dsl::*;
+            match diesel::delete(purchase_order_line.filter(id.eq(line_id).and(purchase_order_id.eq(order_id)))).execute(conn)? {
+                0 => Err(diesel::result::Error::NotFound.into()),
+                _ => Ok(())
+            }
+        }, conn)
+    }
+
+    /// Applies `change` to the lines of a draft order and increments the header's version.
+    /// Returns `None` without applying it if the order changed meanwhile.
+    fn revise_draft<C, F>(self, change: F, conn: &C) -> Result<Option<PurchaseOrder>, DomainError>
+        where C: Connection<Backend=Pg>, F: FnOnce(&C) -> Result<(), DomainError> {
+        use crate::schema::purchase_order::dsl::*;
+        if self.status != PurchaseOrderStatus::Draft {
+            return Err(DomainError::Rejected(format!("Lines of purchase order {} can only be changed while it is a draft", self.id)));
+        }
+        conn.transaction(|| {
+            let revised = update(purchase_order.filter(id.eq(self.id).and(version.eq(self.version)).and(status.eq(PurchaseOrderStatus::Draft))))
+                .set(version.eq(version + 1))
+                .get_result::<PurchaseOrder>(conn)
+                .optional()?;
+            if revised.is_some() {
+                change(conn)?;
+            }
+            Ok(revised)
+        })
+    }
+}
+
+/// An order with its lines and their total cost in the minor unit of the order's currency.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PurchaseOrderView {
+    #[serde(flatten)]
+    pub order: PurchaseOrder,
+    pub lines: Vec<PurchaseOrderLine>,
+    pub total_cost_minor: i64
+}
+
+impl PurchaseOrderView {
+    pub fn load(order: PurchaseOrder, conn: &impl Connection<Backend=Pg>) -> Result<PurchaseOrderView, diesel::result::Error> {
+        let lines = order.lines(conn)?;
+        let total_cost_minor = total_cost_minor(lines.iter().map(|line| (line.quantity, line.unit_cost_minor)))
+            .unwrap_or(i64::MAX);
+        Ok(PurchaseOrderView { order, lines, total_cost_minor })
+    }
+}
+
+/// Sum of quantity times unit cost over `lines`, `None` if it does not fit into an `i64`.
+fn total_cost_minor(lines: impl IntoIterator<Item=(i32, i64)>) -> Option<i64> {
+    lines.into_iter().try_fold(0i64, |total, (quantity, unit_cost_minor)| {
+        total.checked_add(i64::from(quantity).checked_mul(unit_cost_minor)?)
+    })
+}
+
+/// Body of a `PUT /purchase-order/<id>` request, supplier and warehouse can not be changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseOrderChanges {
+    #[serde(default)]
+    pub expected_on: Option<NaiveDate>
+}
+
+impl PurchaseOrderChanges {
+    pub fn for_order(self, current: PurchaseOrder, expected_version: i32) -> Result<PurchaseOrder, String> {
+        match current.status {
+            PurchaseOrderStatus::Draft | PurchaseOrderStatus::Sent => Ok(PurchaseOrder {
+                expected_on: self.expected_on,
+                version: expected_version,
+                ..current
+            }),
+            other => Err(format!("Purchase order {} is {} and can no longer be changed", current.id, other))
+        }
+    }
+}
+
+/// Body of a `POST /purchase-order/<id>/line` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseOrderLineDefinition {
+    pub product_id: i32,
+    pub quantity: crate::product::packaging::models::Quantity,
+    pub unit_cost_minor: i64
+}
+
+/// Body of a `POST /purchase-order` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurchaseOrderRequest {
+    pub supplier_id: i32,
+    pub warehouse_id: i32,
+    #[serde(default)]
+    pub expected_on: Option<NaiveDate>
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="purchase_order"]
+pub struct NewPurchaseOrder {
+    supplier_id: i32,
+    warehouse_id: i32,
+    currency: String,
+    expected_on: Option<NaiveDate>,
+    created_by: String
+}
+
+impl NewPurchaseOrder {
+    /// A draft order in the supplier's currency.
+    pub fn new(supplier: &Supplier, warehouse: &Warehouse, operator: &Operator) -> NewPurchaseOrder {
+        NewPurchaseOrder {
+            supplier_id: supplier.id(),
+            warehouse_id: warehouse.id(),
+            currency: supplier.currency().to_string(),
+            expected_on: None,
+            created_by: operator.name().to_string()
+        }
+    }
+
+    pub fn expected_on(self, date: Option<NaiveDate>) -> NewPurchaseOrder {
+        NewPurchaseOrder { expected_on: date, ..self }
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<PurchaseOrder, diesel::result::Error> {
+        use crate::schema::purchase_order::dsl::*;
+        conn.transaction(|| {
+            diesel::insert_into(purchase_order)
+                .values(self)
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::purchasing::supplier::models::SupplierDetails;
+    use crate::purchasing::order::models::{NewPurchaseOrder, PurchaseOrderStatus, PurchaseOrderView};
+    use crate::domain::DomainError;
+    use crate::operator::Operator;
+
+    #[test]
+    fn only_orders_nothing_was_received_for_can_be_cancelled() {
+        use PurchaseOrderStatus::*;
+        assert!(Draft.can_become(Sent));
+        assert!(Sent.can_become(Cancelled));
+        assert!(!PartiallyReceived.can_become(Cancelled));
+        assert!(!Draft.can_become(Closed));
+        assert!(!Cancelled.can_become(Sent));
+    }
+
+    #[test]
+    fn lines_change_the_order_version_and_are_fixed_once_sent() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, _) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            let supplier = SupplierDetails { name: "Acme".to_string(), contact_name: None, contact_email: None, lead_time_days: 5, currency: "EUR".to_string(), over_receipt_percent: 0, under_receipt_percent: 0, requires_inspection: false }
+                .create(&conn).unwrap();
+            let clerk = Operator::new("buyer");
+            let order = NewPurchaseOrder::new(&supplier, &warehouse, &clerk).create(&conn).unwrap();
+
+            let empty = order.clone().transition(PurchaseOrderStatus::Sent, &conn);
+            assert!(matches!(empty, Err(DomainError::Rejected(_))));
+
+            let revised = order.clone().add_line(soap.id(), 10, 150, &conn).unwrap().unwrap();
+            assert_eq!(revised.version(), 1);
+            assert!(order.add_line(soap.id(), 1, 1, &conn).unwrap().is_none());
+            assert!(matches!(revised.clone().add_line(soap.id(), 0, 1, &conn), Err(DomainError::Invalid(_))));
+            assert!(matches!(revised.clone().add_line(soap.id(), 1, -1, &conn), Err(DomainError::Invalid(_))));
+            assert!(matches!(revised.clone().add_line(soap.id(), 2, i64::MAX / 2, &conn), Err(DomainError::Invalid(_))));
+
+            let sent = revised.transition(PurchaseOrderStatus::Sent, &conn).unwrap().unwrap();
+            assert!(sent.expected_on.is_some());
+            let view = PurchaseOrderView::load(sent.clone(), &conn).unwrap();
+            assert_eq!(view.total_cost_minor, 1500);
+
+            let line_id = view.lines[0].id();
+            assert!(matches!(sent.remove_line(line_id, &conn), Err(DomainError::Rejected(_))));
+            Ok(())
+        })
+    }
+}
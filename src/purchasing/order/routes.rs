@@ -0,0 +1,122 @@
+use crate::purchasing::order::models::{PurchaseOrder, PurchaseOrderChanges, PurchaseOrderLineDefinition, PurchaseOrderRequest, PurchaseOrderStatus, PurchaseOrderView, NewPurchaseOrder};
+use crate::purchasing::supplier::models::Supplier;
+use crate::location::warehouse::models::Warehouse;
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+use crate::operator::Operator;
+use diesel::pg::Pg;
+
+
+#[post("/purchase-order", format="application/json", data="<request>")]
+pub fn post(request: Json<PurchaseOrderRequest>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Tagged<Created<Json<PurchaseOrderView>>>, ApiError> {
+    let operator = operator?;
+    let supplier = Supplier::find(request.supplier_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Supplier {} does not exist", request.supplier_id)))?;
+    let warehouse = Warehouse::find(request.warehouse_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Warehouse {} does not exist", request.warehouse_id)))?;
+    let created = NewPurchaseOrder::new(&supplier, &warehouse, &operator)
+        .expected_on(request.expected_on)
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/purchase-order/{}", created.id()), Some(Json(PurchaseOrderView::load(created, &*conn)?)))))
+}
+
+#[get("/purchase-order?<status>&<supplier>")]
+pub fn list(status: Option<String>, supplier: Option<i32>, conn: PostgresConnection) -> Result<Json<Vec<PurchaseOrder>>, ApiError> {
+    let status = match status {
+        Some(status) => Some(PurchaseOrderStatus::parse(&status)
+            .ok_or_else(|| ApiError::BadRequest(format!("Unknown purchase order status {}", status)))?),
+        None => None
+    };
+    Ok(Json(PurchaseOrder::list(status, supplier, &*conn)?))
+}
+
+#[get("/purchase-order/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    let order = PurchaseOrder::find(id, &*conn)?;
+    tagged_view(order, &conn)
+}
+
+#[put("/purchase-order/<id>", format="application/json", data="<changes>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, changes: Json<PurchaseOrderChanges>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    let expected_version = if_match?.version();
+    let current = PurchaseOrder::find(id, &*conn)?;
+    let changed = changes.into_inner().for_order(current, expected_version).map_err(ApiError::Conflict)?;
+    match changed.update(&*conn)? {
+        Some(updated) => tagged_view(updated, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+#[post("/purchase-order/<id>/line", format="application/json", data="<line>")]
+pub fn add_line(id: i32, if_match: Result<IfMatch, ApiError>, line: Json<PurchaseOrderLineDefinition>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    let line = line.into_inner();
+    let product = Product::find(line.product_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Product {} does not exist", line.product_id)))?;
+    let quantity = line.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    match order.add_line(product.id(), quantity, line.unit_cost_minor, &*conn)? {
+        Some(revised) => tagged_view(revised, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+#[delete("/purchase-order/<id>/line/<line_id>")]
+pub fn remove_line(id: i32, line_id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    match order.remove_line(line_id, &*conn)? {
+        Some(revised) => tagged_view(revised, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+#[post("/purchase-order/<id>/send")]
+pub fn send(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    transition(id, if_match, PurchaseOrderStatus::Sent, &conn)
+}
+
+#[post("/purchase-order/<id>/cancel")]
+pub fn cancel(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    transition(id, if_match, PurchaseOrderStatus::Cancelled, &conn)
+}
+
+#[post("/purchase-order/<id>/close")]
+pub fn close(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    transition(id, if_match, PurchaseOrderStatus::Closed, &conn)
+}
+
+fn transition(id: i32, if_match: Result<IfMatch, ApiError>, next: PurchaseOrderStatus, conn: &PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    let order = current(id, if_match, conn)?;
+    match order.transition(next, &**conn)? {
+        Some(moved) => tagged_view(moved, conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &**conn), |current| current.order.version()))
+    }
+}
+
+/// The order in the version the client expects it to be in.
+fn current(id: i32, if_match: Result<IfMatch, ApiError>, conn: &PostgresConnection) -> Result<PurchaseOrder, ApiError> {
+    let expected_version = if_match?.version();
+    let order = PurchaseOrder::find(id, &**conn)?;
+    if order.version() != expected_version {
+        return Err(ApiError::precondition_failed(order.version(), &PurchaseOrderView::load(order, &**conn)?));
+    }
+    Ok(order)
+}
+
+fn tagged_view(order: PurchaseOrder, conn: &PostgresConnection) -> Result<Tagged<Json<PurchaseOrderView>>, ApiError> {
+    Ok(Tagged(order.version(), Json(PurchaseOrderView::load(order, &**conn)?)))
+}
+
+/// The order as it is now, to answer a lost optimistic lock with.
+fn reloaded(id: i32, conn: &impl Connection<Backend=Pg>) -> Result<PurchaseOrderView, diesel::result::Error> {
+    PurchaseOrder::find(id, conn).and_then(|current| PurchaseOrderView::load(current, conn))
+}
@@ -0,0 +1,554 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::update;
+use crate::schema::{goods_receipt, goods_receipt_line, shipping_notice, shipping_notice_line, purchase_order_line};
+use crate::purchasing::order::models::{PurchaseOrder, PurchaseOrderLine, PurchaseOrderStatus};
+use crate::purchasing::supplier::models::Supplier;
+use crate::purchasing::inspection::models::NewInspection;
+use crate::inventory::movement::models::{MovementReason, NewStockMovement};
+use crate::location::bin::models::{BinLocation, LocationType};
+use crate::product::models::Product;
+use crate::product::lot::models::LotDefinition;
+use crate::product::packaging::models::{ConversionError, PackagingHierarchy, Quantity};
+use crate::operator::Operator;
+use crate::domain::DomainError;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    /// An advance shipping notice is open until goods are received against it.
+    pub enum NoticeStatus {
+        Open => "open",
+        Received => "received"
+    }
+}
+
+/// Goods can only be received against orders which were sent and are not fully received yet.
+fn check_receivable(order: &PurchaseOrder) -> Result<(), DomainError> {
+    match order.status() {
+        PurchaseOrderStatus::Sent | PurchaseOrderStatus::PartiallyReceived => Ok(()),
+        other => Err(DomainError::Rejected(format!("Purchase order {} is {} and can not receive goods", order.id(), other)))
+    }
+}
+
+/// Advance shipping notice, the supplier's announcement of what a delivery against an order contains.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="shipping_notice"]
+pub struct ShippingNotice {
+    id: i32,
+    purchase_order_id: i32,
+    reference: String,
+    expected_on: Option<NaiveDate>,
+    status: NoticeStatus,
+    created_at: NaiveDateTime
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="shipping_notice_line"]
+pub struct ShippingNoticeLine {
+    id: i32,
+    notice_id: i32,
+    purchase_order_line_id: i32,
+    quantity: i32,
+    lot_number: Option<String>,
+    expires_on: Option<NaiveDate>
+}
+
+impl ShippingNotice {
+
+    pub fn find(notice_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<ShippingNotice, diesel::result::Error> {
+        use crate::schema::shipping_notice::dsl::*;
+        shipping_notice.find(notice_id).first(conn)
+    }
+
+    pub fn for_order(order: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ShippingNotice>, diesel::result::Error> {
+        use crate::schema::shipping_notice::dsl::*;
+        shipping_notice.filter(purchase_order_id.eq(order)).order(id.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn purchase_order_id(&self) -> i32 {
+        self.purchase_order_id
+    }
+
+    pub fn status(&self) -> NoticeStatus {
+        self.status
+    }
+
+    pub fn lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ShippingNoticeLine>, diesel::result::Error> {
+        use crate::schema::shipping_notice_line::dsl::*;
+        shipping_notice_line.filter(notice_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+
+    /// Receipt lines for the delivery arriving exactly as announced, without damages.
+    pub fn expected_lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<ReceivedLine>, diesel::result::Error> {
+        Ok(self.lines(conn)?
+            .into_iter()
+            .map(|line| ReceivedLine {
+                purchase_order_line_id: line.purchase_order_line_id,
+                quantity: line.quantity,
+                damaged_quantity: 0,
+                lot: line.lot_number.map(|lot_number| LotDefinition { lot_number, manufactured_on: None, expires_on: line.expires_on }),
+                serial_numbers: Vec::new()
+            })
+            .collect())
+    }
+}
+
+/// A notice with its lines.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct ShippingNoticeView {
+    #[serde(flatten)]
+    pub notice: ShippingNotice,
+    pub lines: Vec<ShippingNoticeLine>
+}
+
+impl ShippingNoticeView {
+    pub fn load(notice: ShippingNotice, conn: &impl Connection<Backend=Pg>) -> Result<ShippingNoticeView, diesel::result::Error> {
+        let lines = notice.lines(conn)?;
+        Ok(ShippingNoticeView { notice, lines })
+    }
+}
+
+/// Body of a `POST /purchase-order/<id>/asn` request, as sent by the supplier.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShippingNoticeImport {
+    /// The supplier's delivery note or shipment number.
+    pub reference: String,
+    #[serde(default)]
+    pub expected_on: Option<NaiveDate>,
+    pub lines: Vec<ShippingNoticeImportLine>
+}
+
+/// A line of an imported notice, identifying the product by SKU and the quantity in base units.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShippingNoticeImportLine {
+    pub sku: String,
+    pub quantity: i32,
+    #[serde(default)]
+    pub lot_number: Option<String>,
+    #[serde(default)]
+    pub expires_on: Option<NaiveDate>
+}
+
+impl ShippingNoticeImport {
+    /// Records the notice against `order`, matching every line to the order line of its product.
+    pub fn import(self, order: &PurchaseOrder, conn: &impl Connection<Backend=Pg>) -> Result<ShippingNoticeView, DomainError> {
+        check_receivable(order)?;
+        let ShippingNoticeImport { reference, expected_on, lines } = self;
+        let reference = reference.trim();
+        if reference.is_empty() {
+            return Err(DomainError::Invalid(String::from("Shipping notice reference must not be empty")));
+        }
+        if lines.is_empty() {
+            return Err(DomainError::Invalid(format!("Shipping notice {} has no lines", reference)));
+        }
+        conn.transaction(|| {
+            let order_lines = order.lines(conn)?;
+            let notice: ShippingNotice = diesel::insert_into(shipping_notice::table)
+                .values((
+                    shipping_notice::purchase_order_id.eq(order.id()),
+                    shipping_notice::reference.eq(reference),
+                    shipping_notice::expected_on.eq(expected_on)
+                ))
+                .get_result(conn)?;
+
+            for line in lines {
+                if line.quantity <= 0 {
+                    return Err(DomainError::Invalid(format!("Announced quantity of {} must be positive, got {}", line.sku, line.quantity)));
+                }
+                let product = Product::find_by_sku(line.sku.trim(), conn)
+                    .optional()?
+                    .ok_or_else(|| DomainError::Invalid(format!("Product {} does not exist", line.sku)))?;
+                let order_line = order_lines.iter()
+                    .find(|order_line| order_line.product_id() == product.id())
+                    .ok_or_else(|| DomainError::Invalid(format!("Product {} was not ordered on purchase order {}", line.sku, order.id())))?;
+                let lot_number = line.lot_number
+                    .map(|lot_number| lot_number.trim().to_uppercase())
+                    .filter(|lot_number| !lot_number.is_empty());
+                diesel::insert_into(shipping_notice_line::table)
+                    .values((
+                        shipping_notice_line::notice_id.eq(notice.id),
+                        shipping_notice_line::purchase_order_line_id.eq(order_line.id()),
+                        shipping_notice_line::quantity.eq(line.quantity),
+                        shipping_notice_line::lot_number.eq(lot_number),
+                        shipping_notice_line::expires_on.eq(line.expires_on)
+                    ))
+                    .execute(conn)?;
+            }
+            Ok(ShippingNoticeView::load(notice, conn)?)
+        })
+    }
+}
+
+/// A delivery unloaded at the dock and booked into one receiving bin.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="goods_receipt"]
+pub struct GoodsReceipt {
+    id: i32,
+    purchase_order_id: i32,
+    notice_id: Option<i32>,
+    bin_location_id: i32,
+    received_by: String,
+    received_at: NaiveDateTime
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="goods_receipt_line"]
+pub struct GoodsReceiptLine {
+    id: i32,
+    goods_receipt_id: i32,
+    purchase_order_line_id: i32,
+    quantity: i32,
+    damaged_quantity: i32,
+    lot_id: Option<i32>,
+    movement_id: Option<i32>,
+    /// Units already moved from the receiving bin to storage.
+    put_away_quantity: i32,
+    /// Units failing inspection, moved to a returns bin.
+    rejected_quantity: i32
+}
+
+impl GoodsReceiptLine {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn purchase_order_line_id(&self) -> i32 {
+        self.purchase_order_line_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn lot_id(&self) -> Option<i32> {
+        self.lot_id
+    }
+
+    /// Received units still waiting in the receiving bin.
+    pub fn awaiting_putaway(&self) -> i32 {
+        self.quantity - self.put_away_quantity - self.rejected_quantity
+    }
+}
+
+/// What arrived for one order line, in base units.
+#[derive(Debug)]
+pub struct ReceivedLine {
+    pub purchase_order_line_id: i32,
+    pub quantity: i32,
+    pub damaged_quantity: i32,
+    pub lot: Option<LotDefinition>,
+    pub serial_numbers: Vec<String>
+}
+
+impl GoodsReceipt {
+
+    pub fn find(receipt_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<GoodsReceipt, diesel::result::Error> {
+        use crate::schema::goods_receipt::dsl::*;
+        goods_receipt.find(receipt_id).first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn purchase_order_id(&self) -> i32 {
+        self.purchase_order_id
+    }
+
+    pub fn bin_location_id(&self) -> i32 {
+        self.bin_location_id
+    }
+
+    pub fn lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<GoodsReceiptLine>, diesel::result::Error> {
+        use crate::schema::goods_receipt_line::dsl::*;
+        goods_receipt_line.filter(goods_receipt_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+
+    /// Books the intact units of `lines` into the staging or dock bin `bin`, adds them to the
+    /// received quantities of the order and moves the order to partially received or received.
+    /// Units of products or from suppliers requiring inspection are held until inspected.
+    /// Deliveries beyond the supplier's over-receipt tolerance are rejected, a line counts as
+    /// complete once it is short by no more than the under-receipt tolerance.
+    pub fn record(order_id: i32, bin: &BinLocation, notice: Option<i32>, lines: Vec<ReceivedLine>, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<GoodsReceiptView, DomainError> {
+        if lines.is_empty() {
+            return Err(DomainError::Invalid(String::from("A goods receipt needs at least one line")));
+        }
+        conn.transaction(|| {
+            let order = PurchaseOrder::lock(order_id, conn)?;
+            check_receivable(&order)?;
+            if bin.warehouse_id() != order.warehouse_id() {
+                return Err(DomainError::Invalid(format!("Bin {} is not in the warehouse of purchase order {}", bin.location_code(), order.id())));
+            }
+            if !matches!(bin.location_type(), LocationType::Staging | LocationType::Dock) {
+                return Err(DomainError::Invalid(format!("Goods can only be received into staging or dock bins, {} is {}", bin.location_code(), bin.location_type())));
+            }
+            if let Some(notice) = notice {
+                let notice = ShippingNotice::find(notice, conn)?;
+                if notice.purchase_order_id != order.id() {
+                    return Err(DomainError::Invalid(format!("Shipping notice {} belongs to another purchase order", notice.id)));
+                }
+                if notice.status != NoticeStatus::Open {
+                    return Err(DomainError::Rejected(format!("Shipping notice {} was already received", notice.id)));
+                }
+                update(shipping_notice::table.find(notice.id))
+                    .set(shipping_notice::status.eq(NoticeStatus::Received))
+                    .execute(conn)?;
+            }
+            let supplier = Supplier::find(order.supplier_id(), conn)?;
+            let reference = format!("PO-{}", order.id());
+
+            let receipt: GoodsReceipt = diesel::insert_into(goods_receipt::table)
+                .values((
+                    goods_receipt::purchase_order_id.eq(order.id()),
+                    goods_receipt::notice_id.eq(notice),
+                    goods_receipt::bin_location_id.eq(bin.id()),
+                    goods_receipt::received_by.eq(operator.name())
+                ))
+                .get_result(conn)?;
+
+            for line in lines {
+                if line.quantity < 0 || line.damaged_quantity < 0 || (line.quantity == 0 && line.damaged_quantity == 0) {
+                    return Err(DomainError::Invalid(format!("Line {} needs a positive received or damaged quantity", line.purchase_order_line_id)));
+                }
+                let order_line: PurchaseOrderLine = purchase_order_line::table
+                    .find(line.purchase_order_line_id)
+                    .filter(purchase_order_line::purchase_order_id.eq(order.id()))
+                    .first(conn)
+                    .optional()?
+                    .ok_or_else(|| DomainError::Invalid(format!("Line {} is not a line of purchase order {}", line.purchase_order_line_id, order.id())))?;
+                let allowed = supplier.max_receivable(order_line.quantity()).min(i64::from(i32::MAX));
+                if i64::from(order_line.received_quantity()) + i64::from(line.quantity) > allowed {
+                    return Err(DomainError::Rejected(format!("Receiving {} units on line {} exceeds the {} allowed for {} ordered",
+                        line.quantity, order_line.id(), allowed - i64::from(order_line.received_quantity()), order_line.quantity())));
+                }
+                if i64::from(order_line.damaged_quantity()) + i64::from(line.damaged_quantity) > i64::from(order_line.quantity()) {
+                    return Err(DomainError::Rejected(format!("Recording {} damaged units on line {} exceeds the {} ordered",
+                        line.damaged_quantity, order_line.id(), order_line.quantity())));
+                }
+
+                let product = Product::find(order_line.product_id(), conn)?;
+                let lot = match line.lot {
+                    Some(definition) => {
+                        let captured_expiry = definition.expires_on;
+                        let lot = definition.for_product(&product).map_err(DomainError::Invalid)?.find_or_create(conn)?;
+                        if captured_expiry.is_some() && lot.expires_on() != captured_expiry {
+                            return Err(DomainError::Invalid(format!("Lot {} is recorded with a different expiry date", lot.lot_number())));
+                        }
+                        Some(lot.id())
+                    }
+                    None => None
+                };
+
+                let movement = if line.quantity > 0 {
+                    Some(NewStockMovement::new(product.id(), None, Some(bin.id()), line.quantity, MovementReason::Receipt, operator)
+                        .in_lot(lot)
+                        .with_serials(line.serial_numbers)
+                        .with_reference(&reference)
+                        .post(conn)?
+                        .id())
+                } else {
+                    None
+                };
+
+                update(purchase_order_line::table.find(order_line.id()))
+                    .set((
+                        purchase_order_line::received_quantity.eq(purchase_order_line::received_quantity + line.quantity),
+                        purchase_order_line::damaged_quantity.eq(purchase_order_line::damaged_quantity + line.damaged_quantity)
+                    ))
+                    .execute(conn)?;
+                let receipt_line: GoodsReceiptLine = diesel::insert_into(goods_receipt_line::table)
+                    .values((
+                        goods_receipt_line::goods_receipt_id.eq(receipt.id),
+                        goods_receipt_line::purchase_order_line_id.eq(order_line.id()),
+                        goods_receipt_line::quantity.eq(line.quantity),
+                        goods_receipt_line::damaged_quantity.eq(line.damaged_quantity),
+                        goods_receipt_line::lot_id.eq(lot),
+                        goods_receipt_line::movement_id.eq(movement)
+                    ))
+                    .get_result(conn)?;
+                if line.quantity > 0 && (product.requires_inspection() || supplier.requires_inspection()) {
+                    NewInspection::for_line(&receipt_line, &product, bin).hold(conn)?;
+                }
+            }
+
+            let complete = order.lines(conn)?
+                .iter()
+                .all(|line| i64::from(line.received_quantity()) >= supplier.min_receivable(line.quantity()));
+            let next = if complete { PurchaseOrderStatus::Received } else { PurchaseOrderStatus::PartiallyReceived };
+            let order = order.transition(next, conn)?
+                .ok_or_else(|| DomainError::Rejected(format!("Purchase order {} changed while receiving", order_id)))?;
+            Ok(GoodsReceiptView::load(receipt, order, conn)?)
+        })
+    }
+}
+
+/// A receipt with its lines and the order as it is after receiving.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct GoodsReceiptView {
+    #[serde(flatten)]
+    pub receipt: GoodsReceipt,
+    pub lines: Vec<GoodsReceiptLine>,
+    pub order: PurchaseOrder
+}
+
+impl GoodsReceiptView {
+    pub fn load(receipt: GoodsReceipt, order: PurchaseOrder, conn: &impl Connection<Backend=Pg>) -> Result<GoodsReceiptView, diesel::result::Error> {
+        let lines = receipt.lines(conn)?;
+        Ok(GoodsReceiptView { receipt, lines, order })
+    }
+}
+
+/// Body of a `POST /purchase-order/<id>/receipt` request. Without lines the delivery is received
+/// exactly as announced by the shipping notice.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoodsReceiptRequest {
+    pub to_location_id: i32,
+    #[serde(default)]
+    pub notice_id: Option<i32>,
+    #[serde(default)]
+    pub lines: Vec<ReceiptLineRequest>
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReceiptLineRequest {
+    pub purchase_order_line_id: i32,
+    /// Intact units, booked into stock.
+    pub quantity: Quantity,
+    #[serde(default)]
+    pub damaged_quantity: Option<Quantity>,
+    #[serde(default)]
+    pub lot_number: Option<String>,
+    #[serde(default)]
+    pub manufactured_on: Option<NaiveDate>,
+    #[serde(default)]
+    pub expires_on: Option<NaiveDate>,
+    #[serde(default)]
+    pub serial_numbers: Vec<String>
+}
+
+impl ReceiptLineRequest {
+    pub fn in_base_units(self, hierarchy: &PackagingHierarchy) -> Result<ReceivedLine, ConversionError> {
+        let damaged_quantity = match self.damaged_quantity {
+            Some(ref damaged) => damaged.in_base_units(hierarchy)?,
+            None => 0
+        };
+        let (manufactured_on, expires_on) = (self.manufactured_on, self.expires_on);
+        Ok(ReceivedLine {
+            purchase_order_line_id: self.purchase_order_line_id,
+            quantity: self.quantity.in_base_units(hierarchy)?,
+            damaged_quantity,
+            lot: self.lot_number.map(|lot_number| LotDefinition { lot_number, manufactured_on, expires_on }),
+            serial_numbers: self.serial_numbers
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::purchasing::supplier::models::SupplierDetails;
+    use crate::purchasing::order::models::{NewPurchaseOrder, PurchaseOrder, PurchaseOrderStatus};
+    use crate::purchasing::receiving::models::{GoodsReceipt, NoticeStatus, ReceivedLine, ShippingNotice, ShippingNoticeImport, ShippingNoticeImportLine};
+    use crate::domain::DomainError;
+    use crate::inventory::balance::models::InventoryBalance;
+    use crate::product::lot::models::LotDefinition;
+    use crate::product::models::Product;
+    use crate::location::warehouse::models::Warehouse;
+    use crate::operator::Operator;
+    use diesel::PgConnection;
+
+    fn sent_order(conn: &PgConnection, warehouse: &Warehouse, soap: &Product, quantity: i32) -> PurchaseOrder {
+        let supplier = SupplierDetails {
+            name: "Acme".to_string(), contact_name: None, contact_email: None, lead_time_days: 5,
+            currency: "EUR".to_string(), over_receipt_percent: 10, under_receipt_percent: 5, requires_inspection: false
+        }.create(conn).unwrap();
+        NewPurchaseOrder::new(&supplier, warehouse, &Operator::new("buyer")).create(conn).unwrap()
+            .add_line(soap.id(), quantity, 100, conn).unwrap().unwrap()
+            .transition(PurchaseOrderStatus::Sent, conn).unwrap().unwrap()
+    }
+
+    fn received(line: i32, quantity: i32, damaged_quantity: i32) -> ReceivedLine {
+        ReceivedLine { purchase_order_line_id: line, quantity, damaged_quantity, lot: None, serial_numbers: Vec::new() }
+    }
+
+    #[test]
+    fn receipts_within_tolerance_complete_the_order_and_book_intact_units() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let order = sent_order(&conn, &warehouse, &soap, 100);
+            let line = order.lines(&conn).unwrap()[0].id();
+            let clerk = Operator::new("clerk");
+
+            let into_reserve = GoodsReceipt::record(order.id(), &bins[1], None, vec![received(line, 10, 0)], &clerk, &conn);
+            assert!(matches!(into_reserve, Err(DomainError::Invalid(_))));
+
+            let first = GoodsReceipt::record(order.id(), &bins[0], None, vec![received(line, 50, 2)], &clerk, &conn).unwrap();
+            assert_eq!(first.order.status(), PurchaseOrderStatus::PartiallyReceived);
+
+            let lot = LotDefinition { lot_number: "l1".to_string(), manufactured_on: None, expires_on: None };
+            let second = GoodsReceipt::record(order.id(), &bins[0], None, vec![ReceivedLine { lot: Some(lot), ..received(line, 45, 0) }], &clerk, &conn).unwrap();
+            assert_eq!(second.order.status(), PurchaseOrderStatus::Received);
+            assert!(second.lines[0].lot_id.is_some());
+
+            let order_line = &second.order.lines(&conn).unwrap()[0];
+            assert_eq!((order_line.received_quantity(), order_line.damaged_quantity()), (95, 2));
+            let booked = InventoryBalance::lookup(soap.id(), None, bins[0].id(), &conn).unwrap().unwrap();
+            assert_eq!(booked.on_hand(), 50);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn deliveries_beyond_the_over_receipt_tolerance_are_rejected() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Staging]);
+            let soap = product(&conn, "SOAP");
+            let order = sent_order(&conn, &warehouse, &soap, 100);
+            let line = order.lines(&conn).unwrap()[0].id();
+            let clerk = Operator::new("clerk");
+
+            let too_many = GoodsReceipt::record(order.id(), &bins[0], None, vec![received(line, 111, 0)], &clerk, &conn);
+            assert!(matches!(too_many, Err(DomainError::Rejected(_))));
+            let too_many_damaged = GoodsReceipt::record(order.id(), &bins[0], None, vec![received(line, 1, i32::MAX)], &clerk, &conn);
+            assert!(matches!(too_many_damaged, Err(DomainError::Rejected(_))));
+
+            let at_limit = GoodsReceipt::record(order.id(), &bins[0], None, vec![received(line, 110, 0)], &clerk, &conn).unwrap();
+            assert_eq!(at_limit.order.status(), PurchaseOrderStatus::Received);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn shipping_notices_are_matched_by_sku_and_received_as_announced() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Dock]);
+            let soap = product(&conn, "SOAP");
+            product(&conn, "BRUSH");
+            let order = sent_order(&conn, &warehouse, &soap, 100);
+            let notice_line = |sku: &str| ShippingNoticeImportLine { sku: sku.to_string(), quantity: 40, lot_number: Some("l7".to_string()), expires_on: None };
+
+            let not_ordered = ShippingNoticeImport { reference: "DN-1".to_string(), expected_on: None, lines: vec![notice_line("BRUSH")] }
+                .import(&order, &conn);
+            assert!(matches!(not_ordered, Err(DomainError::Invalid(_))));
+
+            let notice = ShippingNoticeImport { reference: "DN-1".to_string(), expected_on: None, lines: vec![notice_line("SOAP")] }
+                .import(&order, &conn).unwrap()
+                .notice;
+            let lines = notice.expected_lines(&conn).unwrap();
+            let receipt = GoodsReceipt::record(order.id(), &bins[0], Some(notice.id()), lines, &Operator::new("clerk"), &conn).unwrap();
+
+            assert_eq!(receipt.order.status(), PurchaseOrderStatus::PartiallyReceived);
+            assert_eq!(ShippingNotice::find(notice.id(), &conn).unwrap().status(), NoticeStatus::Received);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use crate::purchasing::receiving::models::{GoodsReceipt, GoodsReceiptRequest, GoodsReceiptView, ReceivedLine, ShippingNotice, ShippingNoticeImport, ShippingNoticeView};
+use crate::purchasing::order::models::PurchaseOrder;
+use crate::location::bin::models::BinLocation;
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+/// Imports an advance shipping notice announcing a delivery against the order.
+#[post("/purchase-order/<id>/asn", format="application/json", data="<notice>")]
+pub fn import_notice(id: i32, notice: Json<ShippingNoticeImport>, conn: PostgresConnection) -> Result<Created<Json<ShippingNoticeView>>, ApiError> {
+    let order = PurchaseOrder::find(id, &*conn)?;
+    let imported = notice.into_inner().import(&order, &*conn)?;
+    Ok(Created(format!("/asn/{}", imported.notice.id()), Some(Json(imported))))
+}
+
+#[get("/purchase-order/<id>/asn")]
+pub fn list_notices(id: i32, conn: PostgresConnection) -> Result<Json<Vec<ShippingNotice>>, ApiError> {
+    let order = PurchaseOrder::find(id, &*conn)?;
+    Ok(Json(ShippingNotice::for_order(order.id(), &*conn)?))
+}
+
+#[get("/asn/<id>")]
+pub fn get_notice(id: i32, conn: PostgresConnection) -> Result<Json<ShippingNoticeView>, ApiError> {
+    let notice = ShippingNotice::find(id, &*conn)?;
+    Ok(Json(ShippingNoticeView::load(notice, &*conn)?))
+}
+
+/// Receives a delivery against the order, as announced by a notice if no lines are given.
+#[post("/purchase-order/<id>/receipt", format="application/json", data="<request>")]
+pub fn receive(id: i32, request: Json<GoodsReceiptRequest>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<GoodsReceiptView>>, ApiError> {
+    let operator = operator?;
+    let order = PurchaseOrder::find(id, &*conn)?;
+    let request = request.into_inner();
+    let bin = BinLocation::find(request.to_location_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Bin {} does not exist", request.to_location_id)))?;
+
+    let lines = match (request.lines.is_empty(), request.notice_id) {
+        (true, Some(notice)) => ShippingNotice::find(notice, &*conn)
+            .optional()?
+            .ok_or_else(|| ApiError::UnprocessableEntity(format!("Shipping notice {} does not exist", notice)))?
+            .expected_lines(&*conn)?,
+        _ => {
+            let order_lines = order.lines(&*conn)?;
+            let mut lines: Vec<ReceivedLine> = Vec::with_capacity(request.lines.len());
+            for line in request.lines {
+                let order_line = order_lines.iter()
+                    .find(|order_line| order_line.id() == line.purchase_order_line_id)
+                    .ok_or_else(|| ApiError::UnprocessableEntity(format!("Line {} is not a line of purchase order {}", line.purchase_order_line_id, id)))?;
+                let product = Product::find(order_line.product_id(), &*conn)?;
+                lines.push(line.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?);
+            }
+            lines
+        }
+    };
+
+    let received = GoodsReceipt::record(order.id(), &bin, request.notice_id, lines, &operator, &*conn)?;
+    Ok(Created(format!("/goods-receipt/{}", received.receipt.id()), Some(Json(received))))
+}
+
+#[get("/goods-receipt/<id>")]
+pub fn get_receipt(id: i32, conn: PostgresConnection) -> Result<Json<GoodsReceiptView>, ApiError> {
+    let receipt = GoodsReceipt::find(id, &*conn)?;
+    let order = PurchaseOrder::find(receipt.purchase_order_id(), &*conn)?;
+    Ok(Json(GoodsReceiptView::load(receipt, order, &*conn)?))
+}
@@ -0,0 +1,246 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::supplier;
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Bool, Integer, Nullable, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="supplier"]
+pub struct Supplier {
+    id: i32,
+    name: String,
+    contact_name: Option<String>,
+    contact_email: Option<String>,
+    lead_time_days: i32,
+    currency: String,
+    version: i32,
+    over_receipt_percent: i32,
+    under_receipt_percent: i32,
+    requires_inspection: bool
+}
+
+impl AsChangeset for Supplier {
+    type Target = supplier::table;
+    type Changeset = <(DieselEq<supplier::name, Bound<Text, String>>,
+                       DieselEq<supplier::contact_name, Bound<Nullable<Text>, Option<String>>>,
+                       DieselEq<supplier::contact_email, Bound<Nullable<Text>, Option<String>>>,
+                       DieselEq<supplier::lead_time_days, Bound<Integer, i32>>,
+                       DieselEq<supplier::currency, Bound<Text, String>>,
+                       DieselEq<supplier::over_receipt_percent, Bound<Integer, i32>>,
+                       DieselEq<supplier::under_receipt_percent, Bound<Integer, i32>>,
+                       DieselEq<supplier::requires_inspection, Bound<Bool, bool>>,
+                       DieselEq<supplier::version, DieselAdd<supplier::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            supplier::name.eq(self.name),
+            supplier::contact_name.eq(self.contact_name),
+            supplier::contact_email.eq(self.contact_email),
+            supplier::lead_time_days.eq(self.lead_time_days),
+            supplier::currency.eq(self.currency),
+            supplier::over_receipt_percent.eq(self.over_receipt_percent),
+            supplier::under_receipt_percent.eq(self.under_receipt_percent),
+            supplier::requires_inspection.eq(self.requires_inspection),
+            supplier::version.eq(supplier::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Supplier {
+
+    pub fn find(supplier_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Supplier, diesel::result::Error> {
+        use crate::schema::supplier::dsl::*;
+        supplier.find(supplier_id).first(conn)
+    }
+
+    pub fn all(conn: &impl Connection<Backend=Pg>) -> Result<Vec<Supplier>, diesel::result::Error> {
+        use crate::schema::supplier::dsl::*;
+        supplier.order((name.asc(), id.asc())).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Days between sending a purchase order and the goods arriving.
+    pub fn lead_time_days(&self) -> i32 {
+        self.lead_time_days
+    }
+
+    pub fn currency(&self) -> &str {
+        &self.currency
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Most that may be received for an order line, allowing for the supplier's over-delivery
+    /// tolerance. Computed in `i64`, which holds any order line at up to twice its quantity.
+    pub fn max_receivable(&self, ordered: i32) -> i64 {
+        let ordered = i64::from(ordered);
+        ordered + ordered * i64::from(self.over_receipt_percent) / 100
+    }
+
+    /// Everything received from the supplier is held for quality inspection before it can be used.
+    pub fn requires_inspection(&self) -> bool {
+        self.requires_inspection
+    }
+
+    /// Least that has to be received for an order line to count as fully delivered.
+    pub fn min_receivable(&self, ordered: i32) -> i64 {
+        let ordered = i64::from(ordered);
+        ordered - ordered * i64::from(self.under_receipt_percent) / 100
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Supplier>, diesel::result::Error> {
+        use crate::schema::supplier::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(supplier.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::supplier::dsl::*;
+        conn.transaction(|| {
+            delete(supplier.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of both `POST /supplier` and `PUT /supplier/<id>` requests.
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[table_name="supplier"]
+pub struct SupplierDetails {
+    pub name: String,
+    #[serde(default)]
+    pub contact_name: Option<String>,
+    #[serde(default)]
+    pub contact_email: Option<String>,
+    pub lead_time_days: i32,
+    /// ISO 4217 code of the currency the supplier invoices in, e.g. `EUR`.
+    pub currency: String,
+    /// Percentage by which a delivery may exceed the ordered quantity.
+    #[serde(default)]
+    pub over_receipt_percent: i32,
+    /// Percentage by which a delivery may fall short and still complete the order line.
+    #[serde(default)]
+    pub under_receipt_percent: i32,
+    #[serde(default)]
+    pub requires_inspection: bool
+}
+
+impl SupplierDetails {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err(String::from("Supplier name must not be empty"));
+        }
+        if self.lead_time_days < 0 {
+            return Err(format!("Lead time must not be negative, got {} days", self.lead_time_days));
+        }
+        let currency = self.currency.trim();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("Currency {} is not a three letter ISO 4217 code", self.currency));
+        }
+        if self.over_receipt_percent < 0 || self.over_receipt_percent > 100 {
+            return Err(format!("Over-receipt tolerance must be between 0% and 100%, got {}%", self.over_receipt_percent));
+        }
+        if self.under_receipt_percent < 0 || self.under_receipt_percent > 100 {
+            return Err(format!("Under-receipt tolerance must be between 0% and 100%, got {}%", self.under_receipt_percent));
+        }
+        Ok(())
+    }
+
+    fn normalized(self) -> SupplierDetails {
+        SupplierDetails {
+            name: self.name.trim().to_string(),
+            currency: self.currency.trim().to_uppercase(),
+            ..self
+        }
+    }
+
+    pub fn for_supplier(self, current: Supplier, expected_version: i32) -> Supplier {
+        let details = self.normalized();
+        Supplier {
+            name: details.name,
+            contact_name: details.contact_name,
+            contact_email: details.contact_email,
+            lead_time_days: details.lead_time_days,
+            currency: details.currency,
+            over_receipt_percent: details.over_receipt_percent,
+            under_receipt_percent: details.under_receipt_percent,
+            requires_inspection: details.requires_inspection,
+            version: expected_version,
+            ..current
+        }
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Supplier, diesel::result::Error> {
+        use crate::schema::supplier::dsl::*;
+        use crate::schema::supplier::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(supplier)
+                .values(self.normalized())
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::purchasing::supplier::models::{Supplier, SupplierDetails};
+
+    fn details(name: &str, currency: &str) -> SupplierDetails {
+        SupplierDetails { name: name.to_string(), contact_name: None, contact_email: None, lead_time_days: 7, currency: currency.to_string(), over_receipt_percent: 0, under_receipt_percent: 0, requires_inspection: false }
+    }
+
+    #[test]
+    fn currency_must_be_an_iso_code() {
+        assert!(details("Acme", "eur").validate().is_ok());
+        assert!(details("Acme", "EURO").validate().is_err());
+        assert!(details(" ", "EUR").validate().is_err());
+        assert!(SupplierDetails { lead_time_days: -1, ..details("Acme", "EUR") }.validate().is_err());
+        assert!(SupplierDetails { under_receipt_percent: 101, ..details("Acme", "EUR") }.validate().is_err());
+        assert!(SupplierDetails { over_receipt_percent: 101, ..details("Acme", "EUR") }.validate().is_err());
+    }
+
+    #[test]
+    fn tolerances_of_huge_order_lines_do_not_overflow() {
+        let supplier = Supplier {
+            id: 1, name: "Acme".to_string(), contact_name: None, contact_email: None, lead_time_days: 7,
+            currency: "EUR".to_string(), version: 0, over_receipt_percent: 100, under_receipt_percent: 100, requires_inspection: false
+        };
+        assert_eq!(supplier.max_receivable(i32::MAX), 2 * i64::from(i32::MAX));
+        assert_eq!(supplier.min_receivable(i32::MAX), 0);
+    }
+
+    #[test]
+    fn optimistically_locked_supplier_does_not_get_saved_on_conflict() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let saved = details("Acme", "eur").create(&conn).unwrap();
+            assert_eq!(saved.currency(), "EUR");
+            let stale = Supplier::find(saved.id(), &conn).unwrap();
+
+            let updated = details("Acme Ltd", "GBP").for_supplier(saved, 0).update(&conn).unwrap();
+            let stale_updated = details("Acme Inc", "USD").for_supplier(stale, 0).update(&conn).unwrap();
+
+            assert_eq!(updated.map(|s| (s.name, s.version)), Some(("Acme Ltd".to_string(), 1)));
+            assert_eq!(stale_updated, None);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,53 @@
+use crate::purchasing::supplier::models::{Supplier, SupplierDetails};
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/supplier", format="application/json", data="<supplier>")]
+pub fn post(supplier: Json<SupplierDetails>, conn: PostgresConnection) -> Result<Tagged<Created<Json<Supplier>>>, ApiError> {
+    let supplier = supplier.into_inner();
+    supplier.validate().map_err(ApiError::UnprocessableEntity)?;
+    let created = supplier.create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/supplier/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/supplier")]
+pub fn list(conn: PostgresConnection) -> Result<Json<Vec<Supplier>>, ApiError> {
+    Ok(Json(Supplier::all(&*conn)?))
+}
+
+#[get("/supplier/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<Supplier>>, ApiError> {
+    let supplier = Supplier::find(id, &*conn)?;
+    Ok(Tagged(supplier.version(), Json(supplier)))
+}
+
+#[put("/supplier/<id>", format="application/json", data="<details>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, details: Json<SupplierDetails>, conn: PostgresConnection) -> Result<Tagged<Json<Supplier>>, ApiError> {
+    let expected_version = if_match?.version();
+    let details = details.into_inner();
+    details.validate().map_err(ApiError::UnprocessableEntity)?;
+    let current = Supplier::find(id, &*conn)?;
+    match details.for_supplier(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(Supplier::find(id, &*conn), Supplier::version))
+    }
+}
+
+#[delete("/supplier/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let supplier = Supplier::find(id, &*conn)?;
+    if supplier.version() != expected_version {
+        return Err(ApiError::precondition_failed(supplier.version(), &supplier));
+    }
+    match supplier.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(Supplier::find(id, &*conn), Supplier::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
@@ -0,0 +1,285 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use diesel::{update, delete};
+use crate::schema::{customer, customer_address};
+use diesel::query_builder::AsChangeset;
+use diesel::sql_types::{Integer, Nullable, Text};
+use diesel::expression::{operators::Eq as DieselEq, ops::Add as DieselAdd, bound::Bound};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="customer"]
+pub struct Customer {
+    id: i32,
+    name: String,
+    email: Option<String>,
+    version: i32
+}
+
+impl AsChangeset for Customer {
+    type Target = customer::table;
+    type Changeset = <(DieselEq<customer::name, Bound<Text, String>>,
+                       DieselEq<customer::email, Bound<Nullable<Text>, Option<String>>>,
+                       DieselEq<customer::version, DieselAdd<customer::version, Bound<Integer, i32>>>) as AsChangeset>::Changeset;
+
+    fn as_changeset(self) -> Self::Changeset {
+        (
+            customer::name.eq(self.name),
+            customer::email.eq(self.email),
+            customer::version.eq(customer::version + 1)
+        ).as_changeset()
+    }
+}
+
+impl Customer {
+
+    pub fn find(customer_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Customer, diesel::result::Error> {
+        use crate::schema::customer::dsl::*;
+        customer.find(customer_id).first(conn)
+    }
+
+    pub fn all(conn: &impl Connection<Backend=Pg>) -> Result<Vec<Customer>, diesel::result::Error> {
+        use crate::schema::customer::dsl::*;
+        customer.order((name.asc(), id.asc())).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    /// Shipping addresses of the customer, the default one first.
+    pub fn addresses(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Address>, diesel::result::Error> {
+        use crate::schema::customer_address::dsl::*;
+        customer_address
+            .filter(customer_id.eq(self.id))
+            .order((is_default.desc(), id.asc()))
+            .load(conn)
+    }
+
+    pub fn update(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<Customer>, diesel::result::Error> {
+        use crate::schema::customer::dsl::*;
+        conn.transaction(|| {
+            let updated_row = update(customer.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set(self)
+                .get_result(conn);
+
+            match updated_row {
+                Ok(e) => Ok(Some(e)),
+                Err(diesel::result::Error::NotFound) => Ok(None),
+                Err(e) => Err(e)
+            }
+        })
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::customer::dsl::*;
+        conn.transaction(|| {
+            delete(customer.filter(id.eq(self.id).and(version.eq(self.version)))).execute(conn)
+        })
+    }
+}
+
+/// Body of both `POST /customer` and `PUT /customer/<id>` requests.
+#[derive(Debug, Clone, Insertable, Serialize, Deserialize)]
+#[table_name="customer"]
+pub struct CustomerDetails {
+    pub name: String,
+    #[serde(default)]
+    pub email: Option<String>
+}
+
+impl CustomerDetails {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err(String::from("Customer name must not be empty"));
+        }
+        match self.email {
+            Some(ref email) if !email.contains('@') => Err(format!("{} is not an email address", email)),
+            _ => Ok(())
+        }
+    }
+
+    fn normalized(self) -> CustomerDetails {
+        CustomerDetails {
+            name: self.name.trim().to_string(),
+            email: self.email.map(|email| email.trim().to_lowercase())
+        }
+    }
+
+    pub fn for_customer(self, current: Customer, expected_version: i32) -> Customer {
+        let details = self.normalized();
+        Customer {
+            name: details.name,
+            email: details.email,
+            version: expected_version,
+            ..current
+        }
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Customer, diesel::result::Error> {
+        use crate::schema::customer::dsl::*;
+        use crate::schema::customer::all_columns;
+        conn.transaction(|| {
+            diesel::insert_into(customer)
+                .values(self.normalized())
+                .returning(all_columns)
+                .get_result(conn)
+        })
+    }
+}
+
+/// A customer with the addresses goods can be shipped to.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CustomerView {
+    #[serde(flatten)]
+    pub customer: Customer,
+    pub addresses: Vec<Address>
+}
+
+impl CustomerView {
+    pub fn load(customer: Customer, conn: &impl Connection<Backend=Pg>) -> Result<CustomerView, diesel::result::Error> {
+        let addresses = customer.addresses(conn)?;
+        Ok(CustomerView { customer, addresses })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="customer_address"]
+pub struct Address {
+    id: i32,
+    customer_id: i32,
+    recipient: String,
+    street: String,
+    postal_code: String,
+    city: String,
+    /// ISO 3166 code of the country, e.g. `DE`.
+    country: String,
+    is_default: bool
+}
+
+impl Address {
+
+    pub fn find(address_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Address, diesel::result::Error> {
+        use crate::schema::customer_address::dsl::*;
+        customer_address.find(address_id).first(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn customer_id(&self) -> i32 {
+        self.customer_id
+    }
+
+    pub fn is_default(&self) -> bool {
+        self.is_default
+    }
+
+    pub fn delete(self, conn: &impl Connection<Backend=Pg>) -> Result<usize, diesel::result::Error> {
+        use crate::schema::customer_address::dsl::*;
+        conn.transaction(|| {
+            delete(customer_address.filter(id.eq(self.id))).execute(conn)
+        })
+    }
+}
+
+/// Body of a `POST /customer/<id>/address` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddressDefinition {
+    pub recipient: String,
+    pub street: String,
+    pub postal_code: String,
+    pub city: String,
+    pub country: String,
+    /// Makes this the address orders ship to unless they say otherwise.
+    #[serde(default)]
+    pub is_default: bool
+}
+
+impl AddressDefinition {
+    pub fn for_customer(self, customer: &Customer) -> Result<NewAddress, String> {
+        let fields = [&self.recipient, &self.street, &self.postal_code, &self.city];
+        if fields.iter().any(|field| field.trim().is_empty()) {
+            return Err(String::from("Recipient, street, postal code and city must not be empty"));
+        }
+        let country = self.country.trim();
+        if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("Country {} is not a two letter ISO 3166 code", self.country));
+        }
+        Ok(NewAddress {
+            customer_id: customer.id(),
+            recipient: self.recipient.trim().to_string(),
+            street: self.street.trim().to_string(),
+            postal_code: self.postal_code.trim().to_uppercase(),
+            city: self.city.trim().to_string(),
+            country: country.to_uppercase(),
+            is_default: self.is_default
+        })
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="customer_address"]
+pub struct NewAddress {
+    customer_id: i32,
+    recipient: String,
+    street: String,
+    postal_code: String,
+    city: String,
+    country: String,
+    is_default: bool
+}
+
+impl NewAddress {
+    /// Saves the address, which becomes the default if it asks to or is the customer's first.
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<Address, diesel::result::Error> {
+        use crate::schema::customer_address::dsl::*;
+        conn.transaction(|| {
+            let first = customer_address.filter(customer_id.eq(self.customer_id)).count().get_result::<i64>(conn)? == 0;
+            if self.is_default {
+                update(customer_address.filter(customer_id.eq(self.customer_id)))
+                    .set(is_default.eq(false))
+                    .execute(conn)?;
+            }
+            diesel::insert_into(customer_address)
+                .values(NewAddress { is_default: self.is_default || first, ..self })
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::sales::customer::models::{AddressDefinition, Customer, CustomerDetails};
+
+    fn address(city: &str, is_default: bool) -> AddressDefinition {
+        AddressDefinition {
+            recipient: "Receiving".to_string(), street: "Main Street 1".to_string(), postal_code: "10115".to_string(),
+            city: city.to_string(), country: "de".to_string(), is_default
+        }
+    }
+
+    #[test]
+    fn customers_have_exactly_one_default_address() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let customer = CustomerDetails { name: "Corner Shop".to_string(), email: None }.create(&conn).unwrap();
+            assert!(AddressDefinition { country: "DEU".to_string(), ..address("Berlin", false) }.for_customer(&customer).is_err());
+
+            let first = address("Berlin", false).for_customer(&customer).unwrap().create(&conn).unwrap();
+            assert!(first.is_default());
+            let second = address("Hamburg", true).for_customer(&customer).unwrap().create(&conn).unwrap();
+
+            let addresses = Customer::find(customer.id(), &conn).unwrap().addresses(&conn).unwrap();
+            assert_eq!(addresses.iter().map(|a| (a.id(), a.is_default())).collect::<Vec<_>>(), vec![(second.id(), true), (first.id(), false)]);
+            assert_eq!(addresses[0].country, "DE");
+            Ok(())
+        })
+    }
+}
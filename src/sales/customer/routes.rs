@@ -0,0 +1,73 @@
+use crate::sales::customer::models::{Address, AddressDefinition, Customer, CustomerDetails, CustomerView};
+
+use rocket_contrib::json::Json;
+use rocket::http::Status;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+
+
+#[post("/customer", format="application/json", data="<customer>")]
+pub fn post(customer: Json<CustomerDetails>, conn: PostgresConnection) -> Result<Tagged<Created<Json<Customer>>>, ApiError> {
+    let customer = customer.into_inner();
+    customer.validate().map_err(ApiError::UnprocessableEntity)?;
+    let created = customer.create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/customer/{}", created.id()), Some(Json(created)))))
+}
+
+#[get("/customer")]
+pub fn list(conn: PostgresConnection) -> Result<Json<Vec<Customer>>, ApiError> {
+    Ok(Json(Customer::all(&*conn)?))
+}
+
+#[get("/customer/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<CustomerView>>, ApiError> {
+    let customer = Customer::find(id, &*conn)?;
+    Ok(Tagged(customer.version(), Json(CustomerView::load(customer, &*conn)?)))
+}
+
+#[put("/customer/<id>", format="application/json", data="<details>")]
+pub fn put(id: i32, if_match: Result<IfMatch, ApiError>, details: Json<CustomerDetails>, conn: PostgresConnection) -> Result<Tagged<Json<Customer>>, ApiError> {
+    let expected_version = if_match?.version();
+    let details = details.into_inner();
+    details.validate().map_err(ApiError::UnprocessableEntity)?;
+    let current = Customer::find(id, &*conn)?;
+    match details.for_customer(current, expected_version).update(&*conn)? {
+        Some(updated) => Ok(Tagged(updated.version(), Json(updated))),
+        None => Err(ApiError::precondition_failed_with(Customer::find(id, &*conn), Customer::version))
+    }
+}
+
+#[delete("/customer/<id>")]
+pub fn delete(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let expected_version = if_match?.version();
+    let customer = Customer::find(id, &*conn)?;
+    if customer.version() != expected_version {
+        return Err(ApiError::precondition_failed(customer.version(), &customer));
+    }
+    match customer.delete(&*conn)? {
+        0 => Err(ApiError::precondition_failed_with(Customer::find(id, &*conn), Customer::version)),
+        _ => Ok(Status::NoContent)
+    }
+}
+
+#[post("/customer/<id>/address", format="application/json", data="<address>")]
+pub fn post_address(id: i32, address: Json<AddressDefinition>, conn: PostgresConnection) -> Result<Created<Json<Address>>, ApiError> {
+    let customer = Customer::find(id, &*conn)?;
+    let created = address.into_inner()
+        .for_customer(&customer)
+        .map_err(ApiError::UnprocessableEntity)?
+        .create(&*conn)?;
+    Ok(Created(format!("/customer/{}", id), Some(Json(created))))
+}
+
+#[delete("/customer/<id>/address/<address_id>")]
+pub fn delete_address(id: i32, address_id: i32, conn: PostgresConnection) -> Result<Status, ApiError> {
+    let address = Address::find(address_id, &*conn)?;
+    if address.customer_id() != id {
+        return Err(ApiError::NotFound);
+    }
+    address.delete(&*conn)?;
+    Ok(Status::NoContent)
+}
@@ -0,0 +1,4 @@
+pub mod customer;
+pub mod order;
+pub mod wave;
+pub mod picking;
\ No newline at end of file
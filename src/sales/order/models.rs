@@ -0,0 +1,595 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use diesel::update;
+use crate::schema::{sales_order, sales_order_allocation, sales_order_line};
+use crate::sales::customer::models::{Address, Customer};
+use crate::location::bin::models::{BinLocation, PICKABLE};
+use crate::location::warehouse::models::Warehouse;
+use crate::inventory::balance::models::{AllocationStrategy, InventoryBalance, StockChange, StockError};
+use crate::product::models::Product;
+use crate::sales::picking::models::PickList;
+use crate::operator::Operator;
+use crate::domain::DomainError;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    pub enum SalesOrderStatus {
+        Open => "open",
+        Backordered => "backordered",
+        Allocated => "allocated",
+        Released => "released",
+        Cancelled => "cancelled"
+    }
+}
+
+impl SalesOrderStatus {
+    /// Orders are allocated while open or backordered, backorders are allocated again once stock
+    /// arrives. Fully allocated orders are released for picking in a wave. Open, backordered,
+    /// allocated and released orders can be cancelled, cancelled ones stay cancelled.
+    pub fn can_become(self, next: SalesOrderStatus) -> bool {
+        use SalesOrderStatus::*;
+        matches!((self, next),
+            (Open, Allocated) | (Open, Backordered) | (Open, Cancelled)
+            | (Backordered, Backordered) | (Backordered, Allocated) | (Backordered, Cancelled)
+            | (Allocated, Released) | (Allocated, Cancelled)
+            | (Released, Cancelled))
+    }
+}
+
+/// Header of an order a customer placed for shipment from one warehouse. Its version is
+/// incremented by every change to the header, its lines or their allocations.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="sales_order"]
+pub struct SalesOrder {
+    id: i32,
+    customer_id: i32,
+    warehouse_id: i32,
+    shipping_address_id: i32,
+    reference: Option<String>,
+    status: SalesOrderStatus,
+    /// Strategy used when an allocation request does not name one.
+    allocation_strategy: AllocationStrategy,
+    created_by: String,
+    created_at: NaiveDateTime,
+    version: i32,
+    carrier: Option<String>,
+    /// Latest time the carrier collects the order at.
+    cutoff_at: Option<NaiveDateTime>,
+    /// Orders with a higher priority are released first.
+    priority: i32,
+    /// Wave the order was released for picking in.
+    wave_id: Option<i32>
+}
+
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="sales_order_line"]
+pub struct SalesOrderLine {
+    id: i32,
+    sales_order_id: i32,
+    product_id: i32,
+    quantity: i32,
+    allocated_quantity: i32
+}
+
+impl SalesOrderLine {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn product_id(&self) -> i32 {
+        self.product_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+
+    pub fn allocated_quantity(&self) -> i32 {
+        self.allocated_quantity
+    }
+
+    /// Quantity still waiting for stock.
+    pub fn backordered(&self) -> i32 {
+        self.quantity - self.allocated_quantity
+    }
+
+    pub fn allocations(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SalesOrderAllocation>, diesel::result::Error> {
+        use crate::schema::sales_order_allocation::dsl::*;
+        sales_order_allocation.filter(sales_order_line_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+}
+
+/// Stock of one bin and lot reserved for a sales order line.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="sales_order_allocation"]
+pub struct SalesOrderAllocation {
+    id: i32,
+    sales_order_line_id: i32,
+    bin_location_id: i32,
+    lot_id: Option<i32>,
+    quantity: i32
+}
+
+impl SalesOrderAllocation {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn sales_order_line_id(&self) -> i32 {
+        self.sales_order_line_id
+    }
+
+    pub fn bin_location_id(&self) -> i32 {
+        self.bin_location_id
+    }
+
+    pub fn lot_id(&self) -> Option<i32> {
+        self.lot_id
+    }
+
+    pub fn quantity(&self) -> i32 {
+        self.quantity
+    }
+}
+
+impl SalesOrder {
+
+    pub fn find(order_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<SalesOrder, diesel::result::Error> {
+        use crate::schema::sales_order::dsl::*;
+        sales_order.find(order_id).first(conn)
+    }
+
+    /// Orders, newest first, optionally only those in `order_status` or placed by `customer`.
+    pub fn list(order_status: Option<SalesOrderStatus>, customer: Option<i32>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SalesOrder>, diesel::result::Error> {
+        use crate::schema::sales_order::dsl::*;
+        let mut query = sales_order.into_boxed();
+        if let Some(order_status) = order_status {
+            query = query.filter(status.eq(order_status));
+        }
+        if let Some(customer) = customer {
+            query = query.filter(customer_id.eq(customer));
+        }
+        query.order(id.desc()).load(conn)
+    }
+
+    /// Fully allocated orders of `warehouse` not released in a wave yet, locked until the
+    /// surrounding transaction ends.
+    pub fn releasable(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SalesOrder>, diesel::result::Error> {
+        use crate::schema::sales_order::dsl::*;
+        sales_order
+            .filter(warehouse_id.eq(warehouse).and(status.eq(SalesOrderStatus::Allocated)).and(wave_id.is_null()))
+            .order(id.asc())
+            .for_update()
+            .load(conn)
+    }
+
+    /// Orders released in `wave`.
+    pub fn in_wave(wave: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SalesOrder>, diesel::result::Error> {
+        use crate::schema::sales_order::dsl::*;
+        sales_order.filter(wave_id.eq(wave)).order(id.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn customer_id(&self) -> i32 {
+        self.customer_id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn shipping_address_id(&self) -> i32 {
+        self.shipping_address_id
+    }
+
+    pub fn status(&self) -> SalesOrderStatus {
+        self.status
+    }
+
+    pub fn allocation_strategy(&self) -> AllocationStrategy {
+        self.allocation_strategy
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn carrier(&self) -> Option<&str> {
+        self.carrier.as_deref()
+    }
+
+    pub fn cutoff_at(&self) -> Option<NaiveDateTime> {
+        self.cutoff_at
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn wave_id(&self) -> Option<i32> {
+        self.wave_id
+    }
+
+    pub fn lines(&self, conn: &impl Connection<Backend=Pg>) -> Result<Vec<SalesOrderLine>, diesel::result::Error> {
+        use crate::schema::sales_order_line::dsl::*;
+        sales_order_line.filter(sales_order_id.eq(self.id)).order(id.asc()).load(conn)
+    }
+
+    /// Orders `quantity` base units of `product`.
+    pub fn add_line(self, product: i32, quantity: i32, conn: &impl Connection<Backend=Pg>) -> Result<Option<SalesOrder>, DomainError> {
+        if quantity <= 0 {
+            return Err(DomainError::Invalid(String::from("Sales order lines need a positive quantity")));
+        }
+        let order_id = self.id;
+        self.revise_open(|conn| {
+            diesel::insert_into(sales_order_line::table)
+                .values((
+                    sales_order_line::sales_order_id.eq(order_id),
+                    sales_order_line::product_id.eq(product),
+                    sales_order_line::quantity.eq(quantity)
+                ))
+                .execute(conn)?;
+            Ok(())
+        }, conn)
+    }
+
+    pub fn remove_line(self, line_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Option<SalesOrder>, DomainError> {
+        let order_id = self.id;
+        self.revise_open(|conn| {
+            use crate::schema::sales_order_line::dsl::*;
+            match diesel::delete(sales_order_line.filter(id.eq(line_id).and(sales_order_id.eq(order_id)))).execute(conn)? {
+                0 => Err(diesel::result::Error::NotFound.into()),
+                _ => Ok(())
+            }
+        }, conn)
+    }
+
+    /// Allocates what is still outstanding on every line from the storage and pick face bins in
+    /// the order of `strategy`, or of the order's own strategy if none is given. When stock is
+    /// short the order is backordered with whatever could be allocated, unless `allow_partial`
+    /// is false, in which case nothing is allocated and the shortage is returned. Returns `None`
+    /// if the order changed meanwhile.
+    pub fn allocate(self, strategy: Option<AllocationStrategy>, allow_partial: bool, conn: &impl Connection<Backend=Pg>) -> Result<Option<SalesOrder>, DomainError> {
+        use crate::schema::sales_order::dsl::*;
+        if !self.status.can_become(SalesOrderStatus::Allocated) {
+            return Err(DomainError::Rejected(format!("Sales order {} is {} and can not be allocated", self.id, self.status)));
+        }
+        let strategy = strategy.unwrap_or(self.allocation_strategy);
+        conn.transaction(|| {
+            let lines = self.lines(conn)?;
+            if lines.is_empty() {
+                return Err(DomainError::Rejected(format!("Sales order {} has no lines to allocate", self.id)));
+            }
+            let claimed = update(sales_order.filter(id.eq(self.id).and(version.eq(self.version)).and(status.eq(self.status))))
+                .set(version.eq(version + 1))
+                .get_result::<SalesOrder>(conn)
+                .optional()?;
+            if claimed.is_none() {
+                return Ok(None);
+            }
+            let mut complete = true;
+            for line in lines.into_iter().filter(|line| line.backordered() > 0) {
+                let product = Product::find(line.product_id, conn)?;
+                let allocations = InventoryBalance::allocate_by(&product, self.warehouse_id, &PICKABLE, strategy, line.backordered(), conn)?;
+                let allocated: i32 = allocations.iter().map(|allocation| allocation.quantity).sum();
+                if allocated < line.backordered() {
+                    if !allow_partial {
+                        return Err(StockError::Shortage { product_id: product.id(), warehouse_id: self.warehouse_id, available: allocated }.into());
+                    }
+                    complete = false;
+                }
+                for allocation in allocations {
+                    diesel::insert_into(sales_order_allocation::table)
+                        .values((
+                            sales_order_allocation::sales_order_line_id.eq(line.id),
+                            sales_order_allocation::bin_location_id.eq(allocation.bin_location_id),
+                            sales_order_allocation::lot_id.eq(allocation.lot_id),
+                            sales_order_allocation::quantity.eq(allocation.quantity)
+                        ))
+                        .execute(conn)?;
+                }
+                update(sales_order_line::table.find(line.id))
+                    .set(sales_order_line::allocated_quantity.eq(sales_order_line::allocated_quantity + allocated))
+                    .execute(conn)?;
+            }
+            let next = if complete { SalesOrderStatus::Allocated } else { SalesOrderStatus::Backordered };
+            Ok(Some(update(sales_order.find(self.id)).set(status.eq(next)).get_result(conn)?))
+        })
+    }
+
+    /// Cancels the order and releases all stock allocated to it. A released order is taken out
+    /// of its wave and off its pick lists. Returns `None` if the order changed meanwhile.
+    pub fn cancel(self, conn: &impl Connection<Backend=Pg>) -> Result<Option<SalesOrder>, DomainError> {
+        use crate::schema::sales_order::dsl::*;
+        if !self.status.can_become(SalesOrderStatus::Cancelled) {
+            return Err(DomainError::Rejected(format!("Sales order {} is {} and can not be cancelled", self.id, self.status)));
+        }
+        conn.transaction(|| {
+            let cancelled = update(sales_order.filter(id.eq(self.id).and(version.eq(self.version))))
+                .set((status.eq(SalesOrderStatus::Cancelled), wave_id.eq(None::<i32>), version.eq(version + 1)))
+                .get_result::<SalesOrder>(conn)
+                .optional()?;
+            if cancelled.is_some() {
+                if let Some(wave) = self.wave_id {
+                    PickList::withdraw(wave, self.id, conn)?;
+                }
+                for line in self.lines(conn)? {
+                    for allocation in line.allocations(conn)? {
+                        let bin = BinLocation::find(allocation.bin_location_id, conn)?;
+                        InventoryBalance::apply_to_lot(line.product_id, allocation.lot_id, &bin, StockChange::allocated(-allocation.quantity), conn)?;
+                    }
+                    diesel::delete(sales_order_allocation::table.filter(sales_order_allocation::sales_order_line_id.eq(line.id))).execute(conn)?;
+                    update(sales_order_line::table.find(line.id))
+                        .set(sales_order_line::allocated_quantity.eq(0))
+                        .execute(conn)?;
+                }
+            }
+            Ok(cancelled)
+        })
+    }
+
+    /// Releases the allocated order for picking in `wave`. Returns `None` if the order changed
+    /// meanwhile.
+    pub fn release(self, wave: i32, conn: &impl Connection<Backend=Pg>) -> Result<Option<SalesOrder>, DomainError> {
+        use crate::schema::sales_order::dsl::*;
+        if !self.status.can_become(SalesOrderStatus::Released) {
+            return Err(DomainError::Rejected(format!("Sales order {} is {} and can not be released", self.id, self.status)));
+        }
+        Ok(update(sales_order.filter(id.eq(self.id).and(version.eq(self.version)).and(status.eq(SalesOrderStatus::Allocated))))
+            .set((status.eq(SalesOrderStatus::Released), wave_id.eq(wave), version.eq(version + 1)))
+            .get_result(conn)
+            .optional()?)
+    }
+
+    /// Applies `change` to the lines of an open order and increments the header's version.
+    /// Returns `None` without applying it if the order changed meanwhile.
+    fn revise_open<C, F>(self, change: F, conn: &C) -> Result<Option<SalesOrder>, DomainError>
+        where C: Connection<Backend=Pg>, F: FnOnce(&C) -> Result<(), DomainError> {
+        use crate::schema::sales_order::dsl::*;
+        if self.status != SalesOrderStatus::Open {
+            return Err(DomainError::Rejected(format!("Lines of sales order {} can only be changed while it is open", self.id)));
+        }
+        conn.transaction(|| {
+            let revised = update(sales_order.filter(id.eq(self.id).and(version.eq(self.version)).and(status.eq(SalesOrderStatus::Open))))
+                .set(version.eq(version + 1))
+                .get_result::<SalesOrder>(conn)
+                .optional()?;
+            if revised.is_some() {
+                change(conn)?;
+            }
+            Ok(revised)
+        })
+    }
+}
+
+/// A line with the quantity still waiting for stock and where the rest was allocated.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SalesOrderLineView {
+    #[serde(flatten)]
+    pub line: SalesOrderLine,
+    pub backordered_quantity: i32,
+    pub allocations: Vec<SalesOrderAllocation>
+}
+
+/// An order with its lines and their allocations.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct SalesOrderView {
+    #[serde(flatten)]
+    pub order: SalesOrder,
+    pub lines: Vec<SalesOrderLineView>
+}
+
+impl SalesOrderView {
+    pub fn load(order: SalesOrder, conn: &impl Connection<Backend=Pg>) -> Result<SalesOrderView, diesel::result::Error> {
+        let lines = order.lines(conn)?
+            .into_iter()
+            .map(|line| Ok(SalesOrderLineView {
+                backordered_quantity: line.backordered(),
+                allocations: line.allocations(conn)?,
+                line
+            }))
+            .collect::<Result<Vec<_>, diesel::result::Error>>()?;
+        Ok(SalesOrderView { order, lines })
+    }
+}
+
+/// Body of a `POST /sales-order` request. Without an address the order ships to the customer's
+/// default address.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SalesOrderRequest {
+    pub customer_id: i32,
+    pub warehouse_id: i32,
+    #[serde(default)]
+    pub shipping_address_id: Option<i32>,
+    #[serde(default)]
+    pub allocation_strategy: Option<AllocationStrategy>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub carrier: Option<String>,
+    #[serde(default)]
+    pub cutoff_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub priority: i32
+}
+
+/// Body of a `POST /sales-order/<id>/line` request.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SalesOrderLineDefinition {
+    pub product_id: i32,
+    pub quantity: crate::product::packaging::models::Quantity
+}
+
+/// Body of a `POST /sales-order/<id>/allocate` request, by default the order's own strategy is
+/// used and short lines are backordered.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AllocationRequest {
+    #[serde(default)]
+    pub strategy: Option<AllocationStrategy>,
+    #[serde(default = "default_allow_partial")]
+    pub allow_partial: bool
+}
+
+fn default_allow_partial() -> bool {
+    true
+}
+
+impl Default for AllocationRequest {
+    fn default() -> Self {
+        AllocationRequest { strategy: None, allow_partial: default_allow_partial() }
+    }
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="sales_order"]
+pub struct NewSalesOrder {
+    customer_id: i32,
+    warehouse_id: i32,
+    shipping_address_id: i32,
+    reference: Option<String>,
+    allocation_strategy: AllocationStrategy,
+    created_by: String,
+    carrier: Option<String>,
+    cutoff_at: Option<NaiveDateTime>,
+    priority: i32
+}
+
+impl NewSalesOrder {
+    /// An open order allocated First-Expired-First-Out unless another strategy is chosen.
+    pub fn new(customer: &Customer, address: &Address, warehouse: &Warehouse, operator: &Operator) -> Result<NewSalesOrder, String> {
+        if address.customer_id() != customer.id() {
+            return Err(format!("Address {} does not belong to customer {}", address.id(), customer.id()));
+        }
+        Ok(NewSalesOrder {
+            customer_id: customer.id(),
+            warehouse_id: warehouse.id(),
+            shipping_address_id: address.id(),
+            reference: None,
+            allocation_strategy: AllocationStrategy::Fefo,
+            created_by: operator.name().to_string(),
+            carrier: None,
+            cutoff_at: None,
+            priority: 0
+        })
+    }
+
+    pub fn reference(self, reference: Option<String>) -> NewSalesOrder {
+        NewSalesOrder { reference: reference.map(|reference| reference.trim().to_string()).filter(|reference| !reference.is_empty()), ..self }
+    }
+
+    pub fn allocation_strategy(self, strategy: Option<AllocationStrategy>) -> NewSalesOrder {
+        NewSalesOrder { allocation_strategy: strategy.unwrap_or(self.allocation_strategy), ..self }
+    }
+
+    /// Ships the order with `carrier`, which collects it no later than `cutoff_at`.
+    pub fn shipped_by(self, carrier: Option<String>, cutoff_at: Option<NaiveDateTime>) -> NewSalesOrder {
+        NewSalesOrder { carrier: carrier.map(|carrier| carrier.trim().to_string()).filter(|carrier| !carrier.is_empty()), cutoff_at, ..self }
+    }
+
+    pub fn priority(self, priority: i32) -> NewSalesOrder {
+        NewSalesOrder { priority, ..self }
+    }
+
+    pub fn create(self, conn: &impl Connection<Backend=Pg>) -> Result<SalesOrder, diesel::result::Error> {
+        use crate::schema::sales_order::dsl::*;
+        conn.transaction(|| {
+            diesel::insert_into(sales_order)
+                .values(self)
+                .get_result(conn)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange, StockError};
+    use crate::sales::customer::models::{AddressDefinition, CustomerDetails};
+    use crate::sales::order::models::{NewSalesOrder, SalesOrderStatus, SalesOrderView};
+    use crate::domain::DomainError;
+    use crate::operator::Operator;
+
+    #[test]
+    fn backorders_can_be_allocated_again_until_cancelled() {
+        use SalesOrderStatus::*;
+        assert!(Open.can_become(Backordered));
+        assert!(Backordered.can_become(Allocated));
+        assert!(!Allocated.can_become(Backordered));
+        assert!(Released.can_become(Cancelled));
+        assert!(!Cancelled.can_become(Allocated));
+    }
+
+    #[test]
+    fn short_lines_are_backordered_and_filled_by_a_later_allocation() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Reserve, LocationType::Reserve]);
+            let soap = product(&conn, "SOAP");
+            let customer = CustomerDetails { name: "Corner Shop".to_string(), email: None }.create(&conn).unwrap();
+            let address = AddressDefinition {
+                recipient: "Receiving".to_string(), street: "Main Street 1".to_string(), postal_code: "10115".to_string(),
+                city: "Berlin".to_string(), country: "DE".to_string(), is_default: true
+            }.for_customer(&customer).unwrap().create(&conn).unwrap();
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(6), &conn).unwrap();
+
+            let order = NewSalesOrder::new(&customer, &address, &warehouse, &Operator::new("sales")).unwrap()
+                .create(&conn).unwrap()
+                .add_line(soap.id(), 10, &conn).unwrap().unwrap();
+
+            assert!(matches!(order.clone().add_line(soap.id(), 0, &conn), Err(DomainError::Invalid(_))));
+            let refused = order.clone().allocate(None, false, &conn);
+            assert!(matches!(refused, Err(DomainError::Stock(StockError::Shortage { available: 6, .. }))));
+            assert_eq!(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0].allocated(), 0);
+
+            let backordered = order.allocate(None, true, &conn).unwrap().unwrap();
+            assert_eq!(backordered.status(), SalesOrderStatus::Backordered);
+            assert!(matches!(backordered.clone().add_line(soap.id(), 1, &conn), Err(DomainError::Rejected(_))));
+            let view = SalesOrderView::load(backordered.clone(), &conn).unwrap();
+            assert_eq!((view.lines[0].line.allocated_quantity(), view.lines[0].backordered_quantity), (6, 4));
+
+            InventoryBalance::apply(soap.id(), &bins[1], StockChange::on_hand(5), &conn).unwrap();
+            let allocated = backordered.allocate(None, true, &conn).unwrap().unwrap();
+            assert_eq!(allocated.status(), SalesOrderStatus::Allocated);
+            let view = SalesOrderView::load(allocated.clone(), &conn).unwrap();
+            let taken: Vec<_> = view.lines[0].allocations.iter().map(|a| (a.bin_location_id(), a.quantity())).collect();
+            assert_eq!(taken, vec![(bins[0].id(), 6), (bins[1].id(), 4)]);
+
+            allocated.cancel(&conn).unwrap().unwrap();
+            let still_allocated: i32 = InventoryBalance::for_warehouse(warehouse.id(), &conn).unwrap().iter().map(|b| b.allocated()).sum();
+            assert_eq!(still_allocated, 0);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn only_stock_in_storage_and_pick_faces_is_allocated() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::Returns, LocationType::Dock, LocationType::Staging, LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let customer = CustomerDetails { name: "Corner Shop".to_string(), email: None }.create(&conn).unwrap();
+            let address = AddressDefinition {
+                recipient: "Receiving".to_string(), street: "Main Street 1".to_string(), postal_code: "10115".to_string(),
+                city: "Berlin".to_string(), country: "DE".to_string(), is_default: true
+            }.for_customer(&customer).unwrap().create(&conn).unwrap();
+            for bin in &bins {
+                InventoryBalance::apply(soap.id(), bin, StockChange::on_hand(5), &conn).unwrap();
+            }
+
+            let order = NewSalesOrder::new(&customer, &address, &warehouse, &Operator::new("sales")).unwrap()
+                .create(&conn).unwrap()
+                .add_line(soap.id(), 10, &conn).unwrap().unwrap()
+                .allocate(None, true, &conn).unwrap().unwrap();
+            assert_eq!(order.status(), SalesOrderStatus::Backordered);
+            let view = SalesOrderView::load(order, &conn).unwrap();
+            let taken: Vec<_> = view.lines[0].allocations.iter().map(|a| (a.bin_location_id(), a.quantity())).collect();
+            assert_eq!(taken, vec![(bins[3].id(), 5)]);
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,122 @@
+use crate::sales::order::models::{AllocationRequest, NewSalesOrder, SalesOrder, SalesOrderLineDefinition, SalesOrderRequest, SalesOrderStatus, SalesOrderView};
+use crate::sales::customer::models::{Address, Customer};
+use crate::location::warehouse::models::Warehouse;
+use crate::product::models::Product;
+use crate::product::packaging::models::PackagingHierarchy;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use diesel::prelude::*;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::etag::{IfMatch, Tagged};
+use crate::operator::Operator;
+use diesel::pg::Pg;
+
+
+#[post("/sales-order", format="application/json", data="<request>")]
+pub fn post(request: Json<SalesOrderRequest>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Tagged<Created<Json<SalesOrderView>>>, ApiError> {
+    let operator = operator?;
+    let request = request.into_inner();
+    let customer = Customer::find(request.customer_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Customer {} does not exist", request.customer_id)))?;
+    let warehouse = Warehouse::find(request.warehouse_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Warehouse {} does not exist", request.warehouse_id)))?;
+    let address = match request.shipping_address_id {
+        Some(address_id) => Address::find(address_id, &*conn)
+            .optional()?
+            .ok_or_else(|| ApiError::UnprocessableEntity(format!("Address {} does not exist", address_id)))?,
+        None => customer.addresses(&*conn)?
+            .into_iter()
+            .find(Address::is_default)
+            .ok_or_else(|| ApiError::UnprocessableEntity(format!("Customer {} has no default address", customer.id())))?
+    };
+    let created = NewSalesOrder::new(&customer, &address, &warehouse, &operator)
+        .map_err(ApiError::UnprocessableEntity)?
+        .reference(request.reference)
+        .allocation_strategy(request.allocation_strategy)
+        .shipped_by(request.carrier, request.cutoff_at)
+        .priority(request.priority)
+        .create(&*conn)?;
+    Ok(Tagged(created.version(), Created(format!("/sales-order/{}", created.id()), Some(Json(SalesOrderView::load(created, &*conn)?)))))
+}
+
+#[get("/sales-order?<status>&<customer>")]
+pub fn list(status: Option<String>, customer: Option<i32>, conn: PostgresConnection) -> Result<Json<Vec<SalesOrder>>, ApiError> {
+    let status = match status {
+        Some(status) => Some(SalesOrderStatus::parse(&status)
+            .ok_or_else(|| ApiError::BadRequest(format!("Unknown sales order status {}", status)))?),
+        None => None
+    };
+    Ok(Json(SalesOrder::list(status, customer, &*conn)?))
+}
+
+#[get("/sales-order/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    let order = SalesOrder::find(id, &*conn)?;
+    tagged_view(order, &conn)
+}
+
+#[post("/sales-order/<id>/line", format="application/json", data="<line>")]
+pub fn add_line(id: i32, if_match: Result<IfMatch, ApiError>, line: Json<SalesOrderLineDefinition>, conn: PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    let line = line.into_inner();
+    let product = Product::find(line.product_id, &*conn)
+        .optional()?
+        .ok_or_else(|| ApiError::UnprocessableEntity(format!("Product {} does not exist", line.product_id)))?;
+    let quantity = line.quantity.in_base_units(&PackagingHierarchy::load(&product, &*conn)?)?;
+    match order.add_line(product.id(), quantity, &*conn)? {
+        Some(revised) => tagged_view(revised, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+#[delete("/sales-order/<id>/line/<line_id>")]
+pub fn remove_line(id: i32, line_id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    match order.remove_line(line_id, &*conn)? {
+        Some(revised) => tagged_view(revised, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+/// Reserves stock for the order, short lines are backordered unless the request forbids it.
+#[post("/sales-order/<id>/allocate", data="<request>")]
+pub fn allocate(id: i32, if_match: Result<IfMatch, ApiError>, request: Option<Json<AllocationRequest>>, conn: PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    let request = request.map(Json::into_inner).unwrap_or_default();
+    match order.allocate(request.strategy, request.allow_partial, &*conn)? {
+        Some(allocated) => tagged_view(allocated, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+#[post("/sales-order/<id>/cancel")]
+pub fn cancel(id: i32, if_match: Result<IfMatch, ApiError>, conn: PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    let order = current(id, if_match, &conn)?;
+    match order.cancel(&*conn)? {
+        Some(cancelled) => tagged_view(cancelled, &conn),
+        None => Err(ApiError::precondition_failed_with(reloaded(id, &*conn), |current| current.order.version()))
+    }
+}
+
+/// The order in the version the client expects it to be in.
+fn current(id: i32, if_match: Result<IfMatch, ApiError>, conn: &PostgresConnection) -> Result<SalesOrder, ApiError> {
+    let expected_version = if_match?.version();
+    let order = SalesOrder::find(id, &**conn)?;
+    if order.version() != expected_version {
+        return Err(ApiError::precondition_failed(order.version(), &SalesOrderView::load(order, &**conn)?));
+    }
+    Ok(order)
+}
+
+fn tagged_view(order: SalesOrder, conn: &PostgresConnection) -> Result<Tagged<Json<SalesOrderView>>, ApiError> {
+    Ok(Tagged(order.version(), Json(SalesOrderView::load(order, &**conn)?)))
+}
+
+/// The order as it is now, to answer a lost optimistic lock with.
+fn reloaded(id: i32, conn: &impl Connection<Backend=Pg>) -> Result<SalesOrderView, diesel::result::Error> {
+    SalesOrder::find(id, conn).and_then(|current| SalesOrderView::load(current, conn))
+}
@@ -0,0 +1,272 @@
+use diesel::prelude::*;
+use std::fmt::{Debug};
+use crate::schema::{pick_list, pick_list_line};
+use crate::sales::wave::models::{PickingMode, Wave};
+use diesel::sql_types::{Integer, Nullable, Text};
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+/// Picks handed to one picker: those of a single order, of a whole wave or of one zone of a
+/// wave, depending on the wave's picking mode.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="pick_list"]
+pub struct PickList {
+    id: i32,
+    wave_id: i32,
+    sales_order_id: Option<i32>,
+    zone_id: Option<i32>
+}
+
+impl PickList {
+
+    pub fn find(list_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<PickList, diesel::result::Error> {
+        use crate::schema::pick_list::dsl::*;
+        pick_list.find(list_id).first(conn)
+    }
+
+    pub fn for_wave(wave: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PickList>, diesel::result::Error> {
+        use crate::schema::pick_list::dsl::*;
+        pick_list.filter(wave_id.eq(wave)).order(id.asc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn wave_id(&self) -> i32 {
+        self.wave_id
+    }
+
+    /// Cuts the allocations of the orders released in `wave` into pick lists as its picking mode
+    /// asks for, each sorted into a walking path.
+    pub fn generate(wave: &Wave, conn: &impl Connection<Backend=Pg>) -> Result<Vec<PickList>, diesel::result::Error> {
+        let mut lists: Vec<(Option<i32>, Option<i32>, Vec<Stop>)> = Vec::new();
+        for stop in Stop::for_wave(wave.id(), conn)? {
+            let (order, zone) = match wave.picking_mode() {
+                PickingMode::Discrete => (Some(stop.sales_order_id), None),
+                PickingMode::Batch => (None, None),
+                PickingMode::Zone => (None, Some(stop.zone_id))
+            };
+            match lists.iter_mut().find(|(o, z, _)| *o == order && *z == zone) {
+                Some((_, _, stops)) => stops.push(stop),
+                None => lists.push((order, zone, vec![stop]))
+            }
+        }
+        conn.transaction(|| {
+            lists.into_iter()
+                .map(|(order, zone, stops)| {
+                    let created: PickList = diesel::insert_into(pick_list::table)
+                        .values((
+                            pick_list::wave_id.eq(wave.id()),
+                            pick_list::sales_order_id.eq(order),
+                            pick_list::zone_id.eq(zone)
+                        ))
+                        .get_result(conn)?;
+                    let lines: Vec<_> = walking_path(stops).iter()
+                        .enumerate()
+                        .map(|(position, stop)| (
+                            pick_list_line::pick_list_id.eq(created.id),
+                            pick_list_line::sales_order_allocation_id.eq(stop.allocation_id),
+                            pick_list_line::sequence.eq(position as i32 + 1)
+                        ))
+                        .collect();
+                    diesel::insert_into(pick_list_line::table).values(&lines).execute(conn)?;
+                    Ok(created)
+                })
+                .collect()
+        })
+    }
+
+    /// Takes the picks of sales order `order` off the pick lists of `wave`, lists left without
+    /// picks are dropped.
+    pub fn withdraw(wave: i32, order: i32, conn: &impl Connection<Backend=Pg>) -> Result<(), diesel::result::Error> {
+        conn.transaction(|| {
+            diesel::sql_query(
+                "delete from pick_list_line p using sales_order_allocation a, sales_order_line s \
+                 where p.sales_order_allocation_id = a.id and a.sales_order_line_id = s.id and s.sales_order_id = $1")
+                .bind::<Integer, _>(order)
+                .execute(conn)?;
+            diesel::sql_query(
+                "delete from pick_list l where l.wave_id = $1 \
+                   and not exists (select 1 from pick_list_line p where p.pick_list_id = l.id)")
+                .bind::<Integer, _>(wave)
+                .execute(conn)?;
+            Ok(())
+        })
+    }
+}
+
+/// Where one allocation of a wave has to be picked from.
+#[derive(Debug, Clone, QueryableByName)]
+struct Stop {
+    #[sql_type="Integer"]
+    allocation_id: i32,
+    #[sql_type="Integer"]
+    sales_order_id: i32,
+    #[sql_type="Integer"]
+    zone_id: i32,
+    #[sql_type="Text"]
+    zone_code: String,
+    #[sql_type="Integer"]
+    aisle_id: i32,
+    #[sql_type="Integer"]
+    aisle_sequence: i32,
+    #[sql_type="Text"]
+    location_code: String
+}
+
+impl Stop {
+    fn for_wave(wave: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Stop>, diesel::result::Error> {
+        diesel::sql_query(
+            "select a.id as allocation_id, l.sales_order_id, bin.zone_id, z.code as zone_code, \
+                    bin.aisle_id, aisle.sequence as aisle_sequence, bin.location_code \
+             from sales_order_allocation a \
+             join sales_order_line l on l.id = a.sales_order_line_id \
+             join sales_order o on o.id = l.sales_order_id \
+             join bin_location bin on bin.id = a.bin_location_id \
+             join warehouse_zone z on z.id = bin.zone_id \
+             join warehouse_aisle aisle on aisle.id = bin.aisle_id \
+             where o.wave_id = $1 \
+             order by l.sales_order_id, a.id")
+            .bind::<Integer, _>(wave)
+            .load(conn)
+    }
+}
+
+/// Orders `stops` the way a picker walks the warehouse: zone by zone and through the aisles of
+/// a zone in their sequence, going up one aisle and down the next so none is walked twice.
+fn walking_path(mut stops: Vec<Stop>) -> Vec<Stop> {
+    stops.sort_by(|a, b| (&a.zone_code, a.aisle_sequence, a.aisle_id, &a.location_code)
+        .cmp(&(&b.zone_code, b.aisle_sequence, b.aisle_id, &b.location_code)));
+    let mut aisles: Vec<Vec<Stop>> = Vec::new();
+    for stop in stops {
+        match aisles.last_mut() {
+            Some(aisle) if aisle[0].aisle_id == stop.aisle_id => aisle.push(stop),
+            _ => aisles.push(vec![stop])
+        }
+    }
+    aisles.into_iter()
+        .enumerate()
+        .flat_map(|(walked, mut aisle)| {
+            if walked % 2 == 1 {
+                aisle.reverse();
+            }
+            aisle
+        })
+        .collect()
+}
+
+/// One stop on a pick list: what to take from which bin and which order it goes to.
+#[derive(Debug, PartialEq, Clone, QueryableByName, Serialize, Deserialize)]
+pub struct PickLine {
+    #[sql_type="Integer"]
+    pub sequence: i32,
+    #[sql_type="Integer"]
+    pub sales_order_id: i32,
+    #[sql_type="Integer"]
+    pub sales_order_allocation_id: i32,
+    #[sql_type="Integer"]
+    pub bin_location_id: i32,
+    #[sql_type="Text"]
+    pub location_code: String,
+    #[sql_type="Integer"]
+    pub product_id: i32,
+    #[sql_type="Text"]
+    pub sku: String,
+    #[sql_type="Text"]
+    pub product_name: String,
+    #[sql_type="Nullable<Text>"]
+    pub lot_number: Option<String>,
+    #[sql_type="Integer"]
+    pub quantity: i32
+}
+
+/// A pick list with its lines in walking order.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PickListView {
+    #[serde(flatten)]
+    pub pick_list: PickList,
+    pub picking_mode: PickingMode,
+    pub lines: Vec<PickLine>
+}
+
+impl PickListView {
+    pub fn load(pick_list: PickList, conn: &impl Connection<Backend=Pg>) -> Result<PickListView, diesel::result::Error> {
+        let picking_mode = Wave::find(pick_list.wave_id, conn)?.picking_mode();
+        let lines = diesel::sql_query(
+            "select pl.sequence, l.sales_order_id, a.id as sales_order_allocation_id, a.bin_location_id, \
+                    bin.location_code, l.product_id, p.sku, p.name as product_name, lot.lot_number, a.quantity \
+             from pick_list_line pl \
+             join sales_order_allocation a on a.id = pl.sales_order_allocation_id \
+             join sales_order_line l on l.id = a.sales_order_line_id \
+             join bin_location bin on bin.id = a.bin_location_id \
+             join product p on p.id = l.product_id \
+             left join product_lot lot on lot.id = a.lot_id \
+             where pl.pick_list_id = $1 \
+             order by pl.sequence")
+            .bind::<Integer, _>(pick_list.id)
+            .load(conn)?;
+        Ok(PickListView { pick_list, picking_mode, lines })
+    }
+
+    /// The pick list as a standalone HTML page for printing, with an empty column to tick off
+    /// picked lines.
+    pub fn to_html(&self) -> String {
+        let mut scope = format!("Wave {} &middot; {} picking", self.pick_list.wave_id, self.picking_mode);
+        if let Some(order) = self.pick_list.sales_order_id {
+            scope.push_str(&format!(" &middot; sales order {}", order));
+        }
+        if let Some(zone) = self.pick_list.zone_id {
+            scope.push_str(&format!(" &middot; zone {}", zone));
+        }
+        let rows: String = self.lines.iter()
+            .map(|line| format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"quantity\">{}</td><td>{}</td><td></td></tr>\n",
+                line.sequence, escape(&line.location_code), escape(&line.sku), escape(&line.product_name),
+                line.lot_number.as_deref().map(escape).unwrap_or_default(), line.quantity, line.sales_order_id))
+            .collect();
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Pick list {id}</title>\n\
+             <style>\nbody {{ font-family: sans-serif; }}\ntable {{ border-collapse: collapse; width: 100%; }}\n\
+             th, td {{ border: 1px solid #000; padding: 4px 8px; text-align: left; }}\n\
+             td.quantity {{ text-align: right; font-weight: bold; }}\n\
+             @media print {{ tr {{ page-break-inside: avoid; }} }}\n</style>\n</head>\n<body>\n\
+             <h1>Pick list {id}</h1>\n<p>{scope}</p>\n<table>\n\
+             <thead><tr><th>#</th><th>Bin</th><th>SKU</th><th>Product</th><th>Lot</th><th>Quantity</th><th>Order</th><th>Picked</th></tr></thead>\n\
+             <tbody>\n{rows}</tbody>\n</table>\n</body>\n</html>\n",
+            id = self.pick_list.id, scope = scope, rows = rows)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::sales::picking::models::{walking_path, Stop};
+
+    fn stop(allocation_id: i32, zone_code: &str, aisle_sequence: i32, location_code: &str) -> Stop {
+        Stop {
+            allocation_id, sales_order_id: 1, zone_id: 0, zone_code: zone_code.to_string(),
+            aisle_id: aisle_sequence, aisle_sequence, location_code: location_code.to_string()
+        }
+    }
+
+    #[test]
+    fn walking_path_snakes_through_the_aisles_zone_by_zone() {
+        let stops = vec![
+            stop(1, "B", 1, "B-01-01"),
+            stop(2, "A", 2, "A-02-01"),
+            stop(3, "A", 1, "A-01-03"),
+            stop(4, "A", 2, "A-02-05"),
+            stop(5, "A", 1, "A-01-01"),
+            stop(6, "A", 3, "A-03-02")
+        ];
+        let path: Vec<i32> = walking_path(stops).iter().map(|stop| stop.allocation_id).collect();
+        assert_eq!(path, vec![5, 3, 4, 2, 6, 1]);
+    }
+}
@@ -0,0 +1,20 @@
+use crate::sales::picking::models::{PickList, PickListView};
+
+use rocket_contrib::json::Json;
+use rocket::response::content::Html;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+
+
+#[get("/pick-list/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<PickListView>, ApiError> {
+    let pick_list = PickList::find(id, &*conn)?;
+    Ok(Json(PickListView::load(pick_list, &*conn)?))
+}
+
+/// The pick list as a page to print and carry through the warehouse.
+#[get("/pick-list/<id>/print")]
+pub fn print(id: i32, conn: PostgresConnection) -> Result<Html<String>, ApiError> {
+    let pick_list = PickList::find(id, &*conn)?;
+    Ok(Html(PickListView::load(pick_list, &*conn)?.to_html()))
+}
@@ -0,0 +1,302 @@
+use diesel::prelude::*;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{Debug};
+use chrono::NaiveDateTime;
+use crate::schema::{bin_location, sales_order_allocation, sales_order_line, warehouse_zone, wave};
+use crate::location::warehouse::models::Warehouse;
+use crate::sales::order::models::SalesOrder;
+use crate::domain::DomainError;
+use crate::sales::picking::models::PickList;
+use crate::operator::Operator;
+use serde::{Serialize, Deserialize};
+use diesel::pg::Pg;
+
+sql_enum! {
+    /// What the allocated orders of a warehouse are grouped into waves by: the carrier and time
+    /// it collects them, the zones they are picked in or their priority.
+    pub enum WaveGrouping {
+        Cutoff => "cutoff",
+        Zone => "zone",
+        Priority => "priority"
+    }
+}
+
+sql_enum! {
+    /// How the picks of a wave are cut into pick lists: one per order, one for the whole wave
+    /// or one per zone.
+    pub enum PickingMode {
+        Discrete => "discrete",
+        Batch => "batch",
+        Zone => "zone"
+    }
+}
+
+/// Orders released for picking together.
+#[derive(Debug, PartialEq, Clone, Queryable, Identifiable, Deserialize, Serialize)]
+#[table_name="wave"]
+pub struct Wave {
+    id: i32,
+    warehouse_id: i32,
+    grouped_by: WaveGrouping,
+    /// What all orders of the wave have in common, e.g. their priority.
+    group_key: String,
+    picking_mode: PickingMode,
+    released_by: String,
+    released_at: NaiveDateTime
+}
+
+impl Wave {
+
+    pub fn find(wave_id: i32, conn: &impl Connection<Backend=Pg>) -> Result<Wave, diesel::result::Error> {
+        use crate::schema::wave::dsl::*;
+        wave.find(wave_id).first(conn)
+    }
+
+    /// Waves of `warehouse`, newest first.
+    pub fn for_warehouse(warehouse: i32, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Wave>, diesel::result::Error> {
+        use crate::schema::wave::dsl::*;
+        wave.filter(warehouse_id.eq(warehouse)).order(id.desc()).load(conn)
+    }
+
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn warehouse_id(&self) -> i32 {
+        self.warehouse_id
+    }
+
+    pub fn group_key(&self) -> &str {
+        &self.group_key
+    }
+
+    pub fn picking_mode(&self) -> PickingMode {
+        self.picking_mode
+    }
+}
+
+/// A wave with the orders released in it and the pick lists generated for them.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct WaveView {
+    #[serde(flatten)]
+    pub wave: Wave,
+    pub sales_order_ids: Vec<i32>,
+    pub pick_lists: Vec<PickList>
+}
+
+impl WaveView {
+    pub fn load(wave: Wave, conn: &impl Connection<Backend=Pg>) -> Result<WaveView, diesel::result::Error> {
+        let sales_order_ids = SalesOrder::in_wave(wave.id, conn)?.iter().map(SalesOrder::id).collect();
+        let pick_lists = PickList::for_wave(wave.id, conn)?;
+        Ok(WaveView { wave, sales_order_ids, pick_lists })
+    }
+}
+
+/// Body of a `POST /warehouse/<id>/wave` request. Groups larger than `max_orders` are split
+/// over several waves.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WavePlan {
+    pub grouped_by: WaveGrouping,
+    pub picking_mode: PickingMode,
+    #[serde(default)]
+    pub max_orders: Option<usize>
+}
+
+impl WavePlan {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.max_orders {
+            Some(0) => Err(String::from("Waves need room for at least one order")),
+            _ => Ok(())
+        }
+    }
+
+    /// Releases all allocated orders of `warehouse` not released yet, one wave per group, and
+    /// generates the pick lists of every wave. Groups are released earliest cutoff, highest
+    /// priority or first zone first.
+    pub fn release(self, warehouse: &Warehouse, operator: &Operator, conn: &impl Connection<Backend=Pg>) -> Result<Vec<Wave>, DomainError> {
+        conn.transaction(|| {
+            let orders = SalesOrder::releasable(warehouse.id(), conn)?;
+            let mut waves = Vec::new();
+            for (key, orders) in self.group(orders, conn)? {
+                let released: Wave = diesel::insert_into(wave::table)
+                    .values(NewWave {
+                        warehouse_id: warehouse.id(),
+                        grouped_by: self.grouped_by,
+                        group_key: key,
+                        picking_mode: self.picking_mode,
+                        released_by: operator.name().to_string()
+                    })
+                    .get_result(conn)?;
+                for order in orders {
+                    let order_id = order.id();
+                    order.release(released.id, conn)?
+                        .ok_or_else(|| DomainError::Rejected(format!("Sales order {} changed while it was released", order_id)))?;
+                }
+                PickList::generate(&released, conn)?;
+                waves.push(released);
+            }
+            Ok(waves)
+        })
+    }
+
+    /// Sorts `orders` by the plan's grouping and cuts them into groups sharing the same key.
+    fn group(&self, orders: Vec<SalesOrder>, conn: &impl Connection<Backend=Pg>) -> Result<Vec<(String, Vec<SalesOrder>)>, diesel::result::Error> {
+        let zones = match self.grouped_by {
+            WaveGrouping::Zone => zones_of(&orders.iter().map(SalesOrder::id).collect::<Vec<_>>(), conn)?,
+            _ => HashMap::new()
+        };
+        let mut keyed: Vec<(String, SalesOrder)> = orders.into_iter()
+            .map(|order| {
+                let key = match self.grouped_by {
+                    WaveGrouping::Cutoff => format!("{} {}",
+                        order.carrier().unwrap_or("any carrier"),
+                        order.cutoff_at().map(|cutoff| cutoff.format("%Y-%m-%d %H:%M").to_string()).unwrap_or_else(|| String::from("without cutoff"))),
+                    WaveGrouping::Zone => zones.get(&order.id()).map(|codes| codes.join("+")).unwrap_or_default(),
+                    WaveGrouping::Priority => format!("priority {}", order.priority())
+                };
+                (key, order)
+            })
+            .collect();
+        match self.grouped_by {
+            WaveGrouping::Cutoff => keyed.sort_by(|(_, a), (_, b)| (a.cutoff_at().is_none(), a.cutoff_at(), a.carrier(), a.id())
+                .cmp(&(b.cutoff_at().is_none(), b.cutoff_at(), b.carrier(), b.id()))),
+            WaveGrouping::Zone => keyed.sort_by(|(a_key, a), (b_key, b)| (a_key, a.id()).cmp(&(b_key, b.id()))),
+            WaveGrouping::Priority => keyed.sort_by_key(|(_, order)| (Reverse(order.priority()), order.id()))
+        }
+        let mut groups: Vec<(String, Vec<SalesOrder>)> = Vec::new();
+        for (key, order) in keyed {
+            match groups.last_mut() {
+                Some((last, orders)) if *last == key && self.max_orders.map_or(true, |max| orders.len() < max) => orders.push(order),
+                _ => groups.push((key, vec![order]))
+            }
+        }
+        Ok(groups)
+    }
+}
+
+/// Codes of the zones each of `orders` has stock allocated in, in alphabetical order.
+fn zones_of(orders: &[i32], conn: &impl Connection<Backend=Pg>) -> Result<HashMap<i32, Vec<String>>, diesel::result::Error> {
+    let rows: Vec<(i32, String)> = sales_order_allocation::table
+        .inner_join(sales_order_line::table)
+        .inner_join(bin_location::table.inner_join(warehouse_zone::table))
+        .filter(sales_order_line::sales_order_id.eq_any(orders))
+        .select((sales_order_line::sales_order_id, warehouse_zone::code))
+        .distinct()
+        .order((sales_order_line::sales_order_id.asc(), warehouse_zone::code.asc()))
+        .load(conn)?;
+    let mut zones: HashMap<i32, Vec<String>> = HashMap::new();
+    for (order, code) in rows {
+        zones.entry(order).or_default().push(code);
+    }
+    Ok(zones)
+}
+
+#[derive(Debug, Insertable)]
+#[table_name="wave"]
+struct NewWave {
+    warehouse_id: i32,
+    grouped_by: WaveGrouping,
+    group_key: String,
+    picking_mode: PickingMode,
+    released_by: String
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::with_migrated_database_connection;
+    use crate::testing::fixtures::{warehouse_with_bins, product};
+    use crate::location::bin::models::LocationType;
+    use crate::inventory::balance::models::{InventoryBalance, StockChange};
+    use crate::sales::customer::models::{AddressDefinition, CustomerDetails};
+    use crate::sales::order::models::{NewSalesOrder, SalesOrder, SalesOrderStatus};
+    use crate::domain::DomainError;
+    use crate::sales::picking::models::PickListView;
+    use crate::sales::wave::models::{PickingMode, WaveGrouping, WavePlan, WaveView};
+    use crate::operator::Operator;
+
+    #[test]
+    fn orders_are_released_by_priority_and_batch_picked_along_the_aisle() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace, LocationType::PickFace, LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            let shampoo = product(&conn, "SHAMPOO");
+            InventoryBalance::apply(soap.id(), &bins[2], StockChange::on_hand(5), &conn).unwrap();
+            InventoryBalance::apply(shampoo.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+            let customer = CustomerDetails { name: "Corner Shop".to_string(), email: None }.create(&conn).unwrap();
+            let address = AddressDefinition {
+                recipient: "Receiving".to_string(), street: "Main Street 1".to_string(), postal_code: "10115".to_string(),
+                city: "Berlin".to_string(), country: "DE".to_string(), is_default: true
+            }.for_customer(&customer).unwrap().create(&conn).unwrap();
+            let clerk = Operator::new("sales");
+            let allocated = |priority: i32, lines: &[(i32, i32)]| {
+                let mut order = NewSalesOrder::new(&customer, &address, &warehouse, &clerk).unwrap()
+                    .priority(priority)
+                    .create(&conn).unwrap();
+                for (product, quantity) in lines {
+                    order = order.add_line(*product, *quantity, &conn).unwrap().unwrap();
+                }
+                order.allocate(None, true, &conn).unwrap().unwrap()
+            };
+            let low = allocated(1, &[(soap.id(), 1)]);
+            let urgent = allocated(5, &[(soap.id(), 2), (shampoo.id(), 1)]);
+            let also_urgent = allocated(5, &[(shampoo.id(), 2)]);
+
+            let plan = || WavePlan { grouped_by: WaveGrouping::Priority, picking_mode: PickingMode::Batch, max_orders: None };
+            let waves = plan().release(&warehouse, &clerk, &conn).unwrap();
+            assert_eq!(waves.iter().map(|wave| wave.group_key()).collect::<Vec<_>>(), vec!["priority 5", "priority 1"]);
+
+            let first = WaveView::load(waves[0].clone(), &conn).unwrap();
+            assert_eq!(first.sales_order_ids, vec![urgent.id(), also_urgent.id()]);
+            assert_eq!(first.pick_lists.len(), 1);
+            let picks = PickListView::load(first.pick_lists[0].clone(), &conn).unwrap();
+            let walked: Vec<_> = picks.lines.iter().map(|line| (line.bin_location_id, line.sales_order_id, line.quantity)).collect();
+            assert_eq!(walked, vec![(bins[0].id(), urgent.id(), 1), (bins[0].id(), also_urgent.id(), 2), (bins[2].id(), urgent.id(), 2)]);
+            assert!(picks.to_html().contains("<td>SHAMPOO</td>"));
+
+            let released = WaveView::load(waves[1].clone(), &conn).unwrap();
+            assert_eq!(released.sales_order_ids, vec![low.id()]);
+            assert!(plan().release(&warehouse, &clerk, &conn).unwrap().is_empty());
+            let low = SalesOrder::find(low.id(), &conn).unwrap();
+            assert_eq!(low.status(), SalesOrderStatus::Released);
+            assert!(matches!(low.clone().release(waves[0].id(), &conn), Err(DomainError::Rejected(_))));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn cancelled_released_orders_leave_their_wave_and_free_their_stock() -> Result<(), String> {
+        with_migrated_database_connection(|conn| {
+            let (warehouse, bins) = warehouse_with_bins(&conn, "WH1", &[LocationType::PickFace]);
+            let soap = product(&conn, "SOAP");
+            InventoryBalance::apply(soap.id(), &bins[0], StockChange::on_hand(5), &conn).unwrap();
+            let customer = CustomerDetails { name: "Corner Shop".to_string(), email: None }.create(&conn).unwrap();
+            let address = AddressDefinition {
+                recipient: "Receiving".to_string(), street: "Main Street 1".to_string(), postal_code: "10115".to_string(),
+                city: "Berlin".to_string(), country: "DE".to_string(), is_default: true
+            }.for_customer(&customer).unwrap().create(&conn).unwrap();
+            let clerk = Operator::new("sales");
+            let allocated = |quantity: i32| NewSalesOrder::new(&customer, &address, &warehouse, &clerk).unwrap()
+                .create(&conn).unwrap()
+                .add_line(soap.id(), quantity, &conn).unwrap().unwrap()
+                .allocate(None, true, &conn).unwrap().unwrap();
+            let kept = allocated(1);
+            let dropped = allocated(2);
+
+            let plan = WavePlan { grouped_by: WaveGrouping::Priority, picking_mode: PickingMode::Batch, max_orders: None };
+            let wave = plan.release(&warehouse, &clerk, &conn).unwrap().remove(0);
+            let dropped = SalesOrder::find(dropped.id(), &conn).unwrap().cancel(&conn).unwrap().unwrap();
+            assert_eq!(dropped.status(), SalesOrderStatus::Cancelled);
+            assert_eq!(InventoryBalance::for_bin(bins[0].id(), &conn).unwrap()[0].allocated(), 1);
+
+            let view = WaveView::load(wave.clone(), &conn).unwrap();
+            assert_eq!(view.sales_order_ids, vec![kept.id()]);
+            let picks = PickListView::load(view.pick_lists[0].clone(), &conn).unwrap();
+            assert_eq!(picks.lines.iter().map(|line| line.sales_order_id).collect::<Vec<_>>(), vec![kept.id()]);
+
+            SalesOrder::find(kept.id(), &conn).unwrap().cancel(&conn).unwrap().unwrap();
+            assert!(WaveView::load(wave, &conn).unwrap().pick_lists.is_empty());
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use crate::sales::wave::models::{Wave, WavePlan, WaveView};
+use crate::location::warehouse::models::Warehouse;
+
+use rocket_contrib::json::Json;
+use rocket::response::status::Created;
+use crate::configuration::PostgresConnection;
+use crate::error::ApiError;
+use crate::operator::Operator;
+
+
+/// Releases the allocated orders of the warehouse in waves and generates their pick lists. The
+/// created waves are answered with the warehouse's wave list as location.
+#[post("/warehouse/<id>/wave", format="application/json", data="<plan>")]
+pub fn post(id: i32, plan: Json<WavePlan>, operator: Result<Operator, ApiError>, conn: PostgresConnection) -> Result<Created<Json<Vec<WaveView>>>, ApiError> {
+    let operator = operator?;
+    let warehouse = Warehouse::find(id, &*conn)?;
+    let plan = plan.into_inner();
+    plan.validate().map_err(ApiError::UnprocessableEntity)?;
+    let waves = plan.release(&warehouse, &operator, &*conn)?
+        .into_iter()
+        .map(|wave| WaveView::load(wave, &*conn))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Created(format!("/warehouse/{}/wave", warehouse.id()), Some(Json(waves))))
+}
+
+#[get("/warehouse/<id>/wave")]
+pub fn list(id: i32, conn: PostgresConnection) -> Result<Json<Vec<Wave>>, ApiError> {
+    let warehouse = Warehouse::find(id, &*conn)?;
+    Ok(Json(Wave::for_warehouse(warehouse.id(), &*conn)?))
+}
+
+#[get("/wave/<id>")]
+pub fn get(id: i32, conn: PostgresConnection) -> Result<Json<WaveView>, ApiError> {
+    let wave = Wave::find(id, &*conn)?;
+    Ok(Json(WaveView::load(wave, &*conn)?))
+}
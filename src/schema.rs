@@ -1,7 +1,618 @@
+table! {
+    bin_location (id) {
+        id -> Int4,
+        warehouse_id -> Int4,
+        zone_id -> Int4,
+        aisle_id -> Int4,
+        code -> Varchar,
+        location_code -> Varchar,
+        location_type -> Varchar,
+        max_units -> Nullable<Int4>,
+        max_volume_cm3 -> Nullable<Int8>,
+        max_weight_g -> Nullable<Int8>,
+        version -> Int4,
+    }
+}
+
+table! {
+    customer (id) {
+        id -> Int4,
+        name -> Varchar,
+        email -> Nullable<Varchar>,
+        version -> Int4,
+    }
+}
+
+table! {
+    customer_address (id) {
+        id -> Int4,
+        customer_id -> Int4,
+        recipient -> Varchar,
+        street -> Varchar,
+        postal_code -> Varchar,
+        city -> Varchar,
+        country -> Varchar,
+        is_default -> Bool,
+    }
+}
+
+table! {
+    cycle_count (id) {
+        id -> Int4,
+        warehouse_id -> Int4,
+        blind -> Bool,
+        recount_threshold -> Int4,
+        created_by -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    cycle_count_task (id) {
+        id -> Int4,
+        cycle_count_id -> Int4,
+        bin_location_id -> Int4,
+        product_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        status -> Varchar,
+        system_quantity -> Nullable<Int4>,
+        counted_quantity -> Nullable<Int4>,
+        counted_by -> Nullable<Varchar>,
+        recount_quantity -> Nullable<Int4>,
+        recounted_by -> Nullable<Varchar>,
+        version -> Int4,
+    }
+}
+
+table! {
+    goods_receipt (id) {
+        id -> Int4,
+        purchase_order_id -> Int4,
+        notice_id -> Nullable<Int4>,
+        bin_location_id -> Int4,
+        received_by -> Varchar,
+        received_at -> Timestamp,
+    }
+}
+
+table! {
+    goods_receipt_line (id) {
+        id -> Int4,
+        goods_receipt_id -> Int4,
+        purchase_order_line_id -> Int4,
+        quantity -> Int4,
+        damaged_quantity -> Int4,
+        lot_id -> Nullable<Int4>,
+        movement_id -> Nullable<Int4>,
+        put_away_quantity -> Int4,
+        rejected_quantity -> Int4,
+    }
+}
+
+table! {
+    home_location (id) {
+        id -> Int4,
+        product_id -> Int4,
+        bin_location_id -> Int4,
+    }
+}
+
+table! {
+    inspection (id) {
+        id -> Int4,
+        goods_receipt_line_id -> Int4,
+        product_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        bin_location_id -> Int4,
+        quantity -> Int4,
+        status -> Varchar,
+        rejected_quantity -> Int4,
+        inspected_by -> Nullable<Varchar>,
+        inspected_at -> Nullable<Timestamp>,
+        notes -> Nullable<Varchar>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    inventory_balance (id) {
+        id -> Int4,
+        product_id -> Int4,
+        bin_location_id -> Int4,
+        warehouse_id -> Int4,
+        on_hand -> Int4,
+        allocated -> Int4,
+        version -> Int4,
+        lot_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    pick_face (id) {
+        id -> Int4,
+        bin_location_id -> Int4,
+        product_id -> Int4,
+        min_quantity -> Int4,
+        max_quantity -> Int4,
+        version -> Int4,
+    }
+}
+
+table! {
+    pick_list (id) {
+        id -> Int4,
+        wave_id -> Int4,
+        sales_order_id -> Nullable<Int4>,
+        zone_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    pick_list_line (id) {
+        id -> Int4,
+        pick_list_id -> Int4,
+        sales_order_allocation_id -> Int4,
+        sequence -> Int4,
+    }
+}
+
+table! {
+    product (id) {
+        id -> Int4,
+        sku -> Varchar,
+        name -> Varchar,
+        description -> Varchar,
+        unit_of_measure -> Varchar,
+        length_mm -> Nullable<Int4>,
+        width_mm -> Nullable<Int4>,
+        height_mm -> Nullable<Int4>,
+        weight_g -> Nullable<Int4>,
+        version -> Int4,
+        parent_id -> Nullable<Int4>,
+        serialised -> Bool,
+        requires_inspection -> Bool,
+    }
+}
+
+table! {
+    product_barcode (id) {
+        id -> Int4,
+        product_id -> Int4,
+        code -> Varchar,
+        gtin -> Varchar,
+        symbology -> Varchar,
+    }
+}
+
 table! {
     product_category (id) {
         id -> Int4,
         name -> Varchar,
         version -> Int4,
+        parent_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    product_category_assignment (product_id, category_id) {
+        product_id -> Int4,
+        category_id -> Int4,
+    }
+}
+
+table! {
+    product_lot (id) {
+        id -> Int4,
+        product_id -> Int4,
+        lot_number -> Varchar,
+        manufactured_on -> Nullable<Date>,
+        expires_on -> Nullable<Date>,
+    }
+}
+
+table! {
+    product_packaging (id) {
+        id -> Int4,
+        product_id -> Int4,
+        name -> Varchar,
+        quantity -> Int4,
+        contained_packaging_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    product_serial (id) {
+        id -> Int4,
+        product_id -> Int4,
+        serial_number -> Varchar,
+        bin_location_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    product_variant_axis (id) {
+        id -> Int4,
+        product_id -> Int4,
+        name -> Varchar,
+        position -> Int4,
+    }
+}
+
+table! {
+    product_variant_option (variant_id, axis_id) {
+        variant_id -> Int4,
+        axis_id -> Int4,
+        value -> Varchar,
+    }
+}
+
+table! {
+    purchase_order (id) {
+        id -> Int4,
+        supplier_id -> Int4,
+        warehouse_id -> Int4,
+        currency -> Varchar,
+        status -> Varchar,
+        expected_on -> Nullable<Date>,
+        created_by -> Varchar,
+        created_at -> Timestamp,
+        version -> Int4,
+    }
+}
+
+table! {
+    purchase_order_line (id) {
+        id -> Int4,
+        purchase_order_id -> Int4,
+        product_id -> Int4,
+        quantity -> Int4,
+        unit_cost_minor -> Int8,
+        received_quantity -> Int4,
+        damaged_quantity -> Int4,
+    }
+}
+
+table! {
+    reorder_policy (id) {
+        id -> Int4,
+        product_id -> Int4,
+        warehouse_id -> Int4,
+        method -> Varchar,
+        reorder_point -> Int4,
+        reorder_quantity -> Nullable<Int4>,
+        max_quantity -> Nullable<Int4>,
+        order_unit -> Nullable<Varchar>,
+        version -> Int4,
+    }
+}
+
+table! {
+    replenishment_task (id) {
+        id -> Int4,
+        pick_face_id -> Int4,
+        product_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        from_location_id -> Int4,
+        to_location_id -> Int4,
+        quantity -> Int4,
+        status -> Varchar,
+        created_at -> Timestamp,
+        assigned_to -> Nullable<Varchar>,
+        completed_by -> Nullable<Varchar>,
+        completed_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    sales_order (id) {
+        id -> Int4,
+        customer_id -> Int4,
+        warehouse_id -> Int4,
+        shipping_address_id -> Int4,
+        reference -> Nullable<Varchar>,
+        status -> Varchar,
+        allocation_strategy -> Varchar,
+        created_by -> Varchar,
+        created_at -> Timestamp,
+        version -> Int4,
+        carrier -> Nullable<Varchar>,
+        cutoff_at -> Nullable<Timestamp>,
+        priority -> Int4,
+        wave_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    sales_order_allocation (id) {
+        id -> Int4,
+        sales_order_line_id -> Int4,
+        bin_location_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        quantity -> Int4,
+    }
+}
+
+table! {
+    sales_order_line (id) {
+        id -> Int4,
+        sales_order_id -> Int4,
+        product_id -> Int4,
+        quantity -> Int4,
+        allocated_quantity -> Int4,
+    }
+}
+
+table! {
+    shipping_notice (id) {
+        id -> Int4,
+        purchase_order_id -> Int4,
+        reference -> Varchar,
+        expected_on -> Nullable<Date>,
+        status -> Varchar,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    shipping_notice_line (id) {
+        id -> Int4,
+        notice_id -> Int4,
+        purchase_order_line_id -> Int4,
+        quantity -> Int4,
+        lot_number -> Nullable<Varchar>,
+        expires_on -> Nullable<Date>,
+    }
+}
+
+table! {
+    stock_movement (id) {
+        id -> Int4,
+        product_id -> Int4,
+        from_location_id -> Nullable<Int4>,
+        to_location_id -> Nullable<Int4>,
+        quantity -> Int4,
+        reason -> Varchar,
+        user_name -> Varchar,
+        reference -> Nullable<Varchar>,
+        created_at -> Timestamp,
+        lot_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    stock_movement_serial (movement_id, serial_id) {
+        movement_id -> Int4,
+        serial_id -> Int4,
+    }
+}
+
+table! {
+    stock_reservation (id) {
+        id -> Int4,
+        product_id -> Int4,
+        warehouse_id -> Int4,
+        quantity -> Int4,
+        reference -> Nullable<Varchar>,
+        status -> Varchar,
+        created_by -> Varchar,
+        created_at -> Timestamp,
+        expires_at -> Timestamp,
+    }
+}
+
+table! {
+    stock_reservation_line (id) {
+        id -> Int4,
+        reservation_id -> Int4,
+        bin_location_id -> Int4,
+        lot_id -> Nullable<Int4>,
+        quantity -> Int4,
+    }
+}
+
+table! {
+    stock_transfer (id) {
+        id -> Int4,
+        product_id -> Int4,
+        from_warehouse_id -> Int4,
+        from_location_id -> Int4,
+        to_warehouse_id -> Int4,
+        to_location_id -> Nullable<Int4>,
+        quantity -> Int4,
+        status -> Varchar,
+        shipped_by -> Varchar,
+        shipped_at -> Timestamp,
+        received_by -> Nullable<Varchar>,
+        received_at -> Nullable<Timestamp>,
+        lot_id -> Nullable<Int4>,
+    }
+}
+
+table! {
+    supplier (id) {
+        id -> Int4,
+        name -> Varchar,
+        contact_name -> Nullable<Varchar>,
+        contact_email -> Nullable<Varchar>,
+        lead_time_days -> Int4,
+        currency -> Varchar,
+        version -> Int4,
+        over_receipt_percent -> Int4,
+        under_receipt_percent -> Int4,
+        requires_inspection -> Bool,
+    }
+}
+
+table! {
+    warehouse (id) {
+        id -> Int4,
+        code -> Varchar,
+        name -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    warehouse_aisle (id) {
+        id -> Int4,
+        zone_id -> Int4,
+        code -> Varchar,
+        sequence -> Int4,
+        version -> Int4,
+    }
+}
+
+table! {
+    warehouse_zone (id) {
+        id -> Int4,
+        warehouse_id -> Int4,
+        code -> Varchar,
+        name -> Varchar,
+        version -> Int4,
+    }
+}
+
+table! {
+    wave (id) {
+        id -> Int4,
+        warehouse_id -> Int4,
+        grouped_by -> Varchar,
+        group_key -> Varchar,
+        picking_mode -> Varchar,
+        released_by -> Varchar,
+        released_at -> Timestamp,
+    }
+}
+
+table! {
+    zone_restriction (zone_id, category_id) {
+        zone_id -> Int4,
+        category_id -> Int4,
     }
 }
+
+joinable!(bin_location -> warehouse (warehouse_id));
+joinable!(bin_location -> warehouse_aisle (aisle_id));
+joinable!(bin_location -> warehouse_zone (zone_id));
+joinable!(customer_address -> customer (customer_id));
+joinable!(cycle_count -> warehouse (warehouse_id));
+joinable!(cycle_count_task -> bin_location (bin_location_id));
+joinable!(cycle_count_task -> cycle_count (cycle_count_id));
+joinable!(cycle_count_task -> product (product_id));
+joinable!(cycle_count_task -> product_lot (lot_id));
+joinable!(goods_receipt -> bin_location (bin_location_id));
+joinable!(goods_receipt -> purchase_order (purchase_order_id));
+joinable!(goods_receipt -> shipping_notice (notice_id));
+joinable!(goods_receipt_line -> goods_receipt (goods_receipt_id));
+joinable!(goods_receipt_line -> product_lot (lot_id));
+joinable!(goods_receipt_line -> purchase_order_line (purchase_order_line_id));
+joinable!(goods_receipt_line -> stock_movement (movement_id));
+joinable!(home_location -> bin_location (bin_location_id));
+joinable!(home_location -> product (product_id));
+joinable!(inspection -> bin_location (bin_location_id));
+joinable!(inspection -> goods_receipt_line (goods_receipt_line_id));
+joinable!(inspection -> product (product_id));
+joinable!(inspection -> product_lot (lot_id));
+joinable!(inventory_balance -> bin_location (bin_location_id));
+joinable!(inventory_balance -> product (product_id));
+joinable!(inventory_balance -> product_lot (lot_id));
+joinable!(inventory_balance -> warehouse (warehouse_id));
+joinable!(pick_face -> bin_location (bin_location_id));
+joinable!(pick_face -> product (product_id));
+joinable!(pick_list -> sales_order (sales_order_id));
+joinable!(pick_list -> warehouse_zone (zone_id));
+joinable!(pick_list -> wave (wave_id));
+joinable!(pick_list_line -> pick_list (pick_list_id));
+joinable!(pick_list_line -> sales_order_allocation (sales_order_allocation_id));
+joinable!(product_barcode -> product (product_id));
+joinable!(product_category_assignment -> product (product_id));
+joinable!(product_category_assignment -> product_category (category_id));
+joinable!(product_lot -> product (product_id));
+joinable!(product_packaging -> product (product_id));
+joinable!(product_serial -> bin_location (bin_location_id));
+joinable!(product_serial -> product (product_id));
+joinable!(product_variant_axis -> product (product_id));
+joinable!(product_variant_option -> product (variant_id));
+joinable!(product_variant_option -> product_variant_axis (axis_id));
+joinable!(purchase_order -> supplier (supplier_id));
+joinable!(purchase_order -> warehouse (warehouse_id));
+joinable!(purchase_order_line -> product (product_id));
+joinable!(purchase_order_line -> purchase_order (purchase_order_id));
+joinable!(reorder_policy -> product (product_id));
+joinable!(reorder_policy -> warehouse (warehouse_id));
+joinable!(replenishment_task -> pick_face (pick_face_id));
+joinable!(replenishment_task -> product (product_id));
+joinable!(replenishment_task -> product_lot (lot_id));
+joinable!(sales_order -> customer (customer_id));
+joinable!(sales_order -> customer_address (shipping_address_id));
+joinable!(sales_order -> warehouse (warehouse_id));
+joinable!(sales_order -> wave (wave_id));
+joinable!(sales_order_allocation -> bin_location (bin_location_id));
+joinable!(sales_order_allocation -> product_lot (lot_id));
+joinable!(sales_order_allocation -> sales_order_line (sales_order_line_id));
+joinable!(sales_order_line -> product (product_id));
+joinable!(sales_order_line -> sales_order (sales_order_id));
+joinable!(shipping_notice -> purchase_order (purchase_order_id));
+joinable!(shipping_notice_line -> purchase_order_line (purchase_order_line_id));
+joinable!(shipping_notice_line -> shipping_notice (notice_id));
+joinable!(stock_movement -> product (product_id));
+joinable!(stock_movement -> product_lot (lot_id));
+joinable!(stock_movement_serial -> product_serial (serial_id));
+joinable!(stock_movement_serial -> stock_movement (movement_id));
+joinable!(stock_reservation -> product (product_id));
+joinable!(stock_reservation -> warehouse (warehouse_id));
+joinable!(stock_reservation_line -> bin_location (bin_location_id));
+joinable!(stock_reservation_line -> product_lot (lot_id));
+joinable!(stock_reservation_line -> stock_reservation (reservation_id));
+joinable!(stock_transfer -> product (product_id));
+joinable!(stock_transfer -> product_lot (lot_id));
+joinable!(warehouse_aisle -> warehouse_zone (zone_id));
+joinable!(warehouse_zone -> warehouse (warehouse_id));
+joinable!(wave -> warehouse (warehouse_id));
+joinable!(zone_restriction -> product_category (category_id));
+joinable!(zone_restriction -> warehouse_zone (zone_id));
+
+allow_tables_to_appear_in_same_query!(
+    bin_location,
+    customer,
+    customer_address,
+    cycle_count,
+    cycle_count_task,
+    goods_receipt,
+    goods_receipt_line,
+    home_location,
+    inspection,
+    inventory_balance,
+    pick_face,
+    pick_list,
+    pick_list_line,
+    product,
+    product_barcode,
+    product_category,
+    product_category_assignment,
+    product_lot,
+    product_packaging,
+    product_serial,
+    product_variant_axis,
+    product_variant_option,
+    purchase_order,
+    purchase_order_line,
+    reorder_policy,
+    replenishment_task,
+    sales_order,
+    sales_order_allocation,
+    sales_order_line,
+    shipping_notice,
+    shipping_notice_line,
+    stock_movement,
+    stock_movement_serial,
+    stock_reservation,
+    stock_reservation_line,
+    stock_transfer,
+    supplier,
+    warehouse,
+    warehouse_aisle,
+    warehouse_zone,
+    wave,
+    zone_restriction,
+);
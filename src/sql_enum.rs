@@ -0,0 +1,59 @@
+/// Declares a fieldless enum which is stored in a `varchar` column and serialized to JSON
+/// under the same snake case names, e.g.
+///
+/// ```ignore
+/// sql_enum! {
+///     pub enum LocationType {
+///         PickFace => "pick_face",
+///         Reserve => "reserve"
+///     }
+/// }
+/// ```
+macro_rules! sql_enum {
+    ($(#[$meta:meta])* pub enum $name:ident { $($variant:ident => $text:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, AsExpression, FromSqlRow, serde::Serialize, serde::Deserialize)]
+        #[sql_type = "diesel::sql_types::Text"]
+        pub enum $name {
+            $(#[serde(rename = $text)] $variant),+
+        }
+
+        impl $name {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text),+
+                }
+            }
+
+            pub fn parse(text: &str) -> Option<$name> {
+                match text {
+                    $($text => Some($name::$variant),)+
+                    _ => None
+                }
+            }
+
+            pub fn all() -> &'static [$name] {
+                &[$($name::$variant),+]
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+            fn to_sql<W: std::io::Write>(&self, out: &mut diesel::serialize::Output<W, diesel::pg::Pg>) -> diesel::serialize::Result {
+                <str as diesel::serialize::ToSql<diesel::sql_types::Text, diesel::pg::Pg>>::to_sql(self.as_str(), out)
+            }
+        }
+
+        impl diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg> for $name {
+            fn from_sql(bytes: Option<&[u8]>) -> diesel::deserialize::Result<Self> {
+                let text = <String as diesel::deserialize::FromSql<diesel::sql_types::Text, diesel::pg::Pg>>::from_sql(bytes)?;
+                $name::parse(&text).ok_or_else(|| format!("Unrecognized {} {}", stringify!($name), text).into())
+            }
+        }
+    }
+}
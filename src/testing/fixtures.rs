@@ -0,0 +1,31 @@
+use diesel::PgConnection;
+use crate::location::warehouse::models::{NewWarehouse, Warehouse};
+use crate::location::zone::models::ZoneDefinition;
+use crate::location::aisle::models::AisleDefinition;
+use crate::location::bin::models::{BinDefinition, BinLocation, Capacity, LocationType};
+use crate::product::models::{NewProduct, Product};
+
+/// Creates a warehouse with a single zone `A` and aisle `01` holding one bin per entry of
+/// `bins`, named `01`, `02`, ... in order.
+pub fn warehouse_with_bins(conn: &PgConnection, code: &str, bins: &[LocationType]) -> (Warehouse, Vec<BinLocation>) {
+    let warehouse = NewWarehouse::new(code, code).create(conn).unwrap();
+    let zone = ZoneDefinition { code: "A".to_string(), name: "zone a".to_string() }
+        .in_warehouse(&warehouse).unwrap()
+        .create(conn).unwrap();
+    let aisle = AisleDefinition { code: "01".to_string(), sequence: 1 }
+        .in_zone(&zone).unwrap()
+        .create(conn).unwrap();
+    let bins = bins.iter()
+        .enumerate()
+        .map(|(position, location_type)| {
+            BinDefinition { code: format!("{:02}", position + 1), location_type: *location_type, capacity: Capacity::default() }
+                .in_aisle(&warehouse, &zone, &aisle).unwrap()
+                .create(conn).unwrap()
+        })
+        .collect();
+    (warehouse, bins)
+}
+
+pub fn product(conn: &PgConnection, sku: &str) -> Product {
+    NewProduct::new(sku, sku).create(conn).unwrap()
+}
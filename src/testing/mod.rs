@@ -1,3 +1,6 @@
+#[cfg(test)]
+pub mod fixtures;
+
 use testcontainers::{Image, Docker, Container, RunArgs};
 use std::collections::HashMap;
 use testcontainers::core::Port;